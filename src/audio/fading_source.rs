@@ -0,0 +1,147 @@
+use rodio::Source;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Fade outs of the sources played on a queue. Each source gets its own generation and only
+/// fades when asked for its generation, so a source started during a fade plays untouched
+#[derive(Debug, Default)]
+pub struct FadeControl {
+    generation: AtomicU64,
+    faded_generation: AtomicU64,
+    fade_ms: AtomicU64,
+}
+
+impl FadeControl {
+    /// Generation of the next source played on the queue
+    pub fn next_generation(&self) -> u64 {
+        self.generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Fades out the last source played on the queue in the given time, then ends it
+    pub fn fade_out(&self, duration: Duration) {
+        self.fade_ms.store(
+            u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            Ordering::SeqCst,
+        );
+        self.faded_generation
+            .store(self.generation.load(Ordering::SeqCst), Ordering::SeqCst);
+    }
+}
+
+pub struct FadingSource<S> {
+    source: S,
+    control: Arc<FadeControl>,
+    generation: u64,
+    /// Samples left before the end of the fade, none until it starts
+    remaining: Option<u64>,
+    total: u64,
+}
+
+impl<S> FadingSource<S>
+where
+    S: Source<Item = f32>,
+{
+    pub fn new(source: S, control: Arc<FadeControl>) -> Self {
+        let generation = control.next_generation();
+        Self {
+            source,
+            control,
+            generation,
+            remaining: None,
+            total: 0,
+        }
+    }
+
+    fn start_fade(&mut self) {
+        let fade_ms = self.control.fade_ms.load(Ordering::SeqCst);
+        let samples_per_ms =
+            u64::from(self.source.sample_rate()) * u64::from(self.source.channels()) / 1000;
+        self.total = (fade_ms * samples_per_ms).max(1);
+        self.remaining = Some(self.total);
+    }
+}
+
+impl<S> Iterator for FadingSource<S>
+where
+    S: Source<Item = f32>,
+{
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        if self.remaining.is_none()
+            && self.control.faded_generation.load(Ordering::Relaxed) == self.generation
+        {
+            self.start_fade();
+        }
+        let sample = self.source.next()?;
+        match self.remaining {
+            None => Some(sample),
+            Some(0) => None,
+            Some(remaining) => {
+                self.remaining = Some(remaining - 1);
+                #[allow(clippy::cast_precision_loss)] // a ratio, the precision lost is inaudible
+                Some(sample * remaining as f32 / self.total as f32)
+            }
+        }
+    }
+}
+
+impl<S> Source for FadingSource<S>
+where
+    S: Source<Item = f32>,
+{
+    fn current_span_len(&self) -> Option<usize> {
+        self.source.current_span_len()
+    }
+    fn channels(&self) -> u16 {
+        self.source.channels()
+    }
+    fn sample_rate(&self) -> u32 {
+        self.source.sample_rate()
+    }
+    fn total_duration(&self) -> Option<Duration> {
+        self.source.total_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rodio::buffer::SamplesBuffer;
+
+    /// One second of full scale samples, mono at 1 kHz: a sample per millisecond
+    fn get_source(control: &Arc<FadeControl>) -> FadingSource<SamplesBuffer> {
+        FadingSource::new(
+            SamplesBuffer::new(1, 1000, vec![1.0; 1000]),
+            control.clone(),
+        )
+    }
+
+    #[test]
+    fn fade_lowers_the_samples_then_ends_the_source() {
+        let control = Arc::new(FadeControl::default());
+        let mut source = get_source(&control);
+        assert_eq!(source.next(), Some(1.0));
+        control.fade_out(Duration::from_millis(10));
+        let faded: Vec<f32> = source.collect();
+        assert_eq!(faded.len(), 10);
+        assert!(faded.windows(2).all(|x| x[1] < x[0]));
+    }
+
+    #[test]
+    fn fade_does_not_reach_the_source_started_after_it() {
+        let control = Arc::new(FadeControl::default());
+        let mut faded = get_source(&control);
+        control.fade_out(Duration::from_millis(10));
+        let new = get_source(&control);
+        assert_eq!(faded.by_ref().count(), 10);
+        assert_eq!(new.count(), 1000);
+    }
+
+    #[test]
+    fn source_plays_whole_without_a_fade() {
+        let control = Arc::new(FadeControl::default());
+        assert!(get_source(&control).all(|x| (x - 1.0).abs() < f32::EPSILON));
+    }
+}
@@ -1,2 +1,3 @@
 pub mod audio_filter;
+pub mod fading_source;
 pub mod playback_handler;
@@ -1,5 +1,6 @@
 use crate::FilterData;
 use crate::audio::audio_filter::FilteredSource;
+use crate::audio::fading_source::{FadeControl, FadingSource};
 use crate::states::playlist_data::Track;
 use biquad::{Coefficients, DirectForm1, Q_BUTTERWORTH_F32, ToHertz, Type};
 use log::warn;
@@ -14,36 +15,50 @@ pub fn change_filter_frequency_value(
 ) {
     match filter.lock() {
         Ok(mut data) => {
-            let fs = 44100.;
             let next_perc = if data.previous_filter_percentage + value <= 1. {
                 1.
             } else {
                 data.previous_filter_percentage + value
             };
-            let f_val = fs / 100. * next_perc;
-            if let Ok(coeffs) = Coefficients::<f32>::from_params(
-                filter_type,
-                fs.hz(),
-                if f_val < fs / 2. { f_val } else { fs / 2. }.hz(),
-                Q_BUTTERWORTH_F32,
-            ) {
-                data.previous_filter_percentage = next_perc;
-                data.filter_type = filter_type;
-                if let Ok(mut f) = data.filter.lock() {
-                    *f = DirectForm1::<f32>::new(coeffs);
-                } else {
-                    warn!("Failed to get FilterData, cannot change filter frequency");
-                }
-            } else {
-                warn!(
-                    "Failed to get coeffs to change filter value, cannot change filter frequency"
-                );
-            }
+            apply_filter_percentage(&mut data, next_perc, filter_type);
         }
         _ => warn!("Failed to get filter data lock, cannot change filter frequency"),
     }
 }
 
+/// Moves the filter straight to the given percentage, used to restore a saved state
+pub fn set_filter_frequency_value(
+    filter: &Arc<Mutex<FilterData>>,
+    percentage: f32,
+    filter_type: Type<f32>,
+) {
+    match filter.lock() {
+        Ok(mut data) => apply_filter_percentage(&mut data, percentage.max(1.), filter_type),
+        _ => warn!("Failed to get filter data lock, cannot set filter frequency"),
+    }
+}
+
+fn apply_filter_percentage(data: &mut FilterData, percentage: f32, filter_type: Type<f32>) {
+    let fs = 44100.;
+    let f_val = fs / 100. * percentage;
+    if let Ok(coeffs) = Coefficients::<f32>::from_params(
+        filter_type,
+        fs.hz(),
+        if f_val < fs / 2. { f_val } else { fs / 2. }.hz(),
+        Q_BUTTERWORTH_F32,
+    ) {
+        data.previous_filter_percentage = percentage;
+        data.filter_type = filter_type;
+        if let Ok(mut f) = data.filter.lock() {
+            *f = DirectForm1::<f32>::new(coeffs);
+        } else {
+            warn!("Failed to get FilterData, cannot change filter frequency");
+        }
+    } else {
+        warn!("Failed to get coeffs to change filter value, cannot change filter frequency");
+    }
+}
+
 pub fn change_volume(sink: &Sink, value: f32) -> f32 {
     sink.set_volume(if value <= 0. { 0. } else { value.min(1.) });
     sink.volume()
@@ -87,13 +102,24 @@ pub fn play_track(
     sink: &Sink,
     file_path: &str,
     filter: Option<&Arc<Mutex<FilterData>>>,
+    gain: Option<f32>,
+    repeat: bool,
+    fade: Option<&Arc<FadeControl>>,
 ) -> Result<Track, Box<dyn Error>> {
     sink.stop();
     sink.clear();
 
     let file = std::fs::File::open(file_path)?;
-    let source = rodio::Decoder::try_from(file)?;
-    let track_length = source.total_duration();
+    let decoder = rodio::Decoder::try_from(file)?;
+    let track_length = decoder.total_duration();
+    let mut source: Box<dyn Source<Item = f32> + Send> = if repeat {
+        Box::new(decoder.amplify(gain.unwrap_or(1.)).repeat_infinite())
+    } else {
+        Box::new(decoder.amplify(gain.unwrap_or(1.)))
+    };
+    if let Some(fade) = fade {
+        source = Box::new(FadingSource::new(source, fade.clone()));
+    }
     if let Some(filter) = filter {
         match filter.lock() {
             Ok(f) => sink.append(FilteredSource {
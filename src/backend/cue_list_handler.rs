@@ -0,0 +1,174 @@
+use crate::audio::playback_handler;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::backend::scene_handler::SceneHandler;
+use crate::states::cue_list::{Cue, CueAction, CueChannel};
+use crate::states::music_state::MusicState;
+use crate::states::sound_state::SoundState;
+use log::{info, warn};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Waits are split in slices, so that a reset stops them quickly
+const WAIT_SLICE: Duration = Duration::from_millis(50);
+const FADE_STEP: Duration = Duration::from_millis(50);
+
+/// Incremented by every reset, the running cues stop as soon as it changes
+static LAST_RESET: AtomicUsize = AtomicUsize::new(0);
+
+pub struct CueListHandler;
+
+impl CueListHandler {
+    /// Fires the cue under the playhead and moves the playhead to the next one
+    pub fn go(state: &MusicState) {
+        let Ok(mut cue_list) = state.cue_list.lock() else {
+            warn!("Failed to get a lock on the cue list. Will not fire the next cue");
+            return;
+        };
+        let index = cue_list.playhead;
+        let Some(cue) = cue_list.cues.get(index).cloned() else {
+            info!("End of the cue list reached");
+            return;
+        };
+        cue_list.playhead += 1;
+        drop(cue_list);
+        let reset_id = LAST_RESET.load(Ordering::Relaxed);
+        let state = state.clone();
+        thread::spawn(move || Self::run_from(index, cue, &state, reset_id));
+    }
+
+    /// Moves the playhead back to the first cue and stops the cues still waiting
+    pub fn reset(state: &MusicState) {
+        LAST_RESET.fetch_add(1, Ordering::Relaxed);
+        if let Ok(mut cue_list) = state.cue_list.lock() {
+            cue_list.playhead = 0;
+            info!("Cue list back to the first cue");
+        } else {
+            warn!("Failed to get a lock on the cue list. Will not reset it");
+        }
+    }
+
+    /// Runs the cue, then the cues following it on their own
+    fn run_from(mut index: usize, mut cue: Cue, state: &MusicState, reset_id: usize) {
+        loop {
+            if !Self::wait(cue.pre_wait, reset_id) {
+                return;
+            }
+            info!("Cue {}: {}", index + 1, cue.name);
+            Self::fire(cue.action, state, reset_id);
+            if !cue.auto_follow || !Self::wait(cue.post_wait, reset_id) {
+                return;
+            }
+            let Ok(mut cue_list) = state.cue_list.lock() else {
+                warn!("Failed to get a lock on the cue list. Will not follow cue {index}");
+                return;
+            };
+            // A GO during the post-wait already fired the next cue
+            if cue_list.playhead != index + 1 {
+                return;
+            }
+            let Some(next) = cue_list.cues.get(index + 1).cloned() else {
+                return;
+            };
+            cue_list.playhead += 1;
+            drop(cue_list);
+            index += 1;
+            cue = next;
+        }
+    }
+
+    /// Sleeps for the given seconds, returns false if the cue list was reset meanwhile
+    fn wait(seconds: f32, reset_id: usize) -> bool {
+        let started = Instant::now();
+        let duration = Duration::try_from_secs_f32(seconds).unwrap_or_default();
+        while let Some(remaining) = duration.checked_sub(started.elapsed()) {
+            if LAST_RESET.load(Ordering::Relaxed) != reset_id {
+                return false;
+            }
+            thread::sleep(remaining.min(WAIT_SLICE));
+        }
+        LAST_RESET.load(Ordering::Relaxed) == reset_id
+    }
+
+    fn fire(action: CueAction, state: &MusicState, reset_id: usize) {
+        match action {
+            CueAction::PlayAlbum { album } => PadHandler::play_album(album, state, None),
+            CueAction::StopMusic => {
+                if let Ok(mut data) = state.data.lock()
+                    && let Ok(audio_sinks) = state.audio_sinks.lock()
+                {
+                    playback_handler::stop_track(&audio_sinks.music_queue);
+                    data.current_playlist = None;
+                } else {
+                    warn!("Failed to get a lock on data. Will not stop the music");
+                }
+            }
+            CueAction::StartAmbience { number } | CueAction::FireSoundEffect { number } => {
+                let sound_state = SoundState::from(state);
+                if let Ok(mut data) = state.data.lock() {
+                    let is_ambience = matches!(action, CueAction::StartAmbience { .. });
+                    KeyboardHandler::play_numbered_file(
+                        is_ambience,
+                        number,
+                        &mut data,
+                        &sound_state,
+                    );
+                } else {
+                    warn!("Failed to get a lock on data. Will not play file {number}");
+                }
+            }
+            CueAction::StopAmbience => {
+                let sound_state = SoundState::from(state);
+                if let Ok(mut data) = state.data.lock() {
+                    KeyboardHandler::stop_ambience(&mut data, &sound_state);
+                }
+            }
+            CueAction::SetVolume { channel, value } => {
+                PadHandler::set_gui_knob(channel.get_knob(), value, state);
+            }
+            CueAction::Fade {
+                channel,
+                value,
+                seconds,
+            } => {
+                let state = state.clone();
+                thread::spawn(move || Self::fade(channel, value, seconds, &state, reset_id));
+            }
+            CueAction::Wait { seconds } => {
+                Self::wait(seconds, reset_id);
+            }
+            CueAction::RecallScene { number } => match number.checked_sub(1) {
+                Some(index) => SceneHandler::recall(index, state, None),
+                None => warn!("Scenes are numbered from 1, will not recall scene {number}"),
+            },
+        }
+    }
+
+    fn fade(channel: CueChannel, value: f32, seconds: f32, state: &MusicState, reset_id: usize) {
+        let knob = channel.get_knob();
+        let Some(start) = state
+            .data
+            .lock()
+            .ok()
+            .and_then(|data| data.knob_values.get(&knob).copied())
+        else {
+            warn!("Failed to read the {channel} volume, will not fade it");
+            return;
+        };
+        let duration = Duration::try_from_secs_f32(seconds).unwrap_or_default();
+        let steps = u32::try_from(duration.as_millis() / FADE_STEP.as_millis())
+            .unwrap_or(u32::MAX)
+            .max(1);
+        for step in 1..=steps {
+            if LAST_RESET.load(Ordering::Relaxed) != reset_id {
+                return;
+            }
+            let progress = step as f32 / steps as f32;
+            PadHandler::set_gui_knob(knob, (value - start).mul_add(progress, start), state);
+            if step < steps {
+                thread::sleep(FADE_STEP);
+            }
+        }
+    }
+}
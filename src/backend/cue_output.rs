@@ -0,0 +1,153 @@
+use crate::audio::playback_handler;
+use crate::states::cue_event::CueEvent;
+use crate::states::music_state::MusicState;
+use flume::{Receiver, RecvTimeoutError};
+use log::{debug, info, warn};
+use midir::{MidiOutput, MidiOutputConnection};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the music queue is checked for track changes
+const TRACK_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Opens the output port named in the settings and forwards the cues matching the mapping table.
+/// Does nothing if no port is configured
+pub fn start_cue_output(rx_cue: Receiver<CueEvent>, music_state: &MusicState) {
+    let port_name = music_state
+        .data
+        .lock()
+        .ok()
+        .and_then(|data| {
+            data.settings_data
+                .lock()
+                .ok()
+                .map(|x| x.cue_output_port.clone())
+        })
+        .unwrap_or_default();
+    if port_name.is_empty() {
+        debug!("No cue output port configured");
+        return;
+    }
+    match open_output_port(&port_name) {
+        Ok(connection) => {
+            info!("Sending cues to MIDI port '{port_name}'");
+            let music_state = music_state.clone();
+            thread::spawn(move || {
+                handle_cues(&rx_cue, &music_state, &Arc::new(Mutex::new(connection)));
+            });
+        }
+        Err(e) => warn!("Could not open cue output port '{port_name}': {e}"),
+    }
+}
+
+/// Connects to the first output port whose name contains `port_name`
+fn open_output_port(port_name: &str) -> anyhow::Result<MidiOutputConnection> {
+    let midi_out = MidiOutput::new("teatro-cues")?;
+    let port = midi_out
+        .ports()
+        .into_iter()
+        .find(|p| midi_out.port_name(p).is_ok_and(|n| n.contains(port_name)))
+        .ok_or_else(|| anyhow::anyhow!("no port matches the name"))?;
+    midi_out
+        .connect(&port, "teatro-cue-output")
+        .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+/// Forwards the cues as they come, the music queue is checked for track changes on its own
+/// timer so that a steady flow of cues does not hide them
+fn handle_cues(
+    rx_cue: &Receiver<CueEvent>,
+    music_state: &MusicState,
+    connection: &Arc<Mutex<MidiOutputConnection>>,
+) {
+    let mut last_track = None;
+    let mut next_poll = Instant::now() + TRACK_POLL_INTERVAL;
+    loop {
+        match rx_cue.recv_deadline(next_poll) {
+            Ok(event) => send_cue(event, music_state, connection),
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if Instant::now() < next_poll {
+            continue;
+        }
+        next_poll = Instant::now() + TRACK_POLL_INTERVAL;
+        let track = get_current_track(music_state);
+        if let Some(current) = &track
+            && track != last_track
+        {
+            let event = CueEvent::TrackChanged {
+                track: current.position,
+            };
+            send_cue(event, music_state, connection);
+        }
+        last_track = track;
+    }
+}
+
+/// Track being played, told apart by its album and file so that another album
+/// reaching the same position is a change too
+#[derive(Debug, PartialEq, Eq)]
+struct PlayingTrack {
+    album: Option<usize>,
+    file_path: String,
+    position: u64,
+}
+
+fn get_current_track(music_state: &MusicState) -> Option<PlayingTrack> {
+    let remaining = playback_handler::get_n_of_remaining_tracks(
+        &music_state.audio_sinks.lock().ok()?.music_queue,
+    );
+    if remaining == 0 {
+        return None;
+    }
+    let data = music_state.data.lock().ok()?;
+    let tracks = &data.current_playlist.as_ref()?.tracks;
+    let position = (tracks.len() as u64).saturating_sub(remaining);
+    Some(PlayingTrack {
+        album: data.last_pad_pressed,
+        file_path: tracks.get(position as usize)?.file_path.clone(),
+        position,
+    })
+}
+
+fn send_cue(
+    event: CueEvent,
+    music_state: &MusicState,
+    connection: &Arc<Mutex<MidiOutputConnection>>,
+) {
+    let messages: Vec<_> = music_state
+        .data
+        .lock()
+        .ok()
+        .and_then(|data| {
+            data.settings_data.lock().ok().map(|x| {
+                x.cue_mappings
+                    .iter()
+                    .filter(|m| m.on.matches(event))
+                    .flat_map(|m| m.send.clone())
+                    .collect()
+            })
+        })
+        .unwrap_or_default();
+    for message in messages {
+        debug!("Cue {event:?}: sending {message:?}");
+        if let Ok(mut out) = connection.lock()
+            && let Err(e) = out.send(&message.to_midi())
+        {
+            warn!("Failed to send cue message: {e}");
+        }
+        if let Some((release, delay)) = message.get_release() {
+            let connection = connection.clone();
+            thread::spawn(move || {
+                thread::sleep(delay);
+                if let Ok(mut out) = connection.lock()
+                    && let Err(e) = out.send(&release)
+                {
+                    warn!("Failed to send cue release message: {e}");
+                }
+            });
+        }
+    }
+}
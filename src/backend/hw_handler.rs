@@ -1,4 +1,5 @@
 use crate::FilterData;
+use crate::audio::fading_source::FadeControl;
 use crate::audio::playback_handler;
 use crate::states::audio_sinks::AudioSinks;
 use crate::states::playlist_data::{PlaylistData, Track};
@@ -38,6 +39,7 @@ pub trait MidiHandler {
                 .enumerate()
                 .map(|(i, x)| Track {
                     file_path: x.file_path.clone(),
+                    title: x.title.clone(),
                     track_length: x.track_length,
                     elapsed_seconds: if i == curr_track_number as usize {
                         playback_handler::get_current_track_elapsed_time(sink)
@@ -55,6 +57,9 @@ pub trait MidiHandler {
         sound_queue: &Sink,
         filter: &Arc<Mutex<FilterData>>,
         volume: Option<f32>,
+        gain: Option<f32>,
+        repeat: bool,
+        fade: Option<&Arc<FadeControl>>,
     ) -> Option<PlaylistData> {
         let mut tracks = vec![];
         files.first().map(|first_track| {
@@ -62,9 +67,14 @@ pub trait MidiHandler {
             if let Some(v) = volume {
                 playback_handler::change_volume(sound_queue, v);
             }
-            if let Ok(track) =
-                playback_handler::play_track(sound_queue, first_track.as_str(), Some(filter))
-            {
+            if let Ok(track) = playback_handler::play_track(
+                sound_queue,
+                first_track.as_str(),
+                Some(filter),
+                gain,
+                repeat,
+                fade,
+            ) {
                 tracks.push(track);
             }
             files.iter().skip(1).for_each(|file| {
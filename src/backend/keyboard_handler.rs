@@ -1,18 +1,70 @@
 use crate::backend::hw_handler::MidiHandler;
-use crate::os_explorer::explorer::get_all_files_in_folder;
+use crate::backend::pad_handler::send_cue;
 use crate::states::audio_sinks::AudioSinks;
+use crate::states::button_states::ToggleStates;
+use crate::states::cue_event::CueEvent;
+use crate::states::midi_macro::MacroAction;
 use crate::states::sound_state::SoundState;
+use crate::states::trigger_mode::{SoundingKey, TriggerMode};
 use crate::states::visualizer::RuntimeData;
 use anyhow::bail;
 use flume::Sender;
-use log::{debug, warn};
+use log::{debug, info, warn};
+use midi_msg::{ChannelVoiceMsg, MidiMsg};
 use ramidier::enums::input_group::KeyboardInputGroup;
 use ramidier::io::input_data::MidiInputData;
 use ramidier::io::output::ChannelOutput;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
-const fn is_ambience_key(k: u8) -> bool {
+/// Number of keys on the keyboard
+pub const N_OF_KEYS: u8 = 25;
+/// White (true) and black (false) keys of an octave, starting from C
+pub const KEY_PATTERN: [bool; 12] = [
+    true, false, true, false, true, true, false, true, false, true, false, true,
+];
+/// Sound effects addressable in a single bank (white keys)
+const SOUND_EFFECTS_PER_BANK: usize = 15;
+/// Ambiences addressable in a single bank (black keys)
+const AMBIENCES_PER_BANK: usize = 10;
+const FADE_OUT_TIME: Duration = Duration::from_millis(300);
+/// Velocity of the files played without a key press
+const NUMBERED_FILE_VELOCITY: u8 = 127;
+
+/// Files played by the ambience or sound effect keys, in the order of the keys: the audio
+/// files of the folder, replaced by the files assigned in the settings
+fn get_key_files(data: &RuntimeData, is_ambience: bool) -> Vec<Option<PathBuf>> {
+    let Ok((folder, assignments)) = data.settings_data.lock().map(|x| {
+        if is_ambience {
+            (x.ambience_folder.clone(), x.ambience_assignments.clone())
+        } else {
+            (
+                x.sound_effect_folder.clone(),
+                x.sound_effect_assignments.clone(),
+            )
+        }
+    }) else {
+        return vec![];
+    };
+    let mut files: Vec<Option<PathBuf>> = data
+        .library_index
+        .lock()
+        .map(|x| x.get_files(&folder))
+        .unwrap_or_default()
+        .into_iter()
+        .map(Some)
+        .collect();
+    for assignment in assignments.into_iter().filter(|x| x.number > 0) {
+        if files.len() < assignment.number {
+            files.resize(assignment.number, None);
+        }
+        files[assignment.number - 1] = Some(PathBuf::from(assignment.file));
+    }
+    files
+}
+
+pub const fn is_ambience_key(k: u8) -> bool {
     matches!(k, 2 | 4 | 7 | 9 | 11 | 14 | 16 | 19 | 21 | 23)
 }
 
@@ -53,6 +105,31 @@ const fn map_key_to_white_key_index(key: u8) -> u8 {
     }
 }
 
+/// Number of the ambience or sound effect played by the key in the bank, counting from 1
+pub const fn get_key_number(key: u8, bank: usize) -> Option<usize> {
+    if is_ambience_key(key) {
+        match map_key_to_black_key_index(key) {
+            0 => None,
+            i => Some(bank * AMBIENCES_PER_BANK + i as usize),
+        }
+    } else {
+        match map_key_to_white_key_index(key) {
+            0 => None,
+            i => Some(bank * SOUND_EFFECTS_PER_BANK + i as usize),
+        }
+    }
+}
+
+/// Key playing the given sound effect of the current bank, counting from 1
+pub fn get_sound_effect_key(index: u8) -> Option<u8> {
+    (1..=N_OF_KEYS).find(|k| !is_ambience_key(*k) && map_key_to_white_key_index(*k) == index)
+}
+
+/// Key playing the given ambience of the current bank, counting from 1
+pub fn get_ambience_key(index: u8) -> Option<u8> {
+    (1..=N_OF_KEYS).find(|k| is_ambience_key(*k) && map_key_to_black_key_index(*k) == index)
+}
+
 #[derive(Debug)]
 pub struct KeyboardHandler;
 
@@ -76,74 +153,294 @@ impl MidiHandler for KeyboardHandler {
         state: &mut Self::State,
     ) {
         debug!("{stamp}: {msg:?}");
-        if msg.value != 0 {
-            Self::handle_input(msg.input_group, state);
-        }
+        Self::handle_midi_input(msg, state);
     }
 }
 
 impl KeyboardHandler {
-    pub fn handle_input(input_group: KeyboardInputGroup, state: &SoundState) {
+    /// Handles a decoded message of the keyboard channel, whatever port it came from
+    pub fn handle_midi_input(msg: &MidiInputData<KeyboardInputGroup>, state: &SoundState) {
+        if let Ok(mut recorder) = state.macro_recorder.lock() {
+            let KeyboardInputGroup::Key(key) = msg.input_group;
+            recorder.record(MacroAction::Keyboard {
+                key,
+                velocity: msg.value,
+            });
+        }
+        if msg.value == 0 {
+            Self::handle_release(msg.input_group, state);
+        } else {
+            Self::handle_input(msg.input_group, msg.value, state);
+        }
+    }
+
+    /// Decodes a keyboard message keeping the note velocity as value, 0 means the key was released
+    pub fn decode(msg: &MidiMsg) -> Option<MidiInputData<KeyboardInputGroup>> {
+        match msg {
+            MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::NoteOn { note, velocity },
+            } => KeyboardInputGroup::try_from(*note)
+                .map(|input_group| MidiInputData {
+                    channel: *channel,
+                    input_group,
+                    value: *velocity,
+                })
+                .ok(),
+            MidiMsg::ChannelVoice {
+                channel,
+                msg: ChannelVoiceMsg::NoteOff { note, velocity: _ },
+            } => KeyboardInputGroup::try_from(*note)
+                .map(|input_group| MidiInputData {
+                    channel: *channel,
+                    input_group,
+                    value: 0,
+                })
+                .ok(),
+            _ => None,
+        }
+    }
+
+    pub fn handle_input(input_group: KeyboardInputGroup, velocity: u8, state: &SoundState) {
         match input_group {
             KeyboardInputGroup::Key(k) => {
-                if let Ok(data) = state.data.lock() {
-                    if let Ok(audio_sinks) = state.audio_sinks.lock() {
-                        if let Err(e) = Self::play_sound_file(k, &data, &audio_sinks, state) {
-                            warn!("Error while trying to play sound file: {e}");
-                        }
+                if let Ok(mut data) = state.data.lock() {
+                    if data.button_states.contains(ToggleStates::SHIFT) {
+                        Self::select_bank(k, &mut data);
+                    } else {
+                        Self::play_key(k, velocity, &mut data, state);
                     }
                 }
             }
         }
     }
 
+    /// Plays the file assigned to the key in the current bank
+    pub fn play_key(key: u8, velocity: u8, data: &mut RuntimeData, state: &SoundState) {
+        if let Ok(audio_sinks) = state.audio_sinks.lock() {
+            if let Err(e) = Self::play_sound_file(key, velocity, data, &audio_sinks, state) {
+                warn!("Error while trying to play sound file: {e}");
+            }
+        } else {
+            warn!("Failed to get audio sink lock, cannot play key {key}");
+        }
+    }
+
+    /// Plays the Nth ambience or sound effect of its folder, counting from 1, switching to its bank
+    pub fn play_numbered_file(
+        is_ambience: bool,
+        number: usize,
+        data: &mut RuntimeData,
+        state: &SoundState,
+    ) {
+        let per_bank = if is_ambience {
+            AMBIENCES_PER_BANK
+        } else {
+            SOUND_EFFECTS_PER_BANK
+        };
+        let Some(index) = number.checked_sub(1) else {
+            warn!("Files are numbered from 1, will not play file {number}");
+            return;
+        };
+        let position = (index % per_bank) as u8 + 1;
+        let key = if is_ambience {
+            get_ambience_key(position)
+        } else {
+            get_sound_effect_key(position)
+        };
+        data.keyboard_bank = index / per_bank;
+        Self::update_key_labels(data);
+        if let Some(key) = key
+            && data.keyboard_bank == index / per_bank
+        {
+            Self::play_key(key, NUMBERED_FILE_VELOCITY, data, state);
+        } else {
+            warn!("There is no file {number} to play");
+        }
+    }
+
+    /// Fades out the ambience currently playing, if any
+    pub fn stop_ambience(data: &mut RuntimeData, state: &SoundState) {
+        if let Some(sounding_key) = data.sounding_ambience.take() {
+            Self::fade_out(sounding_key.key, state);
+        }
+    }
+
+    /// Fades out the file played by a key in gate mode
+    pub fn handle_release(input_group: KeyboardInputGroup, state: &SoundState) {
+        match input_group {
+            KeyboardInputGroup::Key(k) => {
+                if let Ok(mut data) = state.data.lock() {
+                    let sounding_key = if is_ambience_key(k) {
+                        &mut data.sounding_ambience
+                    } else {
+                        &mut data.sounding_sound_effect
+                    };
+                    if sounding_key.is_some_and(|x| x.key == k && x.mode == TriggerMode::Gate) {
+                        *sounding_key = None;
+                        Self::fade_out(k, state);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Fades out the file the key started, a file started since on the same queue keeps playing
+    fn fade_out(key: u8, state: &SoundState) {
+        if let Ok(audio_sinks) = state.audio_sinks.lock() {
+            if is_ambience_key(key) {
+                audio_sinks.ambience_fade.fade_out(FADE_OUT_TIME);
+            } else {
+                audio_sinks.sound_effect_fade.fade_out(FADE_OUT_TIME);
+            }
+        } else {
+            warn!("Failed to get audio sink lock, cannot fade out key {key}");
+        }
+    }
+
+    /// Shift + the Nth white key selects the Nth bank
+    fn select_bank(key: u8, data: &mut RuntimeData) {
+        let w_k = map_key_to_white_key_index(key);
+        if w_k == 0 {
+            debug!("Black key {key} does not select a bank, ignoring it");
+        } else if (w_k as usize) <= data.keyboard_bank_count {
+            data.keyboard_bank = w_k as usize - 1;
+            info!("Selected keyboard bank {w_k}");
+            Self::update_key_labels(data);
+        } else {
+            warn!("Keyboard bank {w_k} is empty, will not select it");
+        }
+    }
+
+    pub fn change_bank(step: isize, data: &Arc<Mutex<RuntimeData>>) {
+        if let Ok(mut data) = data.lock() {
+            data.keyboard_bank = data
+                .keyboard_bank
+                .saturating_add_signed(step)
+                .min(data.keyboard_bank_count - 1);
+            info!(
+                "Selected keyboard bank {} of {}",
+                data.keyboard_bank + 1,
+                data.keyboard_bank_count
+            );
+            Self::update_key_labels(&mut data);
+        } else {
+            warn!("Failed to get a lock on data. Will not change keyboard bank");
+        }
+    }
+
+    /// Reads the sound folders and labels every key with the file it plays in the current bank,
+    /// along with the trigger modes of the files
+    pub fn update_key_labels(data: &mut RuntimeData) {
+        let sound_effects = get_key_files(data, false);
+        let ambiences = get_key_files(data, true);
+        data.trigger_modes = TriggerMode::load_for_files(
+            sound_effects
+                .iter()
+                .chain(&ambiences)
+                .filter_map(Option::as_deref),
+        );
+
+        data.keyboard_bank_count = sound_effects
+            .len()
+            .div_ceil(SOUND_EFFECTS_PER_BANK)
+            .max(ambiences.len().div_ceil(AMBIENCES_PER_BANK))
+            .max(1);
+        data.keyboard_bank = data.keyboard_bank.min(data.keyboard_bank_count - 1);
+
+        let bank = data.keyboard_bank;
+        data.key_labels = (1..=N_OF_KEYS)
+            .map(|key| {
+                let files = if is_ambience_key(key) {
+                    &ambiences
+                } else {
+                    &sound_effects
+                };
+                get_key_number(key, bank)
+                    .and_then(|x| files.get(x - 1))
+                    .and_then(Option::as_ref)
+                    .and_then(|f| f.file_stem())
+                    .and_then(|f| f.to_str())
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            })
+            .collect();
+    }
+
     fn play_sound_file(
         key: u8,
-        data: &RuntimeData,
+        velocity: u8,
+        data: &mut RuntimeData,
         audio_sinks: &AudioSinks,
         state: &SoundState,
     ) -> anyhow::Result<()> {
-        let w_k = map_key_to_white_key_index(key);
-        let (index, folder, filter, volume) = if w_k > 0 {
+        let Some(number) = get_key_number(key, data.keyboard_bank) else {
+            bail!("Not a valid keyboard key {key}")
+        };
+        let (filter, volume) = if is_ambience_key(key) {
+            (state.ambience_filter.clone(), data.get_ambience_volume())
+        } else {
             (
-                w_k,
-                data.settings_data.lock().map_or_else(
-                    |_| "sound_effect".to_string(),
-                    |x| x.sound_effect_folder.clone(),
-                ),
                 state.sound_effect_filter.clone(),
                 data.get_sound_effect_volume(),
             )
-        } else {
-            let b_k = map_key_to_black_key_index(key);
-            if b_k == 0 {
-                bail!("Not a valid keyboard key {key}")
-            }
-            (
-                b_k,
-                data.settings_data
-                    .lock()
-                    .map_or_else(|_| "ambience".to_string(), |x| x.ambience_folder.clone()),
-                state.ambience_filter.clone(),
-                data.get_ambience_volume(),
-            )
         };
-        if let Ok(file_path) = get_all_files_in_folder(&PathBuf::from(&folder))
-            && let Some(file_path) = file_path.get((index - 1) as usize)
-        {
+        if let Some(Some(file_path)) = get_key_files(data, is_ambience_key(key)).get(number - 1) {
             if let Some(file_str) = file_path.to_str() {
-                let queue = if is_ambience_key(key) {
-                    &audio_sinks.ambience_queue
+                let (queue, fade, sounding_key) = if is_ambience_key(key) {
+                    (
+                        &audio_sinks.ambience_queue,
+                        &audio_sinks.ambience_fade,
+                        &mut data.sounding_ambience,
+                    )
                 } else {
-                    &audio_sinks.sound_effect_queue
+                    (
+                        &audio_sinks.sound_effect_queue,
+                        &audio_sinks.sound_effect_fade,
+                        &mut data.sounding_sound_effect,
+                    )
                 };
-                Self::play_song(&[file_str.to_string()], queue, &filter, volume);
+                let mode = data
+                    .trigger_modes
+                    .get(file_path)
+                    .copied()
+                    .unwrap_or_default();
+                if mode == TriggerMode::Toggle
+                    && sounding_key.is_some_and(|x| x.key == key)
+                    && !queue.empty()
+                {
+                    *sounding_key = None;
+                    fade.fade_out(FADE_OUT_TIME);
+                    return Ok(());
+                }
+                *sounding_key = Some(SoundingKey { key, mode });
+                send_cue(
+                    &state.tx_cue,
+                    if is_ambience_key(key) {
+                        CueEvent::AmbienceStarted { key }
+                    } else {
+                        CueEvent::SoundEffectStarted { key }
+                    },
+                );
+                let gain = data
+                    .settings_data
+                    .lock()
+                    .map_or(1., |x| x.velocity_curve.gain(velocity));
+                Self::play_song(
+                    &[file_str.to_string()],
+                    queue,
+                    &filter,
+                    volume,
+                    Some(gain),
+                    mode != TriggerMode::OneShot,
+                    Some(fade),
+                );
                 Ok(())
             } else {
                 bail!("Invalid UTF-8 in file path")
             }
         } else {
-            bail!("No audio for key {key} in {folder}");
+            bail!("No audio for key {key} in bank {}", data.keyboard_bank + 1);
         }
     }
 }
@@ -0,0 +1,84 @@
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::states::library_index::LibraryIndex;
+use crate::states::music_state::MusicState;
+use log::{info, warn};
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+pub struct LibraryHandler;
+
+impl LibraryHandler {
+    /// Index cached by the last run, empty when there is none
+    pub fn load(path: &str) -> Arc<Mutex<LibraryIndex>> {
+        let index = LibraryIndex::load_from_file(path).unwrap_or_else(|_| {
+            let mut index = LibraryIndex::default();
+            index.path = path.to_string();
+            index
+        });
+        Arc::new(Mutex::new(index))
+    }
+
+    /// Scans the library folders in the background, only new and changed files are read.
+    /// The keys are labelled again once it is done
+    pub fn start_scan(state: &MusicState) {
+        let state = state.clone();
+        thread::spawn(move || Self::scan(&state));
+    }
+
+    fn scan(state: &MusicState) {
+        let Some((index, folders)) = state.data.lock().ok().and_then(|data| {
+            let folders = data.settings_data.lock().ok()?.get_library_folders();
+            Some((data.library_index.clone(), folders))
+        }) else {
+            warn!("Failed to read the library folders, will not scan them");
+            return;
+        };
+        // Tags are read without holding the index, pads and keys keep working meanwhile
+        let Ok(snapshot) = index.lock().map(|mut x| {
+            x.begin_scan();
+            x.clone()
+        }) else {
+            warn!("Failed to get a lock on the library index, will not scan the folders");
+            return;
+        };
+        let scans: Vec<_> = folders
+            .iter()
+            .filter_map(|x| fs::canonicalize(x).ok())
+            .map(|root| {
+                let files = snapshot.scan(&root);
+                (root, files)
+            })
+            .collect();
+        if let Ok(mut index) = index.lock() {
+            index.apply_scan(scans);
+            info!("Library index holds {} audio files", index.files.len());
+        }
+        Self::save(&index);
+        if let Ok(mut data) = state.data.lock() {
+            KeyboardHandler::update_key_labels(&mut data);
+            PadHandler::update_gui(&state.tx_data, &data);
+        }
+    }
+
+    /// Applies the files created, changed, removed or renamed since the scan
+    pub fn update_paths(index: &Arc<Mutex<LibraryIndex>>, paths: &[std::path::PathBuf]) {
+        if let Ok(mut index) = index.lock() {
+            for path in paths {
+                index.update_path(path);
+            }
+        } else {
+            warn!("Failed to get a lock on the library index, it will miss the last changes");
+        }
+    }
+
+    pub fn save(index: &Arc<Mutex<LibraryIndex>>) {
+        if let Ok(index) = index.lock()
+            && let Err(e) = index.write_to_file()
+        {
+            warn!("Failed to save the library index to {}: {e}", index.path);
+        }
+    }
+}
@@ -5,7 +5,7 @@ use crate::states::music_state::MusicState;
 use crate::states::sound_state::SoundState;
 use crate::{MidiInputChannels, MidiOutputChannels};
 use log::debug;
-use ramidier::enums::input_group::{KeyboardChannel, PadsAndKnobsChannel};
+use ramidier::enums::input_group::PadsAndKnobsChannel;
 use ramidier::enums::led_light::color::LedColor;
 use ramidier::enums::led_light::mode::LedMode;
 use ramidier::enums::message_filter::MessageFilter;
@@ -65,18 +65,20 @@ pub fn run(
         music_state.clone(),
         PadsAndKnobsChannel,
     )?;
-    let _conn_keyboard = in_channels.midi_in_keyboard.listen(
+    // Keyboard messages are decoded here, ramidier would drop the note velocity
+    let _conn_keyboard = in_channels.midi_in_keyboard.listen_midi_msg(
         Some("midir-keyboard-read-input"),
-        move |stamp, rx_data, data| {
-            KeyboardHandler::listener(
-                out_channels.keyboard_midi_out.clone(),
-                stamp,
-                &rx_data,
-                data,
-            );
+        move |stamp, midi_msg, data| {
+            if let Some(rx_data) = KeyboardHandler::decode(&midi_msg) {
+                KeyboardHandler::listener(
+                    out_channels.keyboard_midi_out.clone(),
+                    stamp,
+                    &rx_data,
+                    data,
+                );
+            }
         },
         sound_state.clone(),
-        KeyboardChannel,
     )?;
     // Just keep the program alive - MIDI callbacks will handle input
     debug!("MIDI listeners active");
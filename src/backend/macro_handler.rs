@@ -0,0 +1,149 @@
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::comms::command::CommsCommand;
+use crate::states::midi_macro::{MacroAction, MidiMacro};
+use crate::states::music_state::MusicState;
+use crate::states::sound_state::SoundState;
+use flume::{Receiver, Sender};
+use log::{debug, info, warn};
+use ramidier::enums::input_group::{KeyboardInputGroup, PadsAndKnobsInputGroup};
+use ramidier::io::output::ChannelOutput;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Control number of a pads and knobs input, the inverse of `PadsAndKnobsInputGroup::try_from`
+pub fn get_control_number(input_group: PadsAndKnobsInputGroup) -> u8 {
+    match input_group {
+        // ramidier decodes Start from note 93 but encodes it back as 92
+        PadsAndKnobsInputGroup::Start => 93,
+        _ => input_group.into(),
+    }
+}
+
+pub struct MacroHandler;
+
+impl MacroHandler {
+    /// Replays the macros bound to the pads received on `rx_macro`, each one in its own thread
+    pub fn handle_macro_requests(
+        rx_macro: &Receiver<u8>,
+        tx_command: &Sender<CommsCommand>,
+        music_state: &MusicState,
+        sound_state: &SoundState,
+        midi_out: Option<&Arc<Mutex<ChannelOutput>>>,
+    ) {
+        while let Ok(pad) = rx_macro.recv() {
+            let midi_macro = music_state
+                .macro_library
+                .lock()
+                .ok()
+                .and_then(|x| x.macros.get(&pad).cloned());
+            if let Some(midi_macro) = midi_macro {
+                info!("Playing macro bound to pad {pad}");
+                let tx_command = tx_command.clone();
+                let music_state = music_state.clone();
+                let sound_state = sound_state.clone();
+                let midi_out = midi_out.cloned();
+                thread::spawn(move || {
+                    Self::play_macro(
+                        &midi_macro,
+                        &tx_command,
+                        &music_state,
+                        &sound_state,
+                        midi_out.as_ref(),
+                    );
+                });
+            } else {
+                warn!("No macro bound to pad {pad}");
+            }
+        }
+    }
+
+    /// Replays the macro bound to the pad at another speed from now on, and saves it
+    pub fn set_time_scale(pad: u8, time_scale: f32, music_state: &MusicState) {
+        let Ok(mut library) = music_state.macro_library.lock() else {
+            warn!("Failed to get a lock on the macros, will not change their timing");
+            return;
+        };
+        let Some(midi_macro) = library.macros.get_mut(&pad) else {
+            warn!("No macro bound to pad {pad}");
+            return;
+        };
+        midi_macro.time_scale = time_scale.max(0.);
+        info!("Macro bound to pad {pad} now plays with time scale {time_scale}");
+        if let Err(e) = library.write_to_file() {
+            warn!("Failed to save macros: {e}");
+        }
+    }
+
+    fn play_macro(
+        midi_macro: &MidiMacro,
+        tx_command: &Sender<CommsCommand>,
+        music_state: &MusicState,
+        sound_state: &SoundState,
+        midi_out: Option<&Arc<Mutex<ChannelOutput>>>,
+    ) {
+        let started = Instant::now();
+        let time_scale = midi_macro.time_scale.max(0.);
+        for event in &midi_macro.events {
+            let due = Duration::from_millis(event.offset_ms).mul_f32(time_scale);
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+            debug!("Macro event: {event:?}");
+            match &event.action {
+                MacroAction::Pad { control, value } => {
+                    Self::replay_pad_action(*control, *value, music_state, midi_out);
+                }
+                MacroAction::Keyboard { key, velocity } => {
+                    let input_group = KeyboardInputGroup::Key(*key);
+                    if *velocity > 0 {
+                        KeyboardHandler::handle_input(input_group, *velocity, sound_state);
+                    } else {
+                        KeyboardHandler::handle_release(input_group, sound_state);
+                    }
+                }
+                MacroAction::Command { command } => {
+                    if tx_command.send(command.clone()).is_err() {
+                        warn!("Failed to relay macro command {command:?}");
+                    }
+                }
+            }
+            if let Ok(mut data) = music_state.data.lock()
+                && let Ok(audio_sinks) = music_state.audio_sinks.lock()
+            {
+                let x = PadHandler::refresh(&data, &music_state.tx_data, &audio_sinks);
+                data.copy_data(x);
+            }
+        }
+    }
+
+    fn replay_pad_action(
+        control: u8,
+        value: u8,
+        music_state: &MusicState,
+        midi_out: Option<&Arc<Mutex<ChannelOutput>>>,
+    ) {
+        let lock_out = || midi_out.and_then(|x| x.lock().ok());
+        match PadsAndKnobsInputGroup::try_from(control) {
+            // Pads always play their album, a macro never triggers other macros
+            Ok(PadsAndKnobsInputGroup::Pads(pad)) if value > 0 => {
+                PadHandler::handle_pad(pad, music_state, lock_out().as_deref_mut());
+            }
+            Ok(input_group) if value > 0 => PadHandler::handle_input_pressed(
+                lock_out().as_deref_mut(),
+                input_group,
+                value,
+                music_state,
+            ),
+            Ok(input_group) => PadHandler::handle_input_released(
+                lock_out().as_deref_mut(),
+                input_group,
+                value,
+                music_state,
+            ),
+            Err(_) => warn!("Macro contains an unknown control {control}, skipping it"),
+        }
+    }
+}
@@ -1,5 +1,20 @@
+pub mod cue_list_handler;
+#[cfg(feature = "midi")]
+pub mod cue_output;
 pub mod hw_handler;
 pub mod keyboard_handler;
+pub mod library_handler;
 #[cfg(feature = "midi")]
 pub mod listener_initializer;
+pub mod macro_handler;
+#[cfg(feature = "osc")]
+pub mod osc_server;
 pub mod pad_handler;
+pub mod profile_handler;
+pub mod scene_handler;
+pub mod session_handler;
+#[cfg(any(unix, feature = "remote"))]
+pub mod state_snapshot;
+#[cfg(feature = "midi")]
+pub mod virtual_input;
+pub mod watchdog_handler;
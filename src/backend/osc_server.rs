@@ -0,0 +1,494 @@
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::keyboard_handler::{
+    KeyboardHandler, N_OF_KEYS, get_ambience_key, get_sound_effect_key,
+};
+use crate::backend::pad_handler::PadHandler;
+use crate::states::button_states::ToggleStates;
+use crate::states::music_state::MusicState;
+use crate::states::sound_state::SoundState;
+use log::{debug, info, warn};
+use ramidier::enums::button::knob_ctrl::KnobCtrlKey;
+use ramidier::enums::button::pads::PadKey;
+use ramidier::enums::button::soft_keys::SoftKey;
+use ramidier::enums::input_group::{KeyboardInputGroup, PadsAndKnobsInputGroup};
+use ramidier::io::output::ChannelOutput;
+use rosc::{OscMessage, OscPacket, OscType, decoder, encoder};
+use std::collections::HashMap;
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+const ADDRESS_PREFIX: &str = "/teatro/";
+const FEEDBACK_INTERVAL: Duration = Duration::from_millis(200);
+/// Largest packet read, OSC over UDP fits in a single datagram
+const MAX_PACKET_SIZE: usize = 1536;
+const DEFAULT_VELOCITY: u8 = 100;
+
+/// Clients receiving the feedback, mapped to whether they already got the whole state
+type FeedbackClients = Arc<Mutex<HashMap<SocketAddr, bool>>>;
+
+#[derive(Clone)]
+struct OscContext {
+    music_state: MusicState,
+    sound_state: SoundState,
+    midi_out: Option<Arc<Mutex<ChannelOutput>>>,
+    clients: FeedbackClients,
+}
+
+/// Listens for OSC messages under `/teatro/` and sends the state changes back to registered clients
+pub fn start_osc_server(
+    music_state: &MusicState,
+    sound_state: &SoundState,
+    midi_out: Option<Arc<Mutex<ChannelOutput>>>,
+) {
+    let Some(address) = music_state.data.lock().ok().and_then(|data| {
+        data.settings_data
+            .lock()
+            .ok()
+            .map(|x| x.osc_address.clone())
+    }) else {
+        warn!("Failed to read settings, OSC server will not be started");
+        return;
+    };
+    let (socket, feedback_socket) = match UdpSocket::bind(&address)
+        .and_then(|socket| socket.try_clone().map(|clone| (socket, clone)))
+    {
+        Ok(sockets) => sockets,
+        Err(e) => {
+            warn!("Could not listen for OSC on {address}: {e}");
+            return;
+        }
+    };
+    info!("Listening for OSC on {address}");
+    let context = OscContext {
+        music_state: music_state.clone(),
+        sound_state: sound_state.clone(),
+        midi_out,
+        clients: Arc::new(Mutex::new(HashMap::new())),
+    };
+    let feedback_context = context.clone();
+    thread::spawn(move || handle_osc_packets(&socket, &context));
+    thread::spawn(move || send_feedback(&feedback_socket, &feedback_context));
+}
+
+fn handle_osc_packets(socket: &UdpSocket, context: &OscContext) {
+    let mut buffer = [0u8; MAX_PACKET_SIZE];
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((size, sender)) => match decoder::decode_udp(&buffer[..size]) {
+                Ok((_, packet)) => {
+                    for message in get_messages(packet) {
+                        debug!("OSC from {sender}: {message:?}");
+                        handle_osc_message(&message, sender, context);
+                    }
+                    refresh(&context.music_state);
+                }
+                Err(e) => warn!("Invalid OSC packet from {sender}: {e}"),
+            },
+            Err(e) => warn!("Failed to read OSC packet: {e}"),
+        }
+    }
+}
+
+/// Messages contained in a packet, bundles are flattened and their time tag ignored
+fn get_messages(packet: OscPacket) -> Vec<OscMessage> {
+    match packet {
+        OscPacket::Message(message) => vec![message],
+        OscPacket::Bundle(bundle) => bundle.content.into_iter().flat_map(get_messages).collect(),
+    }
+}
+
+fn new_message(address: impl Into<String>, args: Vec<OscType>) -> OscMessage {
+    OscMessage {
+        addr: address.into(),
+        args,
+    }
+}
+
+/// Numeric value of the argument, OSC clients send either ints or floats for the same control
+const fn get_osc_f32(arg: &OscType) -> Option<f32> {
+    match arg {
+        OscType::Int(x) => Some(*x as f32),
+        OscType::Float(x) => Some(*x),
+        _ => None,
+    }
+}
+
+fn handle_osc_message(message: &OscMessage, sender: SocketAddr, context: &OscContext) {
+    let Some(path) = message.addr.strip_prefix(ADDRESS_PREFIX) else {
+        warn!(
+            "Ignoring OSC message outside of {ADDRESS_PREFIX}: {}",
+            message.addr
+        );
+        return;
+    };
+    let segments: Vec<&str> = path.split('/').collect();
+    let value = message.args.first().and_then(get_osc_f32);
+    // Buttons send 1 when pressed and 0 when released, only the press is an action
+    let pressed = value.is_none_or(|x| x > 0.);
+    match segments.as_slice() {
+        ["pad", "next"] if pressed => press_and_release(PadsAndKnobsInputGroup::Down, context),
+        ["pad", "previous"] if pressed => press_and_release(PadsAndKnobsInputGroup::Up, context),
+        ["pad", pad] if pressed => {
+            if let Some(pad) = pad
+                .parse::<u8>()
+                .ok()
+                .and_then(|x| PadKey::try_from(x).ok())
+            {
+                press_and_release(PadsAndKnobsInputGroup::Pads(pad), context);
+            } else {
+                warn!("Invalid OSC pad {pad}");
+            }
+        }
+        ["knob", knob] => match (knob.parse::<u8>(), value) {
+            (Ok(knob), Some(value)) => PadHandler::set_gui_knob(knob, value, &context.music_state),
+            _ => warn!("OSC knob {knob} expects a value between 0 and 1"),
+        },
+        ["sfx", index] => handle_key(
+            index.parse().ok().and_then(get_sound_effect_key),
+            message,
+            context,
+        ),
+        ["ambience", index] => handle_key(
+            index.parse().ok().and_then(get_ambience_key),
+            message,
+            context,
+        ),
+        ["key", key] => handle_key(
+            key.parse().ok().filter(|x| (1..=N_OF_KEYS).contains(x)),
+            message,
+            context,
+        ),
+        ["bank", "next"] if pressed => KeyboardHandler::change_bank(1, &context.sound_state.data),
+        ["bank", "previous"] if pressed => {
+            KeyboardHandler::change_bank(-1, &context.sound_state.data);
+        }
+        ["transport", action] if pressed => {
+            if let Some(input_group) = get_transport_input(action) {
+                press_and_release(input_group, context);
+            } else {
+                warn!("Unknown OSC transport action {action}");
+            }
+        }
+        ["register"] => {
+            let client = get_client_address(sender, message);
+            if let Ok(mut clients) = context.clients.lock() {
+                info!("Sending OSC feedback to {client}");
+                clients.insert(client, false);
+            }
+        }
+        ["unregister"] => {
+            let client = get_client_address(sender, message);
+            if let Ok(mut clients) = context.clients.lock() {
+                clients.remove(&client);
+            }
+        }
+        _ if !pressed => {}
+        _ => warn!("Unknown OSC address {}", message.addr),
+    }
+}
+
+/// Feedback goes to the sender, on the port given as argument if any
+fn get_client_address(sender: SocketAddr, message: &OscMessage) -> SocketAddr {
+    let port = match message.args.first() {
+        Some(OscType::Int(x)) => u16::try_from(*x).ok(),
+        _ => None,
+    };
+    SocketAddr::new(sender.ip(), port.unwrap_or_else(|| sender.port()))
+}
+
+/// Plays or releases a key, ints are read as velocities and floats as a 0 to 1 range
+#[allow(clippy::cast_sign_loss)] // the value is clamped before the cast
+fn handle_key(key: Option<u8>, message: &OscMessage, context: &OscContext) {
+    let Some(key) = key else {
+        warn!("Invalid OSC key in {}", message.addr);
+        return;
+    };
+    let velocity = match message.args.first() {
+        Some(OscType::Int(x)) => u8::try_from((*x).clamp(0, 127)).unwrap_or_default(),
+        Some(OscType::Float(x)) => (x.clamp(0., 1.) * 127.).round() as u8,
+        _ => DEFAULT_VELOCITY,
+    };
+    let input_group = KeyboardInputGroup::Key(key);
+    if velocity > 0 {
+        KeyboardHandler::handle_input(input_group, velocity, &context.sound_state);
+    } else {
+        KeyboardHandler::handle_release(input_group, &context.sound_state);
+    }
+}
+
+fn get_transport_input(action: &str) -> Option<PadsAndKnobsInputGroup> {
+    match action {
+        "pause" => Some(PadsAndKnobsInputGroup::SoftKeys(SoftKey::ClipStop)),
+        "skip" => Some(PadsAndKnobsInputGroup::Right),
+        "stop" => Some(PadsAndKnobsInputGroup::StopAllClips),
+        "mute" => Some(PadsAndKnobsInputGroup::SoftKeys(SoftKey::Mute)),
+        "solo" => Some(PadsAndKnobsInputGroup::SoftKeys(SoftKey::Solo)),
+        "loop" => Some(PadsAndKnobsInputGroup::SoftKeys(SoftKey::Select)),
+        "shuffle" => Some(PadsAndKnobsInputGroup::KnobCtrl(KnobCtrlKey::Send)),
+        _ => None,
+    }
+}
+
+fn press_and_release(input_group: PadsAndKnobsInputGroup, context: &OscContext) {
+    let lock_out = || context.midi_out.as_ref().and_then(|x| x.lock().ok());
+    PadHandler::handle_input_pressed(
+        lock_out().as_deref_mut(),
+        input_group,
+        1,
+        &context.music_state,
+    );
+    PadHandler::handle_input_released(
+        lock_out().as_deref_mut(),
+        input_group,
+        1,
+        &context.music_state,
+    );
+}
+
+fn refresh(music_state: &MusicState) {
+    if let Ok(mut data) = music_state.data.lock()
+        && let Ok(audio_sinks) = music_state.audio_sinks.lock()
+    {
+        let x = PadHandler::refresh(&data, &music_state.tx_data, &audio_sinks);
+        data.copy_data(x);
+    }
+}
+
+/// Sends the values that changed since the last round, new clients get all of them
+fn send_feedback(socket: &UdpSocket, context: &OscContext) {
+    let mut last_sent: HashMap<String, Vec<OscType>> = HashMap::new();
+    loop {
+        thread::sleep(FEEDBACK_INTERVAL);
+        let Ok(mut clients) = context.clients.lock() else {
+            continue;
+        };
+        if clients.is_empty() {
+            continue;
+        }
+        for message in get_feedback_messages(&context.music_state) {
+            let changed = last_sent.get(&message.addr) != Some(&message.args);
+            let packet = match encoder::encode(&OscPacket::Message(message.clone())) {
+                Ok(packet) => packet,
+                Err(e) => {
+                    warn!("Failed to encode OSC feedback {}: {e}", message.addr);
+                    continue;
+                }
+            };
+            for (client, synced) in clients.iter() {
+                if (changed || !*synced)
+                    && let Err(e) = socket.send_to(&packet, client)
+                {
+                    debug!("Failed to send OSC feedback to {client}: {e}");
+                }
+            }
+            last_sent.insert(message.addr, message.args);
+        }
+        clients.values_mut().for_each(|synced| *synced = true);
+    }
+}
+
+fn get_feedback_messages(music_state: &MusicState) -> Vec<OscMessage> {
+    // Same lock order as the packet thread, data before the sinks
+    let Ok(data) = music_state.data.lock() else {
+        return vec![];
+    };
+    let Ok(audio_sinks) = music_state.audio_sinks.lock() else {
+        return vec![];
+    };
+    let playlist = data
+        .current_playlist
+        .clone()
+        .map(|playlist| PadHandler::get_current_playlist_state(playlist, &audio_sinks.music_queue));
+    drop(audio_sinks);
+    let mut messages = vec![
+        new_message(
+            "/teatro/pad/last",
+            vec![OscType::Int(data.last_pad_pressed.map_or(-1, get_osc_int))],
+        ),
+        new_message(
+            "/teatro/pad/page",
+            vec![OscType::Int(get_osc_int(data.pad_page))],
+        ),
+        new_message(
+            "/teatro/bank",
+            vec![OscType::Int(get_osc_int(data.keyboard_bank))],
+        ),
+        new_message(
+            "/teatro/ambience",
+            vec![OscType::Int(
+                data.sounding_ambience.map_or(0, |x| i32::from(x.key)),
+            )],
+        ),
+        new_message(
+            "/teatro/sfx",
+            vec![OscType::Int(
+                data.sounding_sound_effect.map_or(0, |x| i32::from(x.key)),
+            )],
+        ),
+    ];
+    messages.extend(data.knob_values.iter().map(|(knob, value)| {
+        new_message(format!("/teatro/knob/{knob}"), vec![OscType::Float(*value)])
+    }));
+    messages.extend(ToggleStates::all().iter_names().map(|(name, button)| {
+        new_message(
+            format!("/teatro/button/{}", name.to_lowercase()),
+            vec![OscType::Int(i32::from(data.button_states.contains(button)))],
+        )
+    }));
+    drop(data);
+    if let Some(playlist) = playlist
+        && let Some(track) = playlist.get_current_track()
+    {
+        messages.push(new_message(
+            "/teatro/track",
+            vec![
+                OscType::Int(i32::try_from(playlist.current_track).unwrap_or(i32::MAX)),
+                OscType::String(track.file_path),
+            ],
+        ));
+    }
+    messages
+}
+
+fn get_osc_int(value: usize) -> i32 {
+    i32::try_from(value).unwrap_or(i32::MAX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rosc::OscBundle;
+    use std::time::Instant;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn encode(message: OscMessage) -> Vec<u8> {
+        encoder::encode(&OscPacket::Message(message)).expect("The message can be encoded")
+    }
+
+    /// Starts the server threads on a free local port, returns its address
+    fn start_test_server(music_state: &MusicState, sound_state: &SoundState) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").expect("A local port should be free");
+        let address = socket.local_addr().expect("The socket has an address");
+        let feedback_socket = socket.try_clone().expect("The socket can be cloned");
+        let context = OscContext {
+            music_state: music_state.clone(),
+            sound_state: sound_state.clone(),
+            midi_out: None,
+            clients: Arc::new(Mutex::new(HashMap::new())),
+        };
+        let feedback_context = context.clone();
+        thread::spawn(move || handle_osc_packets(&socket, &context));
+        thread::spawn(move || send_feedback(&feedback_socket, &feedback_context));
+        address
+    }
+
+    #[test]
+    fn nested_bundles_are_flattened() {
+        let first = new_message("/teatro/pad/3", vec![OscType::Int(1)]);
+        let second = new_message("/teatro/bank/next", vec![]);
+        let bundle = |content| {
+            OscPacket::Bundle(OscBundle {
+                timetag: (0, 1).into(),
+                content,
+            })
+        };
+        let packet = encoder::encode(&bundle(vec![
+            OscPacket::Message(first.clone()),
+            bundle(vec![OscPacket::Message(second.clone())]),
+        ]))
+        .expect("The bundle can be encoded");
+        let (_, packet) = decoder::decode_udp(&packet).expect("The bundle can be decoded");
+        assert_eq!(get_messages(packet), vec![first, second]);
+    }
+
+    #[test]
+    fn pad_message_changes_the_state_and_is_sent_back() {
+        let (music_state, sound_state, _rx_data) = crate::get_test_states();
+        let server = start_test_server(&music_state, &sound_state);
+        let client = UdpSocket::bind("127.0.0.1:0").expect("A local port should be free");
+        client
+            .set_read_timeout(Some(TIMEOUT))
+            .expect("The timeout is valid");
+        for message in [
+            new_message("/teatro/register", vec![]),
+            new_message("/teatro/pad/3", vec![OscType::Float(1.)]),
+        ] {
+            client
+                .send_to(&encode(message), server)
+                .expect("The server is listening");
+        }
+
+        let expected = new_message("/teatro/pad/last", vec![OscType::Int(3)]);
+        let started = Instant::now();
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        let mut received = false;
+        while !received && started.elapsed() < TIMEOUT {
+            let (size, _) = client
+                .recv_from(&mut buffer)
+                .expect("The server should send feedback");
+            received = decoder::decode_udp(&buffer[..size])
+                .is_ok_and(|(_, packet)| packet == OscPacket::Message(expected.clone()));
+        }
+        assert!(received);
+        assert_eq!(
+            music_state
+                .data
+                .lock()
+                .ok()
+                .and_then(|x| x.last_pad_pressed),
+            Some(3)
+        );
+    }
+
+    #[test]
+    fn messages_keep_being_handled_while_feedback_is_sent() {
+        let (music_state, sound_state, _rx_data) = crate::get_test_states();
+        let server = start_test_server(&music_state, &sound_state);
+        let client = UdpSocket::bind("127.0.0.1:0").expect("A local port should be free");
+        client
+            .set_read_timeout(Some(TIMEOUT))
+            .expect("The timeout is valid");
+        client
+            .send_to(&encode(new_message("/teatro/register", vec![])), server)
+            .expect("The server is listening");
+        let mut buffer = [0u8; MAX_PACKET_SIZE];
+        client
+            .recv_from(&mut buffer)
+            .expect("The server should send feedback");
+
+        // While the packet thread waits on the data, the feedback thread must not hold the sinks
+        let data = music_state.data.lock().expect("The data lock is free");
+        client
+            .send_to(
+                &encode(new_message("/teatro/knob/1", vec![OscType::Float(0.5)])),
+                server,
+            )
+            .expect("The server is listening");
+        thread::sleep(FEEDBACK_INTERVAL * 2);
+        let sinks_free = music_state.audio_sinks.try_lock().is_ok();
+        drop(data);
+        assert!(sinks_free);
+
+        client
+            .send_to(
+                &encode(new_message("/teatro/pad/7", vec![OscType::Float(1.)])),
+                server,
+            )
+            .expect("The server is listening");
+        let started = Instant::now();
+        let mut last_pad = None;
+        while last_pad != Some(7) && started.elapsed() < TIMEOUT {
+            thread::sleep(Duration::from_millis(10));
+            // try_lock keeps a deadlocked server from hanging the test
+            last_pad = music_state
+                .data
+                .try_lock()
+                .ok()
+                .and_then(|x| x.last_pad_pressed);
+        }
+        assert_eq!(last_pad, Some(7));
+    }
+}
@@ -1,14 +1,20 @@
 use crate::MusicState;
 use crate::audio::playback_handler;
+use crate::backend::cue_list_handler::CueListHandler;
 use crate::backend::hw_handler::MidiHandler;
-use crate::os_explorer::explorer::{
-    files_in_nth_subdir, get_album_name_from_folder_in_path, map_to_indexed_vec,
-};
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::macro_handler::get_control_number;
+use crate::backend::scene_handler::SceneHandler;
 use crate::states::audio_sinks::AudioSinks;
 use crate::states::button_states::ToggleStates;
+use crate::states::cue_event::CueEvent;
 use crate::states::filter_data::FilterData;
+use crate::states::knob_mode::KnobMode;
 use crate::states::knob_value_update::KnobValueUpdate;
-use crate::states::visualizer::RuntimeData;
+use crate::states::midi_macro::{MacroAction, MacroLibrary};
+use crate::states::playlist_data::PlaylistData;
+use crate::states::scene::SceneLibrary;
+use crate::states::visualizer::{PADS_PER_PAGE, RuntimeData};
 use biquad::Type;
 use flume::Sender;
 use log::{debug, info, warn};
@@ -24,6 +30,21 @@ use rodio::Sink;
 use std::sync::{Arc, Mutex};
 
 const KNOB_INCREMENT: f32 = 0.005;
+const MACRO_COLOR: LedColor = LedColor::Red;
+const SCENE_COLOR: LedColor = LedColor::Yellow;
+/// Pads of the bottom row, used as a progress bar in meter mode
+const METER_PADS: u8 = 8;
+const METER_COLOR: LedColor = LedColor::Green;
+
+/// Color used to dim the pads of each page, so that the current page is visible on the controller
+const PAGE_COLORS: [LedColor; 6] = [
+    LedColor::Green,
+    LedColor::Blue1,
+    LedColor::Orange,
+    LedColor::Magenta,
+    LedColor::Cyan1,
+    LedColor::Yellow,
+];
 
 impl ToggleStates {
     pub fn toggle_button<T: Into<u8> + Copy>(
@@ -53,15 +74,11 @@ impl MidiHandler for PadHandler {
         let x = old_data.current_playlist.as_ref().map_or_else(
             || old_data.clone(),
             |playlist| RuntimeData {
-                settings_data: old_data.settings_data.clone(),
-                pad_labels: old_data.pad_labels.clone(),
-                knob_values: old_data.knob_values.clone(),
-                button_states: old_data.button_states,
-                last_pad_pressed: old_data.last_pad_pressed,
                 current_playlist: Some(Self::get_current_playlist_state(
                     playlist.clone(),
                     &audio_sinks.music_queue,
                 )),
+                ..old_data.clone()
             },
         );
         Self::update_gui(tx_data, &x);
@@ -74,12 +91,32 @@ impl MidiHandler for PadHandler {
         state: &mut Self::State,
     ) {
         debug!("{stamp}: {msg:?}");
-        if let Ok(mut out) = midi_out.lock() {
-            if msg.value > 0 {
-                Self::handle_input_pressed(Some(&mut *out), msg.input_group, msg.value, state);
-            } else {
-                Self::handle_input_released(Some(&mut *out), msg.input_group, msg.value, state);
-            }
+        let mut out = midi_out.lock().ok();
+        Self::handle_midi_input(out.as_deref_mut(), msg, state);
+    }
+}
+
+impl PadHandler {
+    /// Handles a decoded message of the pads and knobs channel, whatever port it came from
+    pub fn handle_midi_input(
+        mut midi_out: Option<&mut ChannelOutput>,
+        msg: &MidiInputData<PadsAndKnobsInputGroup>,
+        state: &MusicState,
+    ) {
+        if !matches!(
+            msg.input_group,
+            PadsAndKnobsInputGroup::SoftKeys(SoftKey::RecArm)
+        ) && let Ok(mut recorder) = state.macro_recorder.lock()
+        {
+            recorder.record(MacroAction::Pad {
+                control: get_control_number(msg.input_group),
+                value: msg.value,
+            });
+        }
+        if msg.value > 0 {
+            Self::handle_input_pressed(midi_out.as_deref_mut(), msg.input_group, msg.value, state);
+        } else {
+            Self::handle_input_released(midi_out, msg.input_group, msg.value, state);
         }
         if let Ok(mut data) = state.data.lock()
             && let Ok(audio_sink) = state.audio_sinks.lock()
@@ -89,36 +126,15 @@ impl MidiHandler for PadHandler {
             Self::update_gui(&state.tx_data, &data);
         }
     }
-}
 
-impl PadHandler {
-    pub fn get_pad_albums_list(music_folder: &str) -> anyhow::Result<Vec<String>> {
-        Ok(
-            map_to_indexed_vec(get_album_name_from_folder_in_path(music_folder)?)
-                .into_iter()
-                .flatten()
-                .collect(),
-        )
-    }
-
-    pub fn update_pad_albums_list(
-        stale_data: &RuntimeData,
-        tx_data: &Sender<RuntimeData>,
-    ) -> anyhow::Result<RuntimeData> {
-        let folder = stale_data
+    /// Labels the pads after the folders assigned to them in the settings
+    pub fn update_pad_labels(data: &mut RuntimeData) {
+        let assignments = data
             .settings_data
             .lock()
-            .map_or_else(|_| "music".to_string(), |x| x.music_folder.clone());
-        let data = RuntimeData {
-            settings_data: stale_data.settings_data.clone(),
-            pad_labels: Self::get_pad_albums_list(&folder)?,
-            knob_values: stale_data.knob_values.clone(),
-            button_states: stale_data.button_states,
-            last_pad_pressed: stale_data.last_pad_pressed,
-            current_playlist: stale_data.current_playlist.clone(),
-        };
-        Self::update_gui(tx_data, &data);
-        Ok(data)
+            .map(|x| x.pad_assignments.clone())
+            .unwrap_or_default();
+        data.set_pad_assignments(&assignments);
     }
 
     fn toggle_state_button(
@@ -145,7 +161,8 @@ impl PadHandler {
             PadsAndKnobsInputGroup::Left
             | PadsAndKnobsInputGroup::Right
             | PadsAndKnobsInputGroup::Up
-            | PadsAndKnobsInputGroup::Down => {
+            | PadsAndKnobsInputGroup::Down
+            | PadsAndKnobsInputGroup::Start => {
                 if let Some(out) = midi_out {
                     change_button_status(out, false, input_group, LedColor::Green);
                 }
@@ -161,10 +178,8 @@ impl PadHandler {
         state: &MusicState,
     ) {
         match input_group {
-            PadsAndKnobsInputGroup::Pads(ref pad) => Self::handle_pad(*pad, state, midi_out),
-            PadsAndKnobsInputGroup::Knob(index) => {
-                Self::handle_knob(index, KnobValueUpdate::from(value), state);
-            }
+            PadsAndKnobsInputGroup::Pads(pad) => Self::handle_pad_press(pad, state, midi_out),
+            PadsAndKnobsInputGroup::Knob(index) => Self::handle_knob_message(index, value, state),
             PadsAndKnobsInputGroup::ResumePause => Self::handle_resume_pause(state, midi_out),
             PadsAndKnobsInputGroup::SoftKeys(key) => Self::handle_soft_key(key, state, midi_out),
             PadsAndKnobsInputGroup::KnobCtrl(key) => {
@@ -198,20 +213,33 @@ impl PadHandler {
                 Self::toggle_state_button(state, midi_out, ToggleStates::SHIFT, input_group);
             }
             PadsAndKnobsInputGroup::Start => {
-                Self::toggle_state_button(state, midi_out, ToggleStates::START, input_group);
+                if let Some(out) = midi_out {
+                    change_button_status(out, true, input_group, LedColor::Green);
+                }
+                // GO of the cue list, SHIFT moves it back to the first cue
+                if Self::is_shift_active(state) {
+                    CueListHandler::reset(state);
+                } else {
+                    CueListHandler::go(state);
+                }
             }
-            PadsAndKnobsInputGroup::Left
-            | PadsAndKnobsInputGroup::Up
-            | PadsAndKnobsInputGroup::Down => {
+            PadsAndKnobsInputGroup::Left => {
                 if let Some(out) = midi_out {
                     change_button_status(out, true, input_group, LedColor::Green);
                 }
+                if Self::is_shift_active(state) {
+                    KeyboardHandler::change_bank(-1, &state.data);
+                }
             }
+            PadsAndKnobsInputGroup::Up => Self::change_pad_page(-1, input_group, state, midi_out),
+            PadsAndKnobsInputGroup::Down => Self::change_pad_page(1, input_group, state, midi_out),
             PadsAndKnobsInputGroup::Right => {
                 if let Some(out) = midi_out {
                     change_button_status(out, true, input_group, LedColor::Green);
                 }
-                if let Ok(audio_sinks) = state.audio_sinks.lock() {
+                if Self::is_shift_active(state) {
+                    KeyboardHandler::change_bank(1, &state.data);
+                } else if let Ok(audio_sinks) = state.audio_sinks.lock() {
                     audio_sinks.music_queue.skip_one();
                 } else {
                     warn!("Failed to get audio sink lock, cannot skip music");
@@ -220,21 +248,223 @@ impl PadHandler {
         }
     }
 
-    fn handle_pad(pad: PadKey, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+    fn is_shift_active(state: &MusicState) -> bool {
+        state
+            .data
+            .lock()
+            .is_ok_and(|data| data.button_states.contains(ToggleStates::SHIFT))
+    }
+
+    fn change_pad_page(
+        step: isize,
+        input_group: PadsAndKnobsInputGroup,
+        state: &MusicState,
+        midi_out: Option<&mut ChannelOutput>,
+    ) {
+        if let Ok(mut data) = state.data.lock() {
+            let last_page = data.get_pad_page_count() - 1;
+            data.pad_page = data.pad_page.saturating_add_signed(step).min(last_page);
+            info!(
+                "Showing pad page {} of {}",
+                data.pad_page + 1,
+                last_page + 1
+            );
+            if let Some(out) = midi_out {
+                change_button_status(out, true, input_group, LedColor::Green);
+                Self::light_pad_page(out, &data);
+            }
+        } else {
+            warn!("Failed to get a lock on data. Will not change pad page");
+        }
+    }
+
+    /// Lights the pads of the current page: pads with an album are dimmed with
+    /// the page color, the pad of the album currently playing is fully lit
+    pub fn light_pad_page(midi_out: &mut ChannelOutput, data: &RuntimeData) {
+        for pad in 0..PADS_PER_PAGE as u8 {
+            let _ = if data.last_pad_pressed == Some(data.get_album_index(pad)) {
+                midi_out.set_pad_led(LedMode::On100Percent, pad, get_pad_color(pad))
+            } else {
+                midi_out.set_pad_led(LedMode::On10Percent, pad, get_idle_pad_color(data, pad))
+            };
+        }
+    }
+
+    /// A pad press first binds the macro just recorded, then plays either the scene
+    /// (with PAN toggled), the macro (with DEVICE toggled) or the album bound to the pad
+    fn handle_pad_press(pad: PadKey, state: &MusicState, mut midi_out: Option<&mut ChannelOutput>) {
+        let note = pad.get_index();
+        if Self::bind_pending_macro(note, state, midi_out.as_deref_mut()) {
+            return;
+        }
+        let (scene_mode, macro_mode) = state.data.lock().map_or((false, false), |data| {
+            (
+                data.button_states.contains(ToggleStates::PAN),
+                data.button_states.contains(ToggleStates::DEVICE),
+            )
+        });
+        if scene_mode {
+            SceneHandler::handle_scene_pad(note, state, midi_out);
+        } else if macro_mode {
+            if state.tx_macro.send(note).is_err() {
+                warn!("Failed to request macro of pad {note}");
+            }
+        } else {
+            Self::handle_pad(pad, state, midi_out);
+        }
+    }
+
+    fn bind_pending_macro(
+        pad: u8,
+        state: &MusicState,
+        midi_out: Option<&mut ChannelOutput>,
+    ) -> bool {
+        let Some(midi_macro) = state
+            .macro_recorder
+            .lock()
+            .ok()
+            .and_then(|mut recorder| recorder.take_pending())
+        else {
+            return false;
+        };
+        if let Ok(mut library) = state.macro_library.lock() {
+            library.macros.insert(pad, midi_macro);
+            info!("Macro bound to pad {pad}");
+            if let Err(e) = library.write_to_file() {
+                warn!("Failed to save macros: {e}");
+            }
+            if let Some(out) = midi_out {
+                let _ = out.set_pad_led(LedMode::On100Percent, pad, MACRO_COLOR);
+            }
+        } else {
+            warn!("Failed to get a lock on macros. Will not bind macro to pad {pad}");
+        }
+        true
+    }
+
+    /// Lights the pads that have a macro bound, shown while DEVICE is toggled
+    pub fn light_macro_pads(midi_out: &mut ChannelOutput, library: &MacroLibrary) {
+        for pad in 0..PADS_PER_PAGE as u8 {
+            let color = if library.macros.contains_key(&pad) {
+                MACRO_COLOR
+            } else {
+                LedColor::Off
+            };
+            let _ = midi_out.set_pad_led(LedMode::On100Percent, pad, color);
+        }
+    }
+
+    /// Lights the pads that have a scene bound, shown while PAN is toggled
+    pub fn light_scene_pads(midi_out: &mut ChannelOutput, library: &SceneLibrary) {
+        for pad in 0..PADS_PER_PAGE as u8 {
+            let color = if library.get_scene_on_pad(pad).is_some() {
+                SCENE_COLOR
+            } else {
+                LedColor::Off
+            };
+            let _ = midi_out.set_pad_led(LedMode::On100Percent, pad, color);
+        }
+    }
+
+    /// Lights every toggle button and the pads as the data says, e.g. once the session is restored
+    pub fn light_controller(midi_out: &mut ChannelOutput, data: &RuntimeData, state: &MusicState) {
+        let is_on = |toggle_state| data.button_states.contains(toggle_state);
+        for key in [
+            SoftKey::ClipStop,
+            SoftKey::Solo,
+            SoftKey::Mute,
+            SoftKey::Select,
+        ] {
+            change_button_status(midi_out, is_on(key.into()), key, LedColor::Green);
+        }
+        for key in [
+            KnobCtrlKey::Volume,
+            KnobCtrlKey::Pan,
+            KnobCtrlKey::Send,
+            KnobCtrlKey::Device,
+        ] {
+            change_button_status(midi_out, is_on(key.into()), key, LedColor::Green);
+        }
+        for (toggle_state, input_group) in [
+            (ToggleStates::STOP_ALL, PadsAndKnobsInputGroup::StopAllClips),
+            (ToggleStates::FILTER, PadsAndKnobsInputGroup::ResumePause),
+        ] {
+            change_button_status(midi_out, is_on(toggle_state), input_group, LedColor::Green);
+        }
+        if is_on(ToggleStates::PAN) {
+            if let Ok(library) = state.scene_library.lock() {
+                Self::light_scene_pads(midi_out, &library);
+            }
+        } else if is_on(ToggleStates::DEVICE) {
+            if let Ok(library) = state.macro_library.lock() {
+                Self::light_macro_pads(midi_out, &library);
+            }
+        } else {
+            Self::light_pad_page(midi_out, data);
+        }
+    }
+
+    /// Meter mode, toggled by VOLUME: the bottom pad row shows the progress of the current track,
+    /// CLIP STOP and SOLO light up while the ambience and sound effect channels are playing
+    pub fn show_meters(midi_out: &mut ChannelOutput, data: &RuntimeData, audio_sinks: &AudioSinks) {
+        let lit_pads = data
+            .current_playlist
+            .as_ref()
+            .and_then(PlaylistData::get_current_track)
+            .filter(|track| track.track_length > 0)
+            .map_or(0, |track| {
+                (track.elapsed_seconds * u64::from(METER_PADS)).div_ceil(track.track_length)
+            });
+        for pad in 0..METER_PADS {
+            let color = if u64::from(pad) < lit_pads {
+                METER_COLOR
+            } else {
+                LedColor::Off
+            };
+            let _ = midi_out.set_pad_led(LedMode::On100Percent, pad, color);
+        }
+        for (soft_key, sink) in [
+            (SoftKey::ClipStop, &audio_sinks.ambience_queue),
+            (SoftKey::Solo, &audio_sinks.sound_effect_queue),
+        ] {
+            let is_playing = !sink.empty() && !sink.is_paused();
+            change_button_status(midi_out, is_playing, soft_key, LedColor::Green);
+        }
+    }
+
+    pub fn handle_pad(pad: PadKey, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
         let note = pad.get_index();
+        if let Ok(album) = state.data.lock().map(|data| data.get_album_index(note)) {
+            Self::play_album(album, state, midi_out);
+        } else {
+            warn!("Failed to get a lock on data. Will not handle pad action");
+        }
+    }
+
+    /// Plays the given album from its first track, lighting its pad if it is in the current page
+    pub fn play_album(album: usize, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
         if let Ok(mut data) = state.data.lock() {
             let old_pad = data.last_pad_pressed;
-            data.last_pad_pressed = Some(note);
-            if let Ok(res) = files_in_nth_subdir(
-                data.settings_data
+            data.last_pad_pressed = Some(album);
+            // New music replaces the one of the previous session
+            data.resume_offer = None;
+            let folder = data
+                .settings_data
+                .lock()
+                .ok()
+                .and_then(|x| x.get_pad_folder(album).map(str::to_string));
+            let tracks = folder.as_deref().map_or_else(Vec::new, |x| {
+                data.library_index
                     .lock()
-                    .map_or_else(|_| "music".to_string(), |x| x.music_folder.clone())
-                    .as_ref(),
-                note as usize,
-            ) {
-                info!("playing the following audio folder: {}", res.0.display());
-                let mut files = res
-                    .1
+                    .map(|index| index.get_folder_files(x))
+                    .unwrap_or_default()
+            });
+            if let Some(folder) = &folder
+                && !tracks.is_empty()
+            {
+                info!("playing the following audio folder: {folder}");
+                send_cue(&state.tx_cue, CueEvent::PadPressed { album });
+                let mut files = tracks
                     .iter()
                     .filter_map(|x| x.to_str())
                     .map(ToString::to_string)
@@ -243,33 +473,106 @@ impl PadHandler {
                     fastrand::shuffle(files.as_mut_slice());
                 }
                 if let Ok(audio_sinks) = state.audio_sinks.lock() {
-                    data.current_playlist = Self::play_song(
+                    let mut playlist = Self::play_song(
                         &files,
                         &audio_sinks.music_queue,
                         &state.music_filter,
                         data.get_music_volume(),
+                        None,
+                        false,
+                        None,
                     );
+                    if let Some(playlist) = playlist.as_mut()
+                        && let Ok(index) = data.library_index.lock()
+                    {
+                        index.fill_playlist(playlist);
+                    }
+                    data.current_playlist = playlist;
                 } else {
                     warn!("Failed to get audio sink lock, cannot play song");
                 }
+            } else if let Some(folder) = folder {
+                warn!("There is no audio to play in {folder}, assigned to pad {album}");
             } else {
-                warn!("No folder associated with the given button {note}");
+                warn!("No folder is assigned to pad {album}");
             }
-            let color: LedColor = (pad.get_index() + 1).try_into().unwrap_or(LedColor::Green);
             if let Some(out) = midi_out {
-                // Turn off previous pad
-                if let Some(l_p) = old_pad {
-                    let _ = out.set_pad_led(LedMode::On10Percent, l_p, LedColor::Off);
+                // Dim previous pad back to the page color, if it is still visible
+                if let Some(l_p) = old_pad.and_then(|x| data.get_pad_in_current_page(x)) {
+                    let _ =
+                        out.set_pad_led(LedMode::On10Percent, l_p, get_idle_pad_color(&data, l_p));
+                }
+                if let Some(pad) = data.get_pad_in_current_page(album) {
+                    let _ = out.set_pad_led(LedMode::On100Percent, pad, get_pad_color(pad));
                 }
-                let _ = out.set_pad_led(LedMode::On100Percent, note, color);
             }
         } else {
-            warn!("Failed to get a lock on data. Will not handle pad action");
+            warn!("Failed to get a lock on data. Will not play album {album}");
+        }
+    }
+
+    /// Turns a knob message into steps, following the knob mode set in the settings
+    fn handle_knob_message(index: u8, value: u8, state: &MusicState) {
+        let Ok(mut tracker) = state.knob_tracker.lock() else {
+            warn!("Failed to get a lock on knob tracker. Will not handle knob action");
+            return;
+        };
+        let Ok(data) = state.data.lock() else {
+            warn!("Failed to get a lock on data. Will not handle knob action");
+            return;
+        };
+        let current = data.knob_values.get(&index).copied();
+        let (knob_mode, acceleration) = data
+            .settings_data
+            .lock()
+            .map_or((KnobMode::Relative, false), |x| {
+                (x.knob_mode, x.knob_acceleration)
+            });
+        drop(data);
+        let steps = match knob_mode {
+            KnobMode::Relative => Some(tracker.get_relative_steps(index, value, acceleration)),
+            KnobMode::Absolute => current
+                .and_then(|current| tracker.get_absolute_delta(index, value, current))
+                .map(|delta| delta / KNOB_INCREMENT),
+        };
+        drop(tracker);
+        if let Some(steps) = steps {
+            Self::handle_knob(index, steps, state);
+        } else {
+            debug!("Knob {index} has not yet reached its value, ignoring it");
+        }
+    }
+
+    /// Knob turned from the GUI, a physical pot will have to pick the value up again
+    pub fn handle_gui_knob(index: u8, value: KnobValueUpdate, state: &MusicState) {
+        if let Ok(mut tracker) = state.knob_tracker.lock() {
+            tracker.release(index);
+        }
+        Self::handle_knob(index, value.into(), state);
+    }
+
+    /// Knob moved to an exact percentage from the GUI or a remote, same takeover rules as above
+    pub fn set_gui_knob(index: u8, value: f32, state: &MusicState) {
+        if let Ok(mut tracker) = state.knob_tracker.lock() {
+            tracker.release(index);
+        }
+        let current = state
+            .data
+            .lock()
+            .ok()
+            .and_then(|data| data.knob_values.get(&index).copied());
+        if let Some(current) = current {
+            Self::handle_knob(
+                index,
+                (value.clamp(0., 1.) - current) / KNOB_INCREMENT,
+                state,
+            );
+        } else {
+            warn!("Unknown knob {index}, ignoring it");
         }
     }
 
-    fn handle_knob(index: u8, value: KnobValueUpdate, state: &MusicState) {
-        let delta = value.into();
+    fn handle_knob(index: u8, delta: f32, state: &MusicState) {
         if let Ok(mut data) = state.data.lock() {
             match index {
                 1 => {
@@ -386,6 +689,7 @@ impl PadHandler {
                     warn!("Failed to get audio sink lock, cannot mute song");
                 }
             }
+            SoftKey::RecArm => Self::handle_rec_arm(key, state, midi_out),
             SoftKey::Solo => {
                 if let Ok(mut data) = state.data.lock() {
                     data.button_states.toggle_button(
@@ -405,7 +709,7 @@ impl PadHandler {
                     }
                 }
             }
-            _ => {
+            SoftKey::Select => {
                 if let Ok(mut data) = state.data.lock() {
                     data.button_states.toggle_button(
                         ToggleStates::from(key),
@@ -420,18 +724,73 @@ impl PadHandler {
         }
     }
 
+    /// REC ARM starts recording a macro, pressing it again stops it
+    fn handle_rec_arm(key: SoftKey, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+        if let Ok(mut data) = state.data.lock() {
+            data.button_states
+                .toggle_button(ToggleStates::from(key), midi_out, key, LedColor::Red);
+            if let Ok(mut recorder) = state.macro_recorder.lock() {
+                if data.button_states.contains(ToggleStates::REC_ARM) {
+                    info!("Recording macro");
+                    recorder.start();
+                } else {
+                    recorder.stop();
+                    info!("Macro recorded, press a pad to bind it");
+                }
+            } else {
+                warn!("Failed to get macro recorder lock, cannot record macro");
+            }
+        } else {
+            warn!("Failed to get data lock, cannot handle rec arm press");
+        }
+    }
+
     fn handle_knob_ctrl(
         key: KnobCtrlKey,
         state: &MusicState,
         midi_out: Option<&mut ChannelOutput>,
     ) {
+        let mut midi_out = midi_out;
         if let Ok(mut data) = state.data.lock() {
             data.button_states.toggle_button(
                 ToggleStates::from(key),
-                midi_out,
+                midi_out.as_deref_mut(),
                 key,
                 LedColor::Green,
             );
+            if let Some(out) = midi_out {
+                match key {
+                    KnobCtrlKey::Device => {
+                        if !data.button_states.contains(ToggleStates::DEVICE) {
+                            Self::light_pad_page(out, &data);
+                        } else if let Ok(library) = state.macro_library.lock() {
+                            Self::light_macro_pads(out, &library);
+                        }
+                    }
+                    KnobCtrlKey::Volume => {
+                        if data.button_states.contains(ToggleStates::VOLUME) {
+                            info!("Meter mode on");
+                        } else {
+                            info!("Meter mode off");
+                            Self::light_pad_page(out, &data);
+                            for soft_key in [SoftKey::ClipStop, SoftKey::Solo] {
+                                let is_on = data.button_states.contains(soft_key.into());
+                                change_button_status(out, is_on, soft_key, LedColor::Green);
+                            }
+                        }
+                    }
+                    KnobCtrlKey::Pan => {
+                        if !data.button_states.contains(ToggleStates::PAN) {
+                            info!("Scene bank off");
+                            Self::light_pad_page(out, &data);
+                        } else if let Ok(library) = state.scene_library.lock() {
+                            info!("Scene bank on");
+                            Self::light_scene_pads(out, &library);
+                        }
+                    }
+                    KnobCtrlKey::Send => {}
+                }
+            }
         }
     }
 }
@@ -453,6 +812,28 @@ fn adjust_filter(filter: &Arc<Mutex<FilterData>>, delta: f32, filter_type: Type<
     playback_handler::change_filter_frequency_value(filter, delta, filter_type);
 }
 
+pub fn send_cue(tx_cue: &Sender<CueEvent>, event: CueEvent) {
+    if tx_cue.send(event).is_err() {
+        debug!("No cue output listening, {event:?} was not sent");
+    }
+}
+
+fn get_pad_color(pad: u8) -> LedColor {
+    (pad + 1).try_into().unwrap_or(LedColor::Green)
+}
+
+fn get_idle_pad_color(data: &RuntimeData, pad: u8) -> LedColor {
+    if data
+        .pad_labels
+        .get(data.get_album_index(pad))
+        .is_some_and(|x| !x.is_empty())
+    {
+        PAGE_COLORS[data.pad_page % PAGE_COLORS.len()]
+    } else {
+        LedColor::Off
+    }
+}
+
 fn change_button_status<T>(
     midi_out: &mut ChannelOutput,
     next_state: bool,
@@ -0,0 +1,107 @@
+use crate::backend::cue_list_handler::CueListHandler;
+use crate::backend::library_handler::LibraryHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::backend::session_handler::SessionHandler;
+use crate::comms::command::CommsCommand;
+use crate::states::app_paths::AppPaths;
+use crate::states::cue_list::CueList;
+use crate::states::midi_macro::MacroLibrary;
+use crate::states::music_state::MusicState;
+use crate::states::scene::SceneLibrary;
+use crate::states::settings_data::SettingsData;
+use log::{info, warn};
+use ramidier::io::output::ChannelOutput;
+use std::path::Path;
+
+pub struct ProfileHandler;
+
+impl ProfileHandler {
+    /// Macros, scenes and cue list kept in the paths, empty when their files are missing
+    pub fn load_libraries(
+        paths: &AppPaths,
+        adventure: &str,
+    ) -> (MacroLibrary, SceneLibrary, CueList) {
+        let macro_library =
+            MacroLibrary::load_from_file(&paths.macros).unwrap_or_else(|_| MacroLibrary {
+                path: paths.macros.clone(),
+                ..MacroLibrary::default()
+            });
+        let scene_library =
+            SceneLibrary::load_from_file(&paths.scenes).unwrap_or_else(|_| SceneLibrary {
+                path: paths.scenes.clone(),
+                ..SceneLibrary::default()
+            });
+        let cue_list = CueList::load_adventure(&paths.cue_lists, adventure)
+            .unwrap_or_else(|e| CueList::unloaded(&e));
+        (macro_library, scene_library, cue_list)
+    }
+
+    /// Replaces settings, macros, scenes, cue list and pads with the ones of the profile.
+    /// What is playing keeps playing, the watchdog moves to the folders and config of the profile
+    /// and labels the pads and keys again
+    pub fn switch(name: &str, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+        if !AppPaths::get_profiles().iter().any(|x| x == name) {
+            warn!("There is no profile {name}, will not switch");
+            return;
+        }
+        let paths = AppPaths::builder().profile(name.to_string()).build();
+        let settings = match Self::load_settings(&paths.config) {
+            Ok(settings) => settings,
+            Err(e) => {
+                warn!("Will not switch to profile {name}: {e:#}");
+                return;
+            }
+        };
+        // Saved while the session still belongs to the previous profile
+        SessionHandler::save(state);
+
+        let (macro_library, scene_library, cue_list) =
+            Self::load_libraries(&paths, &settings.adventure);
+        if let Ok(mut x) = state.macro_library.lock() {
+            *x = macro_library;
+        }
+        if let Ok(mut x) = state.scene_library.lock() {
+            *x = scene_library;
+        }
+        if let Ok(mut x) = state.cue_list.lock() {
+            *x = cue_list;
+        }
+        CueListHandler::reset(state);
+
+        let Ok(mut data) = state.data.lock() else {
+            warn!("Failed to get a lock on data. Profile {name} is only partially loaded");
+            return;
+        };
+        if let Ok(mut s) = data.settings_data.lock() {
+            s.copy_data(&settings);
+        }
+        // The album indexes belong to the previous music folder
+        data.last_pad_pressed = None;
+        data.profile = Some(name.to_string());
+        if let Some(out) = midi_out {
+            PadHandler::light_controller(out, &data, state);
+        }
+        drop(data);
+        // The folders of the profile may not be indexed yet
+        LibraryHandler::start_scan(state);
+        if let Err(e) = state.tx_watchdog.send(CommsCommand::Refresh) {
+            warn!("Failed to tell the watchdog about profile {name}: {e}");
+        }
+        info!("Switched to profile {name}");
+    }
+
+    /// Settings of the profile, a new profile starts from the defaults
+    fn load_settings(path: &str) -> anyhow::Result<SettingsData> {
+        if !Path::new(path).exists() {
+            let mut settings = SettingsData::default();
+            settings.fill_pad_assignments();
+            settings.write_to_config(path)?;
+            return Ok(settings);
+        }
+        let (settings, issues) = SettingsData::load_from_config(path)?;
+        for issue in &issues {
+            warn!("{issue}");
+        }
+        Ok(settings)
+    }
+}
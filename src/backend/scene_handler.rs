@@ -0,0 +1,492 @@
+use crate::audio::playback_handler;
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::states::button_states::ToggleStates;
+use crate::states::filter_data::FilterData;
+use crate::states::music_state::MusicState;
+use crate::states::playlist_data::PlaylistData;
+use crate::states::scene::{Scene, SceneAmbience, SceneFilter};
+use crate::states::sound_state::SoundState;
+use log::{debug, info, warn};
+use ramidier::enums::button::knob_ctrl::KnobCtrlKey;
+use ramidier::enums::button::soft_keys::SoftKey;
+use ramidier::enums::input_group::PadsAndKnobsInputGroup;
+use ramidier::io::output::ChannelOutput;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// Time between two steps of a morph
+const MORPH_STEP: Duration = Duration::from_millis(50);
+/// Velocity used to start the ambience of a scene
+const AMBIENCE_VELOCITY: u8 = 127;
+
+/// Controller button toggling each of the scene states
+const TOGGLE_INPUTS: [(ToggleStates, PadsAndKnobsInputGroup); 6] = [
+    (
+        ToggleStates::SEND,
+        PadsAndKnobsInputGroup::KnobCtrl(KnobCtrlKey::Send),
+    ),
+    (
+        ToggleStates::SELECT,
+        PadsAndKnobsInputGroup::SoftKeys(SoftKey::Select),
+    ),
+    (
+        ToggleStates::MUTE,
+        PadsAndKnobsInputGroup::SoftKeys(SoftKey::Mute),
+    ),
+    (ToggleStates::FILTER, PadsAndKnobsInputGroup::ResumePause),
+    (
+        ToggleStates::CLIP_STOP,
+        PadsAndKnobsInputGroup::SoftKeys(SoftKey::ClipStop),
+    ),
+    (ToggleStates::STOP_ALL, PadsAndKnobsInputGroup::StopAllClips),
+];
+
+/// Incremented by every recall, a running morph stops as soon as another scene is recalled
+static LAST_RECALL: AtomicUsize = AtomicUsize::new(0);
+
+/// Knob and filter values moved by a morph
+#[derive(Debug, Clone)]
+struct MixLevels {
+    knob_values: BTreeMap<u8, f32>,
+    filters: [SceneFilter; 3],
+    /// A channel the scene leaves silent keeps the volume of its queue: the ambience fades out
+    /// from it, the music is stopped right away
+    set_music_volume: bool,
+    set_ambience_volume: bool,
+}
+
+impl MixLevels {
+    fn interpolate(&self, target: &Self, progress: f32) -> Self {
+        let lerp = |from: f32, to: f32| (to - from).mul_add(progress, from);
+        Self {
+            knob_values: target
+                .knob_values
+                .iter()
+                .map(|(knob, to)| {
+                    let from = self.knob_values.get(knob).copied().unwrap_or(*to);
+                    (*knob, lerp(from, *to))
+                })
+                .collect(),
+            filters: [0, 1, 2].map(|i| SceneFilter {
+                percentage: lerp(self.filters[i].percentage, target.filters[i].percentage),
+                kind: target.filters[i].kind,
+            }),
+            ..target.clone()
+        }
+    }
+}
+
+impl From<&Scene> for MixLevels {
+    fn from(value: &Scene) -> Self {
+        Self {
+            knob_values: value.knob_values.clone(),
+            filters: [
+                value.music_filter,
+                value.ambience_filter,
+                value.sound_effect_filter,
+            ],
+            set_music_volume: value.album.is_some(),
+            set_ambience_volume: value.ambience.is_some(),
+        }
+    }
+}
+
+const fn get_filters(state: &MusicState) -> [&Arc<Mutex<FilterData>>; 3] {
+    [
+        &state.music_filter,
+        &state.ambience_filter,
+        &state.sound_effect_filter,
+    ]
+}
+
+pub struct SceneHandler;
+
+impl SceneHandler {
+    /// In scene bank mode a pad recalls its scene, with SHIFT toggled it saves the current mix on it
+    pub fn handle_scene_pad(pad: u8, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+        let shift = state
+            .data
+            .lock()
+            .is_ok_and(|data| data.button_states.contains(ToggleStates::SHIFT));
+        if shift {
+            Self::capture(Some(pad), state, midi_out);
+        } else if let Some(index) = state
+            .scene_library
+            .lock()
+            .ok()
+            .and_then(|x| x.get_scene_on_pad(pad))
+        {
+            Self::recall(index, state, midi_out);
+        } else {
+            warn!("No scene bound to pad {pad}");
+        }
+    }
+
+    /// Saves the current mix as a new scene, or over the scene already bound to the pad
+    pub fn capture(pad: Option<u8>, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+        let Some(scene) = Self::get_current_scene(pad, state) else {
+            warn!("Failed to read the current mix, will not save the scene");
+            return;
+        };
+        let scene_mode = state
+            .data
+            .lock()
+            .is_ok_and(|data| data.button_states.contains(ToggleStates::PAN));
+        if let Ok(mut library) = state.scene_library.lock() {
+            let index = library.insert(scene);
+            info!("Saved scene {}", library.scenes[index].name);
+            if let Err(e) = library.write_to_file() {
+                warn!("Failed to save scenes: {e}");
+            }
+            if let Some(out) = midi_out
+                && scene_mode
+            {
+                PadHandler::light_scene_pads(out, &library);
+            }
+        } else {
+            warn!("Failed to get a lock on scenes. Will not save the scene");
+        }
+    }
+
+    fn get_current_scene(pad: Option<u8>, state: &MusicState) -> Option<Scene> {
+        let name = format!(
+            "Scene {}",
+            state.scene_library.lock().map_or(1, |x| x.scenes.len() + 1)
+        );
+        let [music_filter, ambience_filter, sound_effect_filter] =
+            get_filters(state).map(|x| x.lock().ok().map(|f| SceneFilter::from(&*f)));
+        let data = state.data.lock().ok()?;
+        let audio_sinks = state.audio_sinks.lock().ok()?;
+        let is_music_playing = !audio_sinks.music_queue.empty();
+        let mut scene = Scene {
+            name,
+            pad,
+            morph_seconds: 0.,
+            album: data.last_pad_pressed.filter(|_| is_music_playing),
+            track: data
+                .current_playlist
+                .clone()
+                .filter(|_| is_music_playing)
+                .map(|x| PadHandler::get_current_playlist_state(x, &audio_sinks.music_queue))
+                .as_ref()
+                .and_then(PlaylistData::get_current_track)
+                .map(|x| x.file_path),
+            ambience: data
+                .sounding_ambience
+                .filter(|_| !audio_sinks.ambience_queue.empty())
+                .map(|x| SceneAmbience {
+                    bank: data.keyboard_bank,
+                    key: x.key,
+                }),
+            knob_values: data.knob_values.iter().map(|(k, v)| (*k, *v)).collect(),
+            music_filter: music_filter?,
+            ambience_filter: ambience_filter?,
+            sound_effect_filter: sound_effect_filter?,
+            toggles: vec![],
+        };
+        drop(audio_sinks);
+        scene.set_toggle_states(data.button_states);
+        drop(data);
+        Some(scene)
+    }
+
+    /// Brings back the scene, knobs and filters move over its morph time
+    pub fn recall(index: usize, state: &MusicState, mut midi_out: Option<&mut ChannelOutput>) {
+        let Some(scene) = state
+            .scene_library
+            .lock()
+            .ok()
+            .and_then(|x| x.scenes.get(index).cloned())
+        else {
+            warn!("There is no scene {index}, will not recall it");
+            return;
+        };
+        info!("Recalling scene {}", scene.name);
+        let recall_id = LAST_RECALL.fetch_add(1, Ordering::Relaxed) + 1;
+
+        Self::restore_toggles(scene.get_toggle_states(), state, midi_out.as_deref_mut());
+        Self::restore_music(&scene, state, midi_out.as_deref_mut());
+        Self::restore_ambience(scene.ambience, state);
+        Self::restore_pause(state);
+
+        if let Ok(mut tracker) = state.knob_tracker.lock() {
+            scene
+                .knob_values
+                .keys()
+                .for_each(|knob| tracker.release(*knob));
+        }
+        let target = MixLevels::from(&scene);
+        let duration = Duration::try_from_secs_f32(scene.morph_seconds).unwrap_or_default();
+        if duration >= MORPH_STEP
+            && let Some(current) = Self::get_current_levels(state)
+        {
+            let state = state.clone();
+            thread::spawn(move || Self::morph(&current, &target, duration, &state, recall_id));
+        } else {
+            Self::apply_levels(&target, state);
+        }
+
+        if let Some(out) = midi_out
+            && let Ok(data) = state.data.lock()
+        {
+            if !data.button_states.contains(ToggleStates::PAN) {
+                PadHandler::light_pad_page(out, &data);
+            } else if let Ok(library) = state.scene_library.lock() {
+                PadHandler::light_scene_pads(out, &library);
+            }
+        }
+    }
+
+    /// Presses the buttons whose state differs from the scene, so that their side effects apply
+    fn restore_toggles(
+        target: ToggleStates,
+        state: &MusicState,
+        mut midi_out: Option<&mut ChannelOutput>,
+    ) {
+        let Ok(current) = state.data.lock().map(|x| x.button_states) else {
+            warn!("Failed to get a lock on data. Will not restore the toggled buttons");
+            return;
+        };
+        for (toggle_state, input_group) in TOGGLE_INPUTS {
+            if current.contains(toggle_state) != target.contains(toggle_state) {
+                debug!("Scene toggles {toggle_state:?}");
+                PadHandler::handle_input_pressed(midi_out.as_deref_mut(), input_group, 1, state);
+            }
+        }
+    }
+
+    /// Keeps the music going if the scene track is already playing, otherwise restarts the album from it
+    fn restore_music(scene: &Scene, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+        let Some(album) = scene.album else {
+            if let Ok(mut data) = state.data.lock()
+                && let Ok(audio_sinks) = state.audio_sinks.lock()
+            {
+                playback_handler::stop_track(&audio_sinks.music_queue);
+                data.current_playlist = None;
+            }
+            return;
+        };
+        if Self::get_playing_track(state) == Some((album, scene.track.clone())) {
+            debug!("Album {album} is already playing, keeping it");
+            return;
+        }
+        Self::play_album_from_track(album, scene.track.as_deref(), state, midi_out);
+    }
+
+    /// Plays the album, skipping the tracks before the given one
+    pub fn play_album_from_track(
+        album: usize,
+        track: Option<&str>,
+        state: &MusicState,
+        midi_out: Option<&mut ChannelOutput>,
+    ) {
+        PadHandler::play_album(album, state, midi_out);
+        if let Some(track) = track
+            && let Ok(data) = state.data.lock()
+            && let Ok(audio_sinks) = state.audio_sinks.lock()
+        {
+            let position = data
+                .current_playlist
+                .as_ref()
+                .and_then(|x| x.tracks.iter().position(|t| t.file_path == *track));
+            if let Some(position) = position {
+                (0..position).for_each(|_| audio_sinks.music_queue.skip_one());
+            } else {
+                warn!("Track {track} is no longer in album {album}, playing it from the start");
+            }
+        }
+    }
+
+    fn get_playing_track(state: &MusicState) -> Option<(usize, Option<String>)> {
+        let data = state.data.lock().ok()?;
+        let audio_sinks = state.audio_sinks.lock().ok()?;
+        if audio_sinks.music_queue.empty() {
+            return None;
+        }
+        let track = data
+            .current_playlist
+            .clone()
+            .map(|x| PadHandler::get_current_playlist_state(x, &audio_sinks.music_queue))
+            .as_ref()
+            .and_then(PlaylistData::get_current_track)
+            .map(|x| x.file_path);
+        drop(audio_sinks);
+        data.last_pad_pressed.map(|album| (album, track))
+    }
+
+    fn restore_ambience(target: Option<SceneAmbience>, state: &MusicState) {
+        let sound_state = SoundState::from(state);
+        let Ok(mut data) = state.data.lock() else {
+            warn!("Failed to get a lock on data. Will not restore the ambience");
+            return;
+        };
+        let is_playing = state
+            .audio_sinks
+            .lock()
+            .is_ok_and(|x| !x.ambience_queue.empty());
+        let current = data
+            .sounding_ambience
+            .filter(|_| is_playing)
+            .map(|x| SceneAmbience {
+                bank: data.keyboard_bank,
+                key: x.key,
+            });
+        if current == target {
+            return;
+        }
+        if let Some(ambience) = target {
+            data.keyboard_bank = ambience.bank;
+            KeyboardHandler::update_key_labels(&mut data);
+            // A toggle ambience on the same key would be stopped instead of replaced
+            data.sounding_ambience = None;
+            KeyboardHandler::play_key(ambience.key, AMBIENCE_VELOCITY, &mut data, &sound_state);
+        } else {
+            KeyboardHandler::stop_ambience(&mut data, &sound_state);
+        }
+    }
+
+    /// Starting the scene music and ambience resumes the queues, pause them again if needed
+    pub fn restore_pause(state: &MusicState) {
+        if let Ok(data) = state.data.lock()
+            && let Ok(audio_sinks) = state.audio_sinks.lock()
+        {
+            if data.button_states.contains(ToggleStates::STOP_ALL) {
+                playback_handler::pause_track(&audio_sinks.music_queue);
+                playback_handler::pause_track(&audio_sinks.ambience_queue);
+                playback_handler::pause_track(&audio_sinks.sound_effect_queue);
+            } else if data.button_states.contains(ToggleStates::CLIP_STOP) {
+                playback_handler::pause_track(&audio_sinks.music_queue);
+            }
+        }
+    }
+
+    fn get_current_levels(state: &MusicState) -> Option<MixLevels> {
+        let knob_values = state
+            .data
+            .lock()
+            .ok()?
+            .knob_values
+            .iter()
+            .map(|(k, v)| (*k, *v))
+            .collect();
+        let [music, ambience, sound_effect] =
+            get_filters(state).map(|x| x.lock().ok().map(|f| SceneFilter::from(&*f)));
+        Some(MixLevels {
+            knob_values,
+            filters: [music?, ambience?, sound_effect?],
+            set_music_volume: true,
+            set_ambience_volume: true,
+        })
+    }
+
+    fn morph(
+        current: &MixLevels,
+        target: &MixLevels,
+        duration: Duration,
+        state: &MusicState,
+        recall_id: usize,
+    ) {
+        let steps = u32::try_from(duration.as_millis() / MORPH_STEP.as_millis())
+            .unwrap_or(u32::MAX)
+            .max(1);
+        for step in 1..=steps {
+            if LAST_RECALL.load(Ordering::Relaxed) != recall_id {
+                debug!("Another scene was recalled, stopping the morph");
+                return;
+            }
+            Self::apply_levels(
+                &current.interpolate(target, step as f32 / steps as f32),
+                state,
+            );
+            if step < steps {
+                thread::sleep(MORPH_STEP);
+            }
+        }
+    }
+
+    fn apply_levels(levels: &MixLevels, state: &MusicState) {
+        if let Ok(mut data) = state.data.lock() {
+            for (knob, value) in &levels.knob_values {
+                data.knob_values.insert(*knob, value.clamp(0., 1.));
+            }
+            if let Ok(audio_sinks) = state.audio_sinks.lock() {
+                if levels.set_music_volume {
+                    let volume = if data.button_states.contains(ToggleStates::MUTE) {
+                        Some(0.)
+                    } else {
+                        data.get_music_volume()
+                    };
+                    if let Some(volume) = volume {
+                        playback_handler::change_volume(&audio_sinks.music_queue, volume);
+                    }
+                }
+                if levels.set_ambience_volume
+                    && let Some(volume) = data.get_ambience_volume()
+                {
+                    playback_handler::change_volume(&audio_sinks.ambience_queue, volume);
+                }
+                if let Some(volume) = data.get_sound_effect_volume() {
+                    playback_handler::change_volume(&audio_sinks.sound_effect_queue, volume);
+                }
+            } else {
+                warn!("Failed to get audio sink lock, could not restore the volumes");
+            }
+            PadHandler::update_gui(&state.tx_data, &data);
+        } else {
+            warn!("Failed to get a lock on data. Will not restore the knobs");
+        }
+        for (filter, target) in get_filters(state).into_iter().zip(levels.filters) {
+            playback_handler::set_filter_frequency_value(
+                filter,
+                target.percentage,
+                target.kind.into(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::scene::FilterKind;
+
+    fn get_levels(volume: f32, filter: f32, kind: FilterKind) -> MixLevels {
+        let filter = SceneFilter {
+            percentage: filter,
+            kind,
+        };
+        MixLevels {
+            knob_values: BTreeMap::from([(1, volume), (5, volume)]),
+            filters: [filter; 3],
+            set_music_volume: true,
+            set_ambience_volume: false,
+        }
+    }
+
+    #[test]
+    fn morph_starts_at_the_current_mix_and_ends_at_the_scene() {
+        let current = get_levels(0.2, 1., FilterKind::AllPass);
+        let mut target = get_levels(0.8, 0.5, FilterKind::LowPass);
+        target.knob_values.insert(7, 0.4);
+
+        let start = current.interpolate(&target, 0.);
+        assert!((start.knob_values[&1] - 0.2).abs() < f32::EPSILON);
+        assert!((start.filters[0].percentage - 1.).abs() < f32::EPSILON);
+        // A knob the current mix does not know jumps straight to the scene value
+        assert!((start.knob_values[&7] - 0.4).abs() < f32::EPSILON);
+
+        let end = current.interpolate(&target, 1.);
+        assert_eq!(end.knob_values, target.knob_values);
+        assert_eq!(end.filters, target.filters);
+        assert!(end.set_music_volume && !end.set_ambience_volume);
+
+        let middle = current.interpolate(&target, 0.5);
+        assert!((middle.knob_values[&5] - 0.5).abs() < f32::EPSILON);
+        assert_eq!(middle.filters[2].kind, FilterKind::LowPass);
+    }
+}
@@ -0,0 +1,220 @@
+use crate::audio::playback_handler;
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::backend::scene_handler::SceneHandler;
+use crate::states::app_paths::AppPaths;
+use crate::states::button_states::ToggleStates;
+use crate::states::music_state::MusicState;
+use crate::states::playlist_data::PlaylistData;
+use crate::states::scene::SceneFilter;
+use crate::states::session::{ResumeOffer, Session};
+use log::{debug, info, warn};
+use ramidier::io::output::ChannelOutput;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+/// Time given to the skipped tracks to leave the queue, before seeking in the resumed one
+const SEEK_TIMEOUT: Duration = Duration::from_secs(1);
+const SEEK_POLL: Duration = Duration::from_millis(10);
+
+pub struct SessionHandler;
+
+impl SessionHandler {
+    /// Saves the session every few seconds, so that a crash loses little of it
+    pub fn start_autosave(state: &MusicState) {
+        let state = state.clone();
+        thread::spawn(move || {
+            let mut last_saved = None;
+            loop {
+                thread::sleep(AUTOSAVE_INTERVAL);
+                let session = Self::capture(&state);
+                if session.is_some() && session != last_saved {
+                    Self::write(session.as_ref(), &Self::get_path(&state));
+                    last_saved = session;
+                }
+            }
+        });
+    }
+
+    pub fn save(state: &MusicState) {
+        Self::write(Self::capture(state).as_ref(), &Self::get_path(state));
+    }
+
+    /// The session belongs to the profile loaded when it is saved
+    fn get_path(state: &MusicState) -> String {
+        let profile = state.data.lock().ok().and_then(|x| x.profile.clone());
+        AppPaths::builder().maybe_profile(profile).build().session
+    }
+
+    fn write(session: Option<&Session>, path: &str) {
+        if let Some(session) = session {
+            if let Err(e) = session.write_to_file(path) {
+                warn!("Failed to save the session to {path}: {e}");
+            } else {
+                debug!("Session saved to {path}");
+            }
+        } else {
+            warn!("Failed to read the runtime state, will not save the session");
+        }
+    }
+
+    fn capture(state: &MusicState) -> Option<Session> {
+        let [music_filter, ambience_filter, sound_effect_filter] = [
+            &state.music_filter,
+            &state.ambience_filter,
+            &state.sound_effect_filter,
+        ]
+        .map(|x| x.lock().ok().map(|f| SceneFilter::from(&*f)));
+        let data = state.data.lock().ok()?;
+        let audio_sinks = state.audio_sinks.lock().ok()?;
+        let track = data
+            .current_playlist
+            .clone()
+            .filter(|_| !audio_sinks.music_queue.empty())
+            .map(|x| PadHandler::get_current_playlist_state(x, &audio_sinks.music_queue))
+            .as_ref()
+            .and_then(PlaylistData::get_current_track);
+        drop(audio_sinks);
+        // An offer not answered yet is kept for the next start
+        let playback = data
+            .last_pad_pressed
+            .zip(track)
+            .map(|(album, track)| ResumeOffer {
+                album,
+                track: track.file_path,
+                elapsed_seconds: track.elapsed_seconds,
+            })
+            .or_else(|| data.resume_offer.clone());
+        let session = Session {
+            knob_values: data.knob_values.iter().map(|(k, v)| (*k, *v)).collect(),
+            music_filter: music_filter?,
+            ambience_filter: ambience_filter?,
+            sound_effect_filter: sound_effect_filter?,
+            toggles: (data.button_states & ToggleStates::SESSION_STATES).get_names(),
+            last_pad_pressed: data.last_pad_pressed,
+            pad_page: data.pad_page,
+            keyboard_bank: data.keyboard_bank,
+            playback,
+        };
+        drop(data);
+        Some(session)
+    }
+
+    /// Brings back knobs, volumes, filters, toggled buttons and pages of the previous session.
+    /// The music it was playing is only offered, see `resume`
+    pub fn restore(path: &str, state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+        let session = match Session::load_from_file(path) {
+            Ok(session) => session,
+            Err(e) => {
+                debug!("No session restored from {path}: {e}");
+                return;
+            }
+        };
+        let filters = [
+            (&state.music_filter, session.music_filter),
+            (&state.ambience_filter, session.ambience_filter),
+            (&state.sound_effect_filter, session.sound_effect_filter),
+        ];
+        for (filter, target) in filters {
+            playback_handler::set_filter_frequency_value(
+                filter,
+                target.percentage,
+                target.kind.into(),
+            );
+        }
+        let Ok(mut data) = state.data.lock() else {
+            warn!("Failed to get a lock on data. Will not restore the session");
+            return;
+        };
+        for (knob, value) in &session.knob_values {
+            data.knob_values.insert(*knob, value.clamp(0., 1.));
+        }
+        data.button_states = session.get_toggle_states();
+        data.last_pad_pressed = session.last_pad_pressed;
+        data.pad_page = session.pad_page.min(data.get_pad_page_count() - 1);
+        data.keyboard_bank = session.keyboard_bank;
+        KeyboardHandler::update_key_labels(&mut data);
+        if let Ok(audio_sinks) = state.audio_sinks.lock() {
+            let music_volume = if data.button_states.contains(ToggleStates::MUTE) {
+                Some(0.)
+            } else {
+                data.get_music_volume()
+            };
+            for (sink, volume) in [
+                (&audio_sinks.music_queue, music_volume),
+                (&audio_sinks.ambience_queue, data.get_ambience_volume()),
+                (
+                    &audio_sinks.sound_effect_queue,
+                    data.get_sound_effect_volume(),
+                ),
+            ] {
+                if let Some(volume) = volume {
+                    playback_handler::change_volume(sink, volume);
+                }
+            }
+        } else {
+            warn!("Failed to get audio sink lock, could not restore the volumes");
+        }
+        if let Some(offer) = &session.playback {
+            info!(
+                "Album {} was playing, resume it from the GUI or with teatro ctl resume",
+                offer.album
+            );
+        }
+        data.resume_offer = session.playback;
+        if let Some(out) = midi_out {
+            PadHandler::light_controller(out, &data, state);
+        }
+        PadHandler::update_gui(&state.tx_data, &data);
+        drop(data);
+        info!("Session restored from {path}");
+    }
+
+    /// Plays the music of the previous session again, from its track and position
+    pub fn resume(state: &MusicState, midi_out: Option<&mut ChannelOutput>) {
+        let Some(offer) = state
+            .data
+            .lock()
+            .ok()
+            .and_then(|mut x| x.resume_offer.take())
+        else {
+            info!("There is no previous session to resume");
+            return;
+        };
+        SceneHandler::play_album_from_track(offer.album, Some(&offer.track), state, midi_out);
+        let remaining = state.data.lock().ok().and_then(|data| {
+            let tracks = &data.current_playlist.as_ref()?.tracks;
+            Some(tracks.len() - tracks.iter().position(|x| x.file_path == offer.track)?)
+        });
+        if let Some(remaining) = remaining {
+            Self::seek_when_current(remaining, Duration::from_secs(offer.elapsed_seconds), state);
+        }
+        SceneHandler::restore_pause(state);
+    }
+
+    /// The skipped tracks leave the queue in the audio thread, the seek waits for the resumed one
+    fn seek_when_current(remaining: usize, position: Duration, state: &MusicState) {
+        let started = Instant::now();
+        while started.elapsed() < SEEK_TIMEOUT {
+            if let Ok(audio_sinks) = state.audio_sinks.lock()
+                && audio_sinks.music_queue.len() <= remaining
+            {
+                if let Err(e) = audio_sinks.music_queue.try_seek(position) {
+                    warn!("Failed to seek the resumed track: {e}");
+                }
+                return;
+            }
+            thread::sleep(SEEK_POLL);
+        }
+        warn!("The resumed track did not start in time, playing it from the start");
+    }
+
+    pub fn dismiss(state: &MusicState) {
+        if let Ok(mut data) = state.data.lock() {
+            data.resume_offer = None;
+            PadHandler::update_gui(&state.tx_data, &data);
+        }
+    }
+}
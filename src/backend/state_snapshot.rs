@@ -0,0 +1,74 @@
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::states::music_state::MusicState;
+use crate::states::playlist_data::PlaylistData;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Everything a remote needs to draw the controller, sent as JSON
+#[derive(Serialize, Debug)]
+pub struct StateSnapshot {
+    pub pad_labels: Vec<String>,
+    pub pad_page: usize,
+    pub pad_page_count: usize,
+    pub last_pad_pressed: Option<usize>,
+    pub key_labels: Vec<String>,
+    pub keyboard_bank: usize,
+    pub keyboard_bank_count: usize,
+    pub knob_values: BTreeMap<u8, f32>,
+    pub button_states: Vec<String>,
+    pub current_playlist: Option<PlaylistData>,
+}
+
+impl StateSnapshot {
+    pub fn take(state: &MusicState) -> Option<Self> {
+        // Same lock order as the MIDI and keyboard handlers, data before the sinks
+        let data = state.data.lock().ok()?;
+        let audio_sinks = state.audio_sinks.lock().ok()?;
+        let current_playlist = data.current_playlist.clone().map(|playlist| {
+            PadHandler::get_current_playlist_state(playlist, &audio_sinks.music_queue)
+        });
+        drop(audio_sinks);
+        Some(Self {
+            pad_labels: data.pad_labels.clone(),
+            pad_page: data.pad_page,
+            pad_page_count: data.get_pad_page_count(),
+            last_pad_pressed: data.last_pad_pressed,
+            key_labels: data.key_labels.clone(),
+            keyboard_bank: data.keyboard_bank,
+            keyboard_bank_count: data.keyboard_bank_count,
+            knob_values: data.knob_values.iter().map(|(k, v)| (*k, *v)).collect(),
+            button_states: data
+                .button_states
+                .iter_names()
+                .map(|(name, _)| name.to_string())
+                .collect(),
+            current_playlist,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn snapshot_waiting_on_the_data_does_not_hold_the_sinks() {
+        let (music_state, _sound_state, _rx_data) = crate::get_test_states();
+        let data = music_state.data.lock().expect("The data lock is free");
+        let snapshot_state = music_state.clone();
+        let snapshot = thread::spawn(move || StateSnapshot::take(&snapshot_state));
+        thread::sleep(Duration::from_millis(100));
+        let sinks_free = music_state.audio_sinks.try_lock().is_ok();
+        drop(data);
+        assert!(sinks_free);
+        assert!(
+            snapshot
+                .join()
+                .expect("The snapshot thread should not panic")
+                .is_some()
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::states::music_state::MusicState;
+use crate::states::sound_state::SoundState;
+use flume::{Receiver, Sender};
+use log::{debug, info, warn};
+use midi_msg::MidiMsg;
+use ramidier::enums::input_group::{ChannelKind, PadsAndKnobsChannel};
+use ramidier::io::output::ChannelOutput;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+/// Channel a virtual message is handled as
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VirtualPort {
+    Pads,
+    Keyboard,
+}
+
+impl VirtualPort {
+    pub const ALL: [Self; 2] = [Self::Pads, Self::Keyboard];
+
+    pub const fn get_port_name(self) -> &'static str {
+        match self {
+            Self::Pads => "Teatro Pads",
+            Self::Keyboard => "Teatro Keyboard",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct VirtualMidiMessage {
+    pub port: VirtualPort,
+    pub stamp: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// In-process MIDI input, raw bytes sent here are handled exactly like the ones coming from the controller
+#[derive(Debug, Clone)]
+pub struct MidiInjector {
+    tx_midi: Sender<VirtualMidiMessage>,
+    started: Instant,
+}
+
+impl MidiInjector {
+    pub fn send(&self, port: VirtualPort, bytes: &[u8]) -> anyhow::Result<()> {
+        Ok(self.tx_midi.send(VirtualMidiMessage {
+            port,
+            stamp: self.started.elapsed().as_micros() as u64,
+            bytes: bytes.to_vec(),
+        })?)
+    }
+}
+
+/// Starts handling virtual MIDI input and, if enabled in the settings, exposes
+/// ALSA virtual ports so that other software can connect to Teatro
+pub fn start_virtual_input(
+    music_state: &MusicState,
+    sound_state: &SoundState,
+    midi_out: Option<Arc<Mutex<ChannelOutput>>>,
+) -> MidiInjector {
+    let (tx_midi, rx_midi) = flume::unbounded::<VirtualMidiMessage>();
+    let injector = MidiInjector {
+        tx_midi,
+        started: Instant::now(),
+    };
+    let (m_state, s_state) = (music_state.clone(), sound_state.clone());
+    thread::spawn(move || {
+        handle_virtual_input(&rx_midi, &m_state, &s_state, midi_out.as_ref());
+    });
+
+    let expose_ports = music_state
+        .data
+        .lock()
+        .ok()
+        .and_then(|data| data.settings_data.lock().ok().map(|x| x.virtual_midi_ports))
+        .unwrap_or_default();
+    if expose_ports {
+        #[cfg(unix)]
+        expose_virtual_ports(injector.clone());
+        #[cfg(not(unix))]
+        warn!("Virtual MIDI ports are not supported on this platform");
+    }
+    injector
+}
+
+fn handle_virtual_input(
+    rx_midi: &Receiver<VirtualMidiMessage>,
+    music_state: &MusicState,
+    sound_state: &SoundState,
+    midi_out: Option<&Arc<Mutex<ChannelOutput>>>,
+) {
+    while let Ok(message) = rx_midi.recv() {
+        debug!(
+            "{}: virtual {:?} {:?}",
+            message.stamp, message.port, message.bytes
+        );
+        let Ok((msg, _)) = MidiMsg::from_midi(&message.bytes) else {
+            warn!("Invalid MIDI message {:?}, ignoring it", message.bytes);
+            continue;
+        };
+        match message.port {
+            VirtualPort::Pads => {
+                if let Some(data) = PadsAndKnobsChannel::decode(&msg) {
+                    let mut out = midi_out.and_then(|x| x.lock().ok());
+                    PadHandler::handle_midi_input(out.as_deref_mut(), &data, music_state);
+                }
+            }
+            VirtualPort::Keyboard => {
+                if let Some(data) = KeyboardHandler::decode(&msg) {
+                    KeyboardHandler::handle_midi_input(&data, sound_state);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn expose_virtual_ports(injector: MidiInjector) {
+    use midir::MidiInput;
+    use midir::os::unix::VirtualInput;
+
+    thread::spawn(move || {
+        let _connections: Vec<_> = VirtualPort::ALL
+            .into_iter()
+            .filter_map(|port| {
+                let midi_in = MidiInput::new(port.get_port_name())
+                    .inspect_err(|e| warn!("Could not initialize MIDI input for {port:?}: {e}"))
+                    .ok()?;
+                midi_in
+                    .create_virtual(
+                        port.get_port_name(),
+                        move |_stamp, bytes, injector: &mut MidiInjector| {
+                            if let Err(e) = injector.send(port, bytes) {
+                                warn!("Failed to forward virtual MIDI message: {e}");
+                            }
+                        },
+                        injector.clone(),
+                    )
+                    .inspect(|_| info!("Virtual MIDI port '{}' is open", port.get_port_name()))
+                    .inspect_err(|e| warn!("Could not open virtual port {port:?}: {e}"))
+                    .ok()
+            })
+            .collect();
+        // The ports stay open as long as their connections are alive
+        loop {
+            thread::park();
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::button_states::ToggleStates;
+    use crate::states::midi_macro::MacroAction;
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    fn is_shift_active(state: &MusicState) -> bool {
+        state
+            .data
+            .lock()
+            .is_ok_and(|x| x.button_states.contains(ToggleStates::SHIFT))
+    }
+
+    #[test]
+    fn pad_bytes_change_the_runtime_data() {
+        let (music_state, sound_state, rx_data) = crate::get_test_states();
+        let injector = start_virtual_input(&music_state, &sound_state, None);
+        // Note on of SHIFT
+        injector
+            .send(VirtualPort::Pads, &[0x90, 98, 127])
+            .expect("The virtual input should be running");
+        let update = rx_data
+            .recv_timeout(TIMEOUT)
+            .expect("The GUI should be updated");
+        assert!(update.button_states.contains(ToggleStates::SHIFT));
+        assert!(is_shift_active(&music_state));
+    }
+
+    #[test]
+    fn keyboard_bytes_keep_the_velocity() {
+        let (music_state, sound_state, _rx_data) = crate::get_test_states();
+        sound_state
+            .macro_recorder
+            .lock()
+            .expect("The recorder should not be poisoned")
+            .start();
+        let injector = start_virtual_input(&music_state, &sound_state, None);
+        injector
+            .send(VirtualPort::Keyboard, &[0x90, 48, 100])
+            .expect("The virtual input should be running");
+        injector
+            .send(VirtualPort::Keyboard, &[0x80, 48, 0])
+            .expect("The virtual input should be running");
+
+        // The recorder keeps recording until both events are in, or the time is over
+        let started = Instant::now();
+        while started.elapsed() < TIMEOUT
+            && sound_state
+                .macro_recorder
+                .lock()
+                .is_ok_and(|x| x.events.len() < 2)
+        {
+            thread::sleep(Duration::from_millis(10));
+        }
+        let events = {
+            let mut recorder = sound_state
+                .macro_recorder
+                .lock()
+                .expect("The recorder should not be poisoned");
+            recorder.stop();
+            recorder
+                .take_pending()
+                .map(|x| x.events)
+                .unwrap_or_default()
+        };
+        let actions: Vec<_> = events
+            .iter()
+            .map(|x| match x.action {
+                MacroAction::Keyboard { key, velocity } => (key, velocity),
+                _ => panic!("Only keyboard actions were sent"),
+            })
+            .collect();
+        assert_eq!(actions, vec![(1, 100), (1, 0)]);
+    }
+
+    #[test]
+    fn invalid_bytes_are_skipped() {
+        let (music_state, sound_state, rx_data) = crate::get_test_states();
+        let injector = start_virtual_input(&music_state, &sound_state, None);
+        injector
+            .send(VirtualPort::Pads, &[0x90, 98])
+            .expect("The virtual input should be running");
+        injector
+            .send(VirtualPort::Pads, &[0x42])
+            .expect("The virtual input should be running");
+        injector
+            .send(VirtualPort::Pads, &[0x90, 98, 127])
+            .expect("The virtual input should be running");
+        let update = rx_data
+            .recv_timeout(TIMEOUT)
+            .expect("The GUI should be updated");
+        assert!(update.button_states.contains(ToggleStates::SHIFT));
+    }
+}
@@ -0,0 +1,197 @@
+use crate::backend::cue_list_handler::CueListHandler;
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::library_handler::LibraryHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::comms::command::CommsCommand;
+use crate::states::app_paths::AppPaths;
+use crate::states::cue_list::CueList;
+use crate::states::library_index::LibraryIndex;
+use crate::states::music_state::MusicState;
+use crate::states::settings_data::SettingsData;
+use crate::states::visualizer::RuntimeData;
+use flume::{Receiver, RecvTimeoutError, Sender};
+use hotwatch::{Event, EventKind, Hotwatch};
+use log::{info, warn};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Quiet time after the last library change before the pads are refreshed
+const LIBRARY_SETTLE_TIME: Duration = Duration::from_millis(500);
+
+fn update_pads(music_state: &MusicState, tx_data: &Sender<RuntimeData>) {
+    if let Ok(mut data) = music_state.data.lock() {
+        KeyboardHandler::update_key_labels(&mut data);
+        PadHandler::update_pad_labels(&mut data);
+        PadHandler::update_gui(tx_data, &data);
+    }
+}
+
+fn get_library_folders(settings_data: &Arc<Mutex<SettingsData>>) -> Vec<String> {
+    settings_data
+        .lock()
+        .map_or_else(|_| vec!["music".to_string()], |x| x.get_library_folders())
+}
+
+/// Watches the folders and their sub folders, new, removed and renamed files are indexed
+/// and reported
+fn observe_folders(
+    watchdog: &mut Hotwatch,
+    folders: &[String],
+    tx_command: &Sender<CommsCommand>,
+    library_index: &Arc<Mutex<LibraryIndex>>,
+) {
+    for folder in folders {
+        let tx_command = tx_command.clone();
+        let library_index = library_index.clone();
+        if let Err(e) = watchdog.watch(folder, move |event: Event| {
+            if !matches!(
+                event.kind,
+                EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+            ) {
+                return;
+            }
+            LibraryHandler::update_paths(&library_index, &event.paths);
+            if let Err(e) = tx_command.send(CommsCommand::LibraryChanged) {
+                warn!("Failed to report the library change: {e}");
+            }
+        }) {
+            warn!("Error while observing folder {folder}: {e}");
+        }
+    }
+}
+
+/// Files replaced by a rename (editors, sync tools) are only seen again once watched anew,
+/// so the watchdog is told about every change and watches the config again
+fn observe_config(watchdog: &mut Hotwatch, config_path: &str, tx_command: Sender<CommsCommand>) {
+    if let Err(e) = watchdog.watch(config_path, move |event: Event| {
+        if matches!(
+            event.kind,
+            EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_)
+        ) && let Err(e) = tx_command.send(CommsCommand::ConfigChanged)
+        {
+            warn!("Failed to report the config change: {e}");
+        }
+    }) {
+        warn!("Error while observing the config file {config_path}: {e}");
+    }
+}
+
+/// The config of the profile loaded, the one given at startup until the profile changes
+fn get_config_path(paths: &AppPaths, music_state: &MusicState) -> String {
+    let profile = music_state.data.lock().ok().and_then(|x| x.profile.clone());
+    if profile == paths.profile {
+        paths.config.clone()
+    } else {
+        AppPaths::builder().maybe_profile(profile).build().config
+    }
+}
+
+/// Applies the config edited outside of Teatro, the settings in use are kept if it is invalid.
+/// The window is told through the config revision, even when nothing changed.
+/// The path is resolved again, the profile may have switched since the config was watched
+fn reload_config(paths: &AppPaths, music_state: &MusicState) {
+    let config_path = get_config_path(paths, music_state);
+    let result = SettingsData::load_from_config(&config_path);
+    let Ok(mut data) = music_state.data.lock() else {
+        warn!("Failed to get a lock on data. Will not reload the config");
+        return;
+    };
+    data.config_revision += 1;
+    let settings = match result {
+        Ok((settings, issues)) => {
+            for issue in &issues {
+                warn!("{issue}");
+            }
+            settings
+        }
+        Err(e) => {
+            warn!("Keeping the current settings, {e:#}");
+            return;
+        }
+    };
+    let Ok(mut current) = data.settings_data.lock() else {
+        warn!("Failed to get a lock on the settings. Will not reload the config");
+        return;
+    };
+    let is_adventure_changed = current.adventure != settings.adventure;
+    current.copy_data(&settings);
+    drop(current);
+    if is_adventure_changed {
+        let folder = AppPaths::builder()
+            .maybe_profile(data.profile.clone())
+            .build()
+            .cue_lists;
+        if let Ok(mut cue_list) = music_state.cue_list.lock() {
+            *cue_list = CueList::load_adventure(&folder, &settings.adventure)
+                .unwrap_or_else(|e| CueList::unloaded(&e));
+        }
+        CueListHandler::reset(music_state);
+    }
+    drop(data);
+    info!("Settings reloaded from {config_path}");
+}
+
+pub fn handle_watchdog(
+    settings_data: &Arc<Mutex<SettingsData>>,
+    (tx_command, rx_command): (&Sender<CommsCommand>, &Receiver<CommsCommand>),
+    tx_data: &Sender<RuntimeData>,
+    music_state: &MusicState,
+    paths: &AppPaths,
+) {
+    let mut hotwatch = Hotwatch::new().expect("hotwatch failed to initialize!");
+    let library_index = music_state
+        .data
+        .lock()
+        .map(|x| x.library_index.clone())
+        .expect("Data should be readable at startup");
+    let mut last_folders = get_library_folders(settings_data);
+    let mut last_config = get_config_path(paths, music_state);
+    observe_folders(&mut hotwatch, &last_folders, tx_command, &library_index);
+    observe_config(&mut hotwatch, &last_config, tx_command.clone());
+
+    let mut is_library_changed = false;
+    loop {
+        // A copy of many files is reported file by file, the pads wait for it to be over
+        let command = if is_library_changed {
+            match rx_command.recv_timeout(LIBRARY_SETTLE_TIME) {
+                Err(RecvTimeoutError::Timeout) => {
+                    is_library_changed = false;
+                    LibraryHandler::save(&library_index);
+                    update_pads(music_state, tx_data);
+                    continue;
+                }
+                x => x.ok(),
+            }
+        } else {
+            rx_command.recv().ok()
+        };
+        match command {
+            Some(CommsCommand::LibraryChanged) => {
+                is_library_changed = true;
+                continue;
+            }
+            Some(CommsCommand::ConfigChanged) => reload_config(paths, music_state),
+            Some(CommsCommand::Refresh) => {}
+            _ => continue,
+        }
+        for folder in &last_folders {
+            if let Err(e) = hotwatch.unwatch(folder) {
+                warn!("Error while unwatching folder {folder}: {e}");
+            }
+        }
+        let folders = get_library_folders(settings_data);
+        if folders != last_folders {
+            LibraryHandler::start_scan(music_state);
+        }
+        last_folders = folders;
+        observe_folders(&mut hotwatch, &last_folders, tx_command, &library_index);
+        is_library_changed = false;
+        update_pads(music_state, tx_data);
+
+        // Also follows the profile switches
+        let _ = hotwatch.unwatch(&last_config);
+        last_config = get_config_path(paths, music_state);
+        observe_config(&mut hotwatch, &last_config, tx_command.clone());
+    }
+}
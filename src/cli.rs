@@ -0,0 +1,266 @@
+use crate::states::app_paths::AppPaths;
+use anyhow::{Context, bail};
+use midir::{MidiInput, MidiOutput};
+use rodio::DeviceTrait;
+use rodio::cpal::traits::HostTrait;
+use std::env;
+use std::process;
+
+const USAGE: &str = "Usage: teatro [OPTIONS]
+       teatro ctl <command>
+
+Options:
+  --config <path>        Settings file [env: CONFIG_PATH, default: config.toml]
+  --data <path>          Information list file [env: DATA_PATH, default: data.json]
+  --profile <name>       Keep config, data, macros, scenes, cue lists and session in profiles/<name> [env: PROFILES_PATH for the folder]
+  --headless             Run without the window, state changes are logged
+  --no-midi              Do not open any MIDI port
+  --list-midi-ports      Print the MIDI ports and exit
+  --list-profiles        Print the profiles and exit
+  --list-audio-devices   Print the audio output devices and exit
+  -V, --version          Print the version and exit
+  -h, --help             Print this help and exit";
+
+/// How the application was asked to run
+#[derive(Debug, Clone)]
+pub struct CliOptions {
+    pub paths: AppPaths,
+    pub headless: bool,
+    pub no_midi: bool,
+}
+
+impl CliOptions {
+    /// Builds without the GUI always run headless
+    pub const fn is_headless(&self) -> bool {
+        self.headless || !cfg!(feature = "gui")
+    }
+}
+
+#[derive(Debug)]
+enum CliCommand {
+    Run(Box<CliOptions>),
+    Ctl(Vec<String>),
+    ListMidiPorts,
+    ListProfiles,
+    ListAudioDevices,
+    Version,
+    Help,
+}
+
+/// Reads the command line, commands that do not start the application are run here and exit the process
+pub fn parse_or_exit() -> CliOptions {
+    match parse_args(env::args().skip(1)) {
+        Ok(CliCommand::Run(options)) => *options,
+        Ok(command) => process::exit(run_command(command)),
+        Err(e) => {
+            eprintln!("{e}\n\n{USAGE}");
+            process::exit(2);
+        }
+    }
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> anyhow::Result<CliCommand> {
+    let (mut config, mut data, mut profile) = (None, None, None);
+    let (mut headless, mut no_midi) = (false, false);
+    let mut first = true;
+    while let Some(arg) = args.next() {
+        if first && arg == "ctl" {
+            return Ok(CliCommand::Ctl(args.collect()));
+        }
+        first = false;
+        let (flag, inline_value) = arg
+            .split_once('=')
+            .map_or((arg.as_str(), None), |(flag, value)| {
+                (flag, Some(value.to_string()))
+            });
+        let mut get_value = || {
+            inline_value
+                .clone()
+                .or_else(|| args.next())
+                .filter(|x| !x.is_empty())
+                .with_context(|| format!("{flag} expects a value"))
+        };
+        match flag {
+            "--config" => config = Some(get_value()?),
+            "--data" => data = Some(get_value()?),
+            "--profile" => profile = Some(get_value()?),
+            "--headless"
+            | "--no-midi"
+            | "--list-midi-ports"
+            | "--list-profiles"
+            | "--list-audio-devices"
+            | "-V"
+            | "--version"
+            | "-h"
+            | "--help"
+                if inline_value.is_some() =>
+            {
+                bail!("{flag} does not take a value")
+            }
+            "--headless" => headless = true,
+            "--no-midi" => no_midi = true,
+            "--list-midi-ports" => return Ok(CliCommand::ListMidiPorts),
+            "--list-profiles" => return Ok(CliCommand::ListProfiles),
+            "--list-audio-devices" => return Ok(CliCommand::ListAudioDevices),
+            "-V" | "--version" => return Ok(CliCommand::Version),
+            "-h" | "--help" => return Ok(CliCommand::Help),
+            _ => bail!("Unknown argument {arg}"),
+        }
+    }
+    Ok(CliCommand::Run(Box::new(CliOptions {
+        paths: AppPaths::builder()
+            .maybe_config(config)
+            .maybe_data(data)
+            .maybe_profile(profile)
+            .build(),
+        headless,
+        no_midi,
+    })))
+}
+
+/// Runs a command that does not start the application, returns the exit code
+fn run_command(command: CliCommand) -> i32 {
+    match command {
+        CliCommand::Run(_) => unreachable!("Running the application is handled by the caller"),
+        #[cfg(unix)]
+        CliCommand::Ctl(args) => {
+            crate::control::run_client(&AppPaths::builder().build().control_socket, &args)
+        }
+        #[cfg(not(unix))]
+        CliCommand::Ctl(_) => {
+            eprintln!(
+                "teatro ctl needs a Unix control socket, it is not available on this platform"
+            );
+            1
+        }
+        CliCommand::ListMidiPorts => report(list_midi_ports()),
+        CliCommand::ListProfiles => {
+            for profile in AppPaths::get_profiles() {
+                println!("{profile}");
+            }
+            0
+        }
+        CliCommand::ListAudioDevices => report(list_audio_devices()),
+        CliCommand::Version => {
+            println!("teatro {}", env!("CARGO_PKG_VERSION"));
+            0
+        }
+        CliCommand::Help => {
+            println!("{USAGE}");
+            0
+        }
+    }
+}
+
+fn report(result: anyhow::Result<()>) -> i32 {
+    if let Err(e) = result {
+        eprintln!("{e}");
+        1
+    } else {
+        0
+    }
+}
+
+fn list_midi_ports() -> anyhow::Result<()> {
+    let input = MidiInput::new("teatro")?;
+    println!("MIDI inputs:");
+    for (i, port) in input.ports().iter().enumerate() {
+        println!("  {i}: {}", input.port_name(port)?);
+    }
+    let output = MidiOutput::new("teatro")?;
+    println!("MIDI outputs:");
+    for (i, port) in output.ports().iter().enumerate() {
+        println!("  {i}: {}", output.port_name(port)?);
+    }
+    Ok(())
+}
+
+fn list_audio_devices() -> anyhow::Result<()> {
+    let host = rodio::cpal::default_host();
+    let default_name = host.default_output_device().and_then(|x| x.name().ok());
+    println!("Audio outputs ({}):", host.id().name());
+    for device in host.output_devices()? {
+        let name = device.name()?;
+        let marker = if default_name.as_ref() == Some(&name) {
+            " (default)"
+        } else {
+            ""
+        };
+        println!("  {name}{marker}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> anyhow::Result<CliCommand> {
+        parse_args(args.iter().map(ToString::to_string))
+    }
+
+    fn parse_options(args: &[&str]) -> CliOptions {
+        match parse(args) {
+            Ok(CliCommand::Run(options)) => *options,
+            other => panic!("{args:?} should run the application, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn no_argument_runs_with_the_window_and_midi() {
+        let options = parse_options(&[]);
+        assert!(!options.headless);
+        assert!(!options.no_midi);
+        assert!(options.paths.profile.is_none());
+    }
+
+    #[test]
+    fn values_are_read_inline_or_from_the_next_argument() {
+        let options = parse_options(&["--headless", "--config=live.toml", "--no-midi"]);
+        assert!(options.headless);
+        assert!(options.no_midi);
+        assert_eq!(options.paths.config, "live.toml");
+        let options = parse_options(&["--profile", "live"]);
+        assert_eq!(options.paths.profile.as_deref(), Some("live"));
+        let options = parse_options(&["--config=a=b.toml"]);
+        assert_eq!(options.paths.config, "a=b.toml");
+    }
+
+    #[test]
+    fn switches_refuse_a_value() {
+        for arg in ["--headless=false", "--no-midi=0", "--version=1", "--help="] {
+            assert!(parse(&[arg]).is_err(), "{arg}");
+        }
+    }
+
+    #[test]
+    fn paths_refuse_an_empty_or_missing_value() {
+        for args in [
+            &["--profile="][..],
+            &["--config", ""],
+            &["--data"],
+            &["--headless", "--profile"],
+        ] {
+            assert!(parse(args).is_err(), "{args:?}");
+        }
+    }
+
+    #[test]
+    fn ctl_takes_the_rest_only_as_first_argument() {
+        assert!(matches!(
+            parse(&["ctl", "pad", "--headless"]),
+            Ok(CliCommand::Ctl(args)) if args == ["pad", "--headless"]
+        ));
+        assert!(parse(&["--headless", "ctl"]).is_err());
+    }
+
+    #[test]
+    fn listing_and_unknown_arguments() {
+        assert!(matches!(
+            parse(&["--headless", "--list-profiles"]),
+            Ok(CliCommand::ListProfiles)
+        ));
+        assert!(matches!(parse(&["-V"]), Ok(CliCommand::Version)));
+        assert!(parse(&["--verbose"]).is_err());
+    }
+}
@@ -0,0 +1,68 @@
+use crate::states::knob_value_update::KnobValueUpdate;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum CommsCommand {
+    Refresh,
+    PadPressed {
+        key: u8,
+    },
+    WhiteKeyPressed {
+        key: u8,
+        velocity: u8,
+    },
+    BlackKeyPressed {
+        key: u8,
+        velocity: u8,
+    },
+    KeyReleased {
+        key: u8,
+    },
+    KnobPercentageChanged {
+        knob: u8,
+        value: KnobValueUpdate,
+    },
+    /// Moves the knob straight to the given percentage, between 0 and 1
+    KnobValueSet {
+        knob: u8,
+        value: f32,
+    },
+    LoopPressed,
+    ShufflePressed,
+    SkipTrackPressed,
+    MutePressed,
+    PausePressed,
+    StopAllPressed,
+    SoloPressed,
+    PreviousPadPagePressed,
+    NextPadPagePressed,
+    PreviousKeyboardBankPressed,
+    NextKeyboardBankPressed,
+    /// Saves the current mix as a scene, bound to the pad if given
+    SceneCaptured {
+        pad: Option<u8>,
+    },
+    SceneRecalled {
+        index: usize,
+    },
+    /// Changes how fast the macro bound to the pad is replayed, see `MidiMacro::time_scale`
+    MacroTimeScaleSet {
+        pad: u8,
+        time_scale: f32,
+    },
+    /// Fires the next cue of the cue list
+    CueGoPressed,
+    /// Moves the cue list back to its first cue
+    CueListReset,
+    /// Plays the music of the previous session from where it stopped
+    SessionResumed,
+    SessionResumeDismissed,
+    /// Switches to the profile with this folder name in the profiles folder
+    ProfileSelected {
+        name: String,
+    },
+    /// The config file changed on disk, handled by the watchdog
+    ConfigChanged,
+    /// A file was added, removed or renamed in a library folder, handled by the watchdog
+    LibraryChanged,
+}
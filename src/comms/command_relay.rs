@@ -0,0 +1,220 @@
+use crate::MidiOutputChannels;
+use crate::backend::cue_list_handler::CueListHandler;
+use crate::backend::hw_handler::MidiHandler;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::macro_handler::MacroHandler;
+use crate::backend::pad_handler::PadHandler;
+use crate::backend::profile_handler::ProfileHandler;
+use crate::backend::scene_handler::SceneHandler;
+use crate::backend::session_handler::SessionHandler;
+use crate::comms::command::CommsCommand;
+use crate::states::button_states::ToggleStates;
+use crate::states::midi_macro::MacroAction;
+use crate::states::music_state::MusicState;
+use crate::states::sound_state::SoundState;
+use crate::states::visualizer::RuntimeData;
+use flume::{Receiver, Sender};
+use log::{debug, warn};
+use ramidier::enums::button::knob_ctrl::KnobCtrlKey;
+use ramidier::enums::button::pads::PadKey;
+use ramidier::enums::button::soft_keys::SoftKey;
+use ramidier::enums::input_group::{KeyboardInputGroup, PadsAndKnobsInputGroup};
+use ramidier::io::output::ChannelOutput;
+
+#[allow(clippy::too_many_lines)] // one arm per command, like the controller buttons they mirror
+pub fn relay_commands_to_backend(
+    rx_command: &Receiver<CommsCommand>,
+    tx_command: &Sender<CommsCommand>,
+    tx_data: &Sender<RuntimeData>,
+    music_state: &MusicState,
+    sound_state: &SoundState,
+    midi_out_channel: Option<MidiOutputChannels>,
+) {
+    loop {
+        if let Ok(command) = rx_command.recv() {
+            debug!("{command:?}");
+            if let Some(action) = get_macro_action(&command)
+                && let Ok(mut recorder) = music_state.macro_recorder.lock()
+            {
+                recorder.record(action);
+            }
+            let mut out_channel = midi_out_channel
+                .as_ref()
+                .and_then(|out| out.midi_out.lock().ok());
+            match command {
+                CommsCommand::Refresh => {
+                    if let Ok(data) = music_state.data.lock()
+                        && let Ok(audio_sink) = music_state.audio_sinks.lock()
+                    {
+                        let refreshed_data = PadHandler::refresh(&data, tx_data, &audio_sink);
+                        if let Some(out) = out_channel.as_deref_mut()
+                            && data.button_states.contains(ToggleStates::VOLUME)
+                        {
+                            PadHandler::show_meters(out, &refreshed_data, &audio_sink);
+                        }
+                    }
+                }
+                CommsCommand::PadPressed { key } => {
+                    if let Ok(padkey) = PadKey::try_from(key) {
+                        PadHandler::handle_input_pressed(
+                            out_channel.as_deref_mut(),
+                            PadsAndKnobsInputGroup::Pads(padkey),
+                            1,
+                            music_state,
+                        );
+                        refresh_backend(tx_command);
+                    } else {
+                        warn!("Invalid padkey, will not update data");
+                    }
+                }
+                CommsCommand::BlackKeyPressed { key, velocity }
+                | CommsCommand::WhiteKeyPressed { key, velocity } => {
+                    KeyboardHandler::handle_input(
+                        KeyboardInputGroup::Key(key),
+                        velocity,
+                        sound_state,
+                    );
+                }
+                CommsCommand::KeyReleased { key } => {
+                    KeyboardHandler::handle_release(KeyboardInputGroup::Key(key), sound_state);
+                }
+                CommsCommand::KnobPercentageChanged { knob, value } => {
+                    PadHandler::handle_gui_knob(knob, value, music_state);
+                    refresh_backend(tx_command);
+                }
+                CommsCommand::KnobValueSet { knob, value } => {
+                    PadHandler::set_gui_knob(knob, value, music_state);
+                    refresh_backend(tx_command);
+                }
+                CommsCommand::ShufflePressed => PadHandler::handle_input_pressed(
+                    out_channel.as_deref_mut(),
+                    PadsAndKnobsInputGroup::KnobCtrl(KnobCtrlKey::Send),
+                    1,
+                    music_state,
+                ),
+                CommsCommand::LoopPressed => PadHandler::handle_input_pressed(
+                    out_channel.as_deref_mut(),
+                    PadsAndKnobsInputGroup::SoftKeys(SoftKey::Select),
+                    1,
+                    music_state,
+                ),
+                CommsCommand::SkipTrackPressed => press_and_release(
+                    out_channel.as_deref_mut(),
+                    PadsAndKnobsInputGroup::Right,
+                    music_state,
+                ),
+                CommsCommand::PreviousPadPagePressed | CommsCommand::NextPadPagePressed => {
+                    let input_group = if matches!(command, CommsCommand::NextPadPagePressed) {
+                        PadsAndKnobsInputGroup::Down
+                    } else {
+                        PadsAndKnobsInputGroup::Up
+                    };
+                    press_and_release(out_channel.as_deref_mut(), input_group, music_state);
+                    refresh_backend(tx_command);
+                }
+                CommsCommand::MutePressed => PadHandler::handle_input_pressed(
+                    out_channel.as_deref_mut(),
+                    PadsAndKnobsInputGroup::SoftKeys(SoftKey::Mute),
+                    1,
+                    music_state,
+                ),
+                CommsCommand::PausePressed => PadHandler::handle_input_pressed(
+                    out_channel.as_deref_mut(),
+                    PadsAndKnobsInputGroup::SoftKeys(SoftKey::ClipStop),
+                    1,
+                    music_state,
+                ),
+                CommsCommand::StopAllPressed => PadHandler::handle_input_pressed(
+                    out_channel.as_deref_mut(),
+                    PadsAndKnobsInputGroup::StopAllClips,
+                    1,
+                    music_state,
+                ),
+                CommsCommand::SoloPressed => PadHandler::handle_input_pressed(
+                    out_channel.as_deref_mut(),
+                    PadsAndKnobsInputGroup::SoftKeys(SoftKey::Solo),
+                    1,
+                    music_state,
+                ),
+                CommsCommand::PreviousKeyboardBankPressed => {
+                    KeyboardHandler::change_bank(-1, &sound_state.data);
+                    refresh_backend(tx_command);
+                }
+                CommsCommand::NextKeyboardBankPressed => {
+                    KeyboardHandler::change_bank(1, &sound_state.data);
+                    refresh_backend(tx_command);
+                }
+                _ => {
+                    if relay_mix_command(&command, music_state, out_channel.as_deref_mut()) {
+                        refresh_backend(tx_command);
+                    } else {
+                        warn!("Unsupported command: {command:?}");
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Scenes, macros, cue list and session resume, which drive several parts of the mix at once.
+/// Returns false if the command is not one of them
+fn relay_mix_command(
+    command: &CommsCommand,
+    music_state: &MusicState,
+    midi_out: Option<&mut ChannelOutput>,
+) -> bool {
+    match command {
+        CommsCommand::SceneCaptured { pad } => SceneHandler::capture(*pad, music_state, midi_out),
+        CommsCommand::SceneRecalled { index } => {
+            SceneHandler::recall(*index, music_state, midi_out);
+        }
+        CommsCommand::MacroTimeScaleSet { pad, time_scale } => {
+            MacroHandler::set_time_scale(*pad, *time_scale, music_state);
+        }
+        CommsCommand::CueGoPressed => CueListHandler::go(music_state),
+        CommsCommand::CueListReset => CueListHandler::reset(music_state),
+        CommsCommand::SessionResumed => SessionHandler::resume(music_state, midi_out),
+        CommsCommand::SessionResumeDismissed => SessionHandler::dismiss(music_state),
+        CommsCommand::ProfileSelected { name } => {
+            ProfileHandler::switch(name, music_state, midi_out);
+        }
+        _ => return false,
+    }
+    true
+}
+
+/// How a command is stored in a macro, pad presses are stored as if they came from the controller
+fn get_macro_action(command: &CommsCommand) -> Option<MacroAction> {
+    match command {
+        CommsCommand::Refresh
+        | CommsCommand::SceneCaptured { .. }
+        | CommsCommand::MacroTimeScaleSet { .. }
+        | CommsCommand::SessionResumed
+        | CommsCommand::SessionResumeDismissed
+        | CommsCommand::ProfileSelected { .. }
+        | CommsCommand::ConfigChanged
+        | CommsCommand::LibraryChanged => None,
+        CommsCommand::PadPressed { key } => Some(MacroAction::Pad {
+            control: *key,
+            value: 1,
+        }),
+        _ => Some(MacroAction::Command {
+            command: command.clone(),
+        }),
+    }
+}
+
+fn press_and_release(
+    mut midi_out: Option<&mut ChannelOutput>,
+    input_group: PadsAndKnobsInputGroup,
+    music_state: &MusicState,
+) {
+    PadHandler::handle_input_pressed(midi_out.as_deref_mut(), input_group, 1, music_state);
+    PadHandler::handle_input_released(midi_out, input_group, 1, music_state);
+}
+
+fn refresh_backend(tx_command: &Sender<CommsCommand>) {
+    if let Err(e) = tx_command.send(CommsCommand::Refresh {}) {
+        warn!("Couldn't send refresh command. Error: {e}");
+    }
+}
@@ -0,0 +1,38 @@
+use crate::control::request::{ControlRequest, USAGE};
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+
+/// Sends the `teatro ctl` arguments to the running instance and prints its answer, returns the exit code
+pub fn run_client(path: &Path, args: &[String]) -> i32 {
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+    if args.is_empty() || matches!(args[0], "-h" | "--help" | "help") {
+        println!("{USAGE}");
+        return 0;
+    }
+    // Checked here too, so that typos do not need a running instance
+    if let Err(e) = ControlRequest::parse(&args) {
+        eprintln!("{e}");
+        return 2;
+    }
+    let answer = UnixStream::connect(path).and_then(|mut stream| {
+        writeln!(stream, "{}", args.join(" "))?;
+        let mut answer = String::new();
+        stream.read_to_string(&mut answer)?;
+        Ok(answer)
+    });
+    match answer {
+        Ok(answer) if answer.starts_with("error:") => {
+            eprint!("{answer}");
+            1
+        }
+        Ok(answer) => {
+            print!("{answer}");
+            0
+        }
+        Err(e) => {
+            eprintln!("Could not reach Teatro on {}: {e}", path.display());
+            1
+        }
+    }
+}
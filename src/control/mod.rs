@@ -0,0 +1,133 @@
+mod client;
+mod request;
+
+pub use client::run_client;
+
+use crate::backend::state_snapshot::StateSnapshot;
+use crate::comms::command::CommsCommand;
+use crate::control::request::ControlRequest;
+use crate::states::app_paths::AppPaths;
+use crate::states::music_state::MusicState;
+use flume::Sender;
+use log::{debug, info, warn};
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::time::Duration;
+
+/// Time given to a client to send its request
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Accepts `teatro ctl` connections, each one sends a single line and gets a single answer
+pub fn start_control_server(path: &Path, state: &MusicState, tx_command: Sender<CommsCommand>) {
+    if UnixStream::connect(path).is_ok() {
+        warn!(
+            "Another instance is listening on {}, control socket will not be available",
+            path.display()
+        );
+        return;
+    }
+    // Left behind by an instance that did not exit cleanly
+    let _ = fs::remove_file(path);
+    let listener = match UnixListener::bind(path) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Could not create control socket {}: {e}", path.display());
+            return;
+        }
+    };
+    // Only the user running Teatro may control it, the fallback path is shared in /tmp
+    if let Err(e) = fs::set_permissions(path, fs::Permissions::from_mode(0o600)) {
+        warn!("Could not restrict control socket {}: {e}", path.display());
+        let _ = fs::remove_file(path);
+        return;
+    }
+    info!("Control socket listening on {}", path.display());
+    let state = state.clone();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let (state, tx_command) = (state.clone(), tx_command.clone());
+                    std::thread::spawn(move || {
+                        if let Err(e) = handle_connection(stream, &state, &tx_command) {
+                            debug!("Control connection closed: {e}");
+                        }
+                    });
+                }
+                Err(e) => warn!("Failed to accept control connection: {e}"),
+            }
+        }
+    });
+}
+
+fn handle_connection(
+    mut stream: UnixStream,
+    state: &MusicState,
+    tx_command: &Sender<CommsCommand>,
+) -> anyhow::Result<()> {
+    // A client that never ends its line is dropped instead of holding its thread
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut line = String::new();
+    BufReader::new(&stream).read_line(&mut line)?;
+    let args: Vec<&str> = line.split_whitespace().collect();
+    let answer = match ControlRequest::parse(&args) {
+        Ok(ControlRequest::Command(CommsCommand::ProfileSelected { name }))
+            if !AppPaths::get_profiles().contains(&name) =>
+        {
+            format!("error: There is no profile {name}")
+        }
+        Ok(ControlRequest::Command(command)) => {
+            tx_command.send(command)?;
+            "ok".to_string()
+        }
+        Ok(ControlRequest::Status { json }) => match StateSnapshot::take(state) {
+            Some(snapshot) if json => serde_json::to_string_pretty(&snapshot)?,
+            Some(snapshot) => describe_snapshot(&snapshot),
+            None => "error: failed to read the current state".to_string(),
+        },
+        Err(e) => format!("error: {e}"),
+    };
+    writeln!(stream, "{answer}")?;
+    Ok(())
+}
+
+fn describe_snapshot(snapshot: &StateSnapshot) -> String {
+    let mut status = format!(
+        "Pad page: {}/{}\nKeys bank: {}/{}\n",
+        snapshot.pad_page + 1,
+        snapshot.pad_page_count,
+        snapshot.keyboard_bank + 1,
+        snapshot.keyboard_bank_count
+    );
+    if let Some(album) = snapshot
+        .last_pad_pressed
+        .and_then(|x| snapshot.pad_labels.get(x))
+    {
+        let _ = writeln!(status, "Album: {album}");
+    }
+    if let Some(playlist) = &snapshot.current_playlist
+        && let Some(track) = playlist.get_current_track()
+    {
+        let _ = writeln!(
+            status,
+            "Track {}/{}: {} ({}s/{}s)",
+            playlist.current_track + 1,
+            playlist.tracks.len(),
+            track.file_path,
+            track.elapsed_seconds,
+            track.track_length
+        );
+    }
+    let knobs: Vec<String> = snapshot
+        .knob_values
+        .iter()
+        .map(|(knob, value)| format!("{knob}={value:.2}"))
+        .collect();
+    let _ = writeln!(status, "Knobs: {}", knobs.join(" "));
+    let _ = write!(status, "Buttons: {}", snapshot.button_states.join(" "));
+    status
+}
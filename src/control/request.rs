@@ -0,0 +1,166 @@
+use crate::backend::keyboard_handler::{
+    N_OF_KEYS, get_ambience_key, get_sound_effect_key, is_ambience_key,
+};
+use crate::comms::command::CommsCommand;
+use crate::states::visualizer::PADS_PER_PAGE;
+use anyhow::{Context, bail};
+
+const DEFAULT_VELOCITY: u8 = 100;
+
+pub const USAGE: &str = "Usage: teatro ctl <command>
+
+Commands:
+  pad <0-39> | pad next | pad previous
+  sfx <1-15> [velocity]
+  ambience <1-10> [velocity]
+  key <1-25> [velocity]
+  release <1-25>
+  bank next | bank previous
+  volume <music|ambience|sfx> <0-1>
+  knob <1-8> <0-1>
+  pause | skip | stop | mute | solo | loop | shuffle
+  scene <number> | scene save
+  macro <0-39> scale <factor>
+  go | cue reset
+  resume
+  profile <name>
+  status [--json]";
+
+/// What a control client asked for, one request per connection
+#[derive(Debug, Clone)]
+pub enum ControlRequest {
+    Command(CommsCommand),
+    Status { json: bool },
+}
+
+impl ControlRequest {
+    pub fn parse(args: &[&str]) -> anyhow::Result<Self> {
+        let command = match args {
+            ["status"] => return Ok(Self::Status { json: false }),
+            ["status", "--json"] => return Ok(Self::Status { json: true }),
+            ["pad", "next"] => CommsCommand::NextPadPagePressed,
+            ["pad", "previous"] => CommsCommand::PreviousPadPagePressed,
+            ["pad", pad] => CommsCommand::PadPressed {
+                key: parse_in_range(pad, 0, PADS_PER_PAGE as u8 - 1)?,
+            },
+            ["sfx", index, velocity @ ..] => {
+                let key = get_sound_effect_key(parse_in_range(index, 1, 15)?)
+                    .context("No key plays this sound effect")?;
+                get_key_command(key, velocity)?
+            }
+            ["ambience", index, velocity @ ..] => {
+                let key = get_ambience_key(parse_in_range(index, 1, 10)?)
+                    .context("No key plays this ambience")?;
+                get_key_command(key, velocity)?
+            }
+            ["key", key, velocity @ ..] => {
+                get_key_command(parse_in_range(key, 1, N_OF_KEYS)?, velocity)?
+            }
+            ["release", key] => CommsCommand::KeyReleased {
+                key: parse_in_range(key, 1, N_OF_KEYS)?,
+            },
+            ["bank", "next"] => CommsCommand::NextKeyboardBankPressed,
+            ["bank", "previous"] => CommsCommand::PreviousKeyboardBankPressed,
+            ["volume", channel, value] => CommsCommand::KnobValueSet {
+                knob: match *channel {
+                    "music" => 1,
+                    "ambience" => 5,
+                    "sfx" => 7,
+                    _ => bail!("Unknown channel {channel}, expected music, ambience or sfx"),
+                },
+                value: parse_percentage(value)?,
+            },
+            ["knob", knob, value] => CommsCommand::KnobValueSet {
+                knob: parse_in_range(knob, 1, 8)?,
+                value: parse_percentage(value)?,
+            },
+            ["pause"] => CommsCommand::PausePressed,
+            ["skip"] => CommsCommand::SkipTrackPressed,
+            ["stop"] => CommsCommand::StopAllPressed,
+            ["mute"] => CommsCommand::MutePressed,
+            ["solo"] => CommsCommand::SoloPressed,
+            ["loop"] => CommsCommand::LoopPressed,
+            ["shuffle"] => CommsCommand::ShufflePressed,
+            ["scene", "save"] => CommsCommand::SceneCaptured { pad: None },
+            ["scene", number] => CommsCommand::SceneRecalled {
+                index: number
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|x| x.checked_sub(1))
+                    .with_context(|| format!("Expected a scene number from 1, got {number}"))?,
+            },
+            ["macro", pad, "scale", time_scale] => CommsCommand::MacroTimeScaleSet {
+                pad: parse_in_range(pad, 0, PADS_PER_PAGE as u8 - 1)?,
+                time_scale: time_scale
+                    .parse::<f32>()
+                    .ok()
+                    .filter(|x| x.is_finite() && *x > 0.)
+                    .with_context(|| format!("Expected a positive time scale, got {time_scale}"))?,
+            },
+            ["go"] => CommsCommand::CueGoPressed,
+            // The running instance checks that it exists, its profiles folder may not be this one
+            ["profile", name] => CommsCommand::ProfileSelected {
+                name: (*name).to_string(),
+            },
+            ["cue", "reset"] => CommsCommand::CueListReset,
+            ["resume"] => CommsCommand::SessionResumed,
+            _ => bail!("Unknown command: {}\n\n{USAGE}", args.join(" ")),
+        };
+        Ok(Self::Command(command))
+    }
+}
+
+fn get_key_command(key: u8, velocity: &[&str]) -> anyhow::Result<CommsCommand> {
+    let velocity = match velocity {
+        [] => DEFAULT_VELOCITY,
+        [velocity] => parse_in_range(velocity, 1, 127)?,
+        _ => bail!("Too many arguments, expected an optional velocity"),
+    };
+    Ok(if is_ambience_key(key) {
+        CommsCommand::BlackKeyPressed { key, velocity }
+    } else {
+        CommsCommand::WhiteKeyPressed { key, velocity }
+    })
+}
+
+fn parse_in_range(text: &str, min: u8, max: u8) -> anyhow::Result<u8> {
+    text.parse::<u8>()
+        .ok()
+        .filter(|x| (min..=max).contains(x))
+        .with_context(|| format!("Expected a number between {min} and {max}, got {text}"))
+}
+
+fn parse_percentage(text: &str) -> anyhow::Result<f32> {
+    text.parse::<f32>()
+        .ok()
+        .filter(|x| (0.0..=1.0).contains(x))
+        .with_context(|| format!("Expected a value between 0 and 1, got {text}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn profile_is_sent_by_name_without_looking_for_it() {
+        assert!(matches!(
+            ControlRequest::parse(&["profile", "not-in-this-folder"]),
+            Ok(ControlRequest::Command(CommsCommand::ProfileSelected { name }))
+                if name == "not-in-this-folder"
+        ));
+        assert!(ControlRequest::parse(&["profile"]).is_err());
+    }
+
+    #[test]
+    fn macro_time_scale_has_to_be_positive() {
+        assert!(matches!(
+            ControlRequest::parse(&["macro", "3", "scale", "0.5"]),
+            Ok(ControlRequest::Command(CommsCommand::MacroTimeScaleSet { pad: 3, time_scale }))
+                if (time_scale - 0.5).abs() < f32::EPSILON
+        ));
+        for scale in ["0", "-1", "inf", "fast"] {
+            assert!(ControlRequest::parse(&["macro", "3", "scale", scale]).is_err());
+        }
+        assert!(ControlRequest::parse(&["macro", "40", "scale", "2"]).is_err());
+    }
+}
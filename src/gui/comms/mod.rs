@@ -1,7 +1 @@
-pub mod command;
-#[cfg(feature = "gui")]
-pub mod to_backend_from_gui;
-#[cfg(feature = "gui")]
 pub mod to_gui_from_backend;
-#[cfg(feature = "gui")]
-pub mod watchdog_handler;
@@ -1,24 +1,26 @@
-use crate::gui::comms::command::CommsCommand;
+use crate::comms::command::CommsCommand;
 use crate::gui::comms::to_gui_from_backend::sync_gui_with_data_received_from_backend;
 use crate::gui::gui_wrapper::GuiWrapper;
 use crate::gui::ui::{AkaiVisualizer, GuiData};
+use crate::states::app_paths::AppPaths;
+use crate::states::cue_list::CueList;
+use crate::states::scene::SceneLibrary;
 use crate::states::settings_data::SettingsData;
 use crate::states::visualizer::RuntimeData;
 use flume::{Receiver, Sender};
 use log::debug;
-use std::env;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 
 pub fn gui_initializer(
     backend_data: RuntimeData,
     settings: Arc<Mutex<SettingsData>>,
-    tx_command: Sender<CommsCommand>,
+    libraries: (Arc<Mutex<SceneLibrary>>, Arc<Mutex<CueList>>),
+    paths: &AppPaths,
+    (tx_command, watchdog_tx): (Sender<CommsCommand>, Sender<CommsCommand>),
     rx_data: Receiver<RuntimeData>,
-    watchdog_tx: Sender<CommsCommand>,
+    config_issues: Vec<String>,
 ) -> eframe::Result {
-    let font_folder = env::var("FONT_FOLDER").unwrap_or_else(|_| "ui/fonts".to_string());
-
     let mut options = eframe::NativeOptions {
         viewport: egui::ViewportBuilder::default()
             .with_inner_size([1200.0, 800.0])
@@ -29,11 +31,7 @@ pub fn gui_initializer(
 
     let gui_data = GuiData::new(backend_data, tx_command, watchdog_tx);
 
-    let icon_path = format!(
-        "{}/{}",
-        env::var("ICON_PATH").unwrap_or_else(|_| "ui/icons".to_string()),
-        "teatro.png"
-    );
+    let icon_path = format!("{}/{}", paths.icons, "teatro.png");
     if let Ok(icon_bytes) = std::fs::read(&icon_path)
         && let Ok(d) = eframe::icon_data::from_png_bytes(&icon_bytes)
     {
@@ -44,7 +42,7 @@ pub fn gui_initializer(
     let arc_gui_data = Arc::new(Mutex::new(gui_data));
     let gui_data_sync = arc_gui_data.clone();
 
-    let data_path = env::var("DATA_PATH").unwrap_or_else(|_| "data.json".to_string());
+    let paths = paths.clone();
     eframe::run_native(
         "Teatro - Akai APC Key 25 Controller",
         options,
@@ -62,8 +60,9 @@ pub fn gui_initializer(
                 cc,
                 &settings,
                 arc_gui_data,
-                &font_folder,
-                &data_path,
+                libraries,
+                &paths,
+                config_issues,
             )));
             std::thread::spawn(move || {
                 sync_gui_with_data_received_from_backend(&rx_data, &gui_data_sync);
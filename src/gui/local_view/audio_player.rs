@@ -1,4 +1,4 @@
-use crate::gui::comms::command::CommsCommand;
+use crate::comms::command::CommsCommand;
 use crate::gui::local_view::audio_player_states::PlayerStatus;
 use crate::gui::ui::AkaiVisualizer;
 use eframe::emath::{Pos2, Rect, Vec2};
@@ -101,7 +101,7 @@ impl AkaiVisualizer {
 
         Self::draw_player_background(ui, player_rect, scale);
 
-        let (file_path, progress, elapsed_ms, total_ms, current_status) = self.get_player_data();
+        let (title, progress, elapsed_ms, total_ms, current_status) = self.get_player_data();
 
         self.draw_control_buttons(ui, player_rect, content_padding, scale, current_status);
         Self::draw_track_info(
@@ -109,7 +109,7 @@ impl AkaiVisualizer {
             player_rect,
             content_padding,
             scale,
-            &file_path,
+            &title,
             elapsed_ms,
             total_ms,
         );
@@ -161,7 +161,14 @@ impl AkaiVisualizer {
                 0.0
             };
             (
-                track.file_path.clone(),
+                // Tagged title first, then the file name
+                track.title.clone().unwrap_or_else(|| {
+                    std::path::Path::new(&track.file_path)
+                        .file_stem()
+                        .and_then(|n| n.to_str())
+                        .unwrap_or_default()
+                        .to_string()
+                }),
                 prog,
                 gui_data.player_info.local_elapsed,
                 track_length * 1000,
@@ -247,7 +254,7 @@ impl AkaiVisualizer {
                     Ok(())
                 )
             {
-                self.send_command_to_backend(*command);
+                self.send_command_to_backend(command.clone());
             }
         }
     }
@@ -258,7 +265,7 @@ impl AkaiVisualizer {
         player_rect: Rect,
         content_padding: f32,
         scale: f32,
-        file_path: &str,
+        title: &str,
         elapsed_ms: u64,
         total_ms: u64,
     ) {
@@ -266,17 +273,17 @@ impl AkaiVisualizer {
         let buttons_y = content_padding + player_rect.min.y;
         let title_y = 8.0f32.mul_add(scale, buttons_y + button_size.y);
 
-        let title = std::path::Path::new(file_path)
-            .file_stem()
-            .and_then(|n| n.to_str())
-            .unwrap_or("No Track Playing")
-            .to_string();
+        let title = if title.is_empty() {
+            "No Track Playing"
+        } else {
+            title
+        };
 
         draw_text_with_shadow(
             ui.painter(),
             Pos2::new(content_padding + player_rect.min.x, title_y),
             egui::Align2::LEFT_TOP,
-            &title,
+            title,
             FontId {
                 size: 17.0 * scale,
                 family: FontFamily::Name("Pixelify".into()),
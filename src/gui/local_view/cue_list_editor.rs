@@ -0,0 +1,355 @@
+use crate::comms::command::CommsCommand;
+use crate::gui::ui::AkaiVisualizer;
+use crate::states::cue_list::{Cue, CueAction, CueChannel, CueList};
+use crate::states::settings_data::SettingsData;
+use eframe::epaint::Color32;
+use egui::{Frame, Response, RichText, ScrollArea};
+use log::warn;
+use std::path::Path;
+
+enum CueEditAction {
+    SetPlayhead(usize),
+    MoveUp(usize),
+    MoveDown(usize),
+    Delete(usize),
+    Changed,
+}
+
+/// True once a value is set, drags count only when released
+fn is_edited(response: &Response) -> bool {
+    response.drag_stopped() || (response.changed() && !response.dragged())
+}
+
+fn draw_seconds(ui: &mut egui::Ui, seconds: &mut f32, prefix: &str) -> bool {
+    is_edited(
+        &ui.add(
+            egui::DragValue::new(seconds)
+                .range(0.0..=600.0)
+                .speed(0.1)
+                .prefix(prefix)
+                .suffix(" s"),
+        ),
+    )
+}
+
+fn draw_number(ui: &mut egui::Ui, number: &mut usize, prefix: &str) -> bool {
+    is_edited(&ui.add(egui::DragValue::new(number).range(1..=999).prefix(prefix)))
+}
+
+fn draw_volume(ui: &mut egui::Ui, index: usize, channel: &mut CueChannel, value: &mut f32) -> bool {
+    let old_channel = *channel;
+    egui::ComboBox::from_id_salt(("cue_channel", index))
+        .selected_text(channel.to_string())
+        .show_ui(ui, |ui| {
+            for x in CueChannel::ALL {
+                ui.selectable_value(channel, x, x.to_string());
+            }
+        });
+    let is_value_edited = is_edited(
+        &ui.add(
+            egui::DragValue::new(value)
+                .range(0.0..=1.0)
+                .speed(0.01)
+                .max_decimals(2),
+        ),
+    );
+    is_value_edited || old_channel != *channel
+}
+
+/// Editors of the values of the action, returns true if one changed
+fn draw_action_values(
+    ui: &mut egui::Ui,
+    index: usize,
+    action: &mut CueAction,
+    albums: &[String],
+) -> bool {
+    match action {
+        CueAction::PlayAlbum { album } => {
+            let old_album = *album;
+            egui::ComboBox::from_id_salt(("cue_album", index))
+                .width(140.0)
+                .selected_text(
+                    albums
+                        .get(*album)
+                        .cloned()
+                        .unwrap_or_else(|| format!("Album {}", *album + 1)),
+                )
+                .show_ui(ui, |ui| {
+                    for (i, label) in albums.iter().enumerate() {
+                        ui.selectable_value(album, i, label);
+                    }
+                });
+            old_album != *album
+        }
+        CueAction::StartAmbience { number } => draw_number(ui, number, "Ambience "),
+        CueAction::FireSoundEffect { number } => draw_number(ui, number, "Sound effect "),
+        CueAction::RecallScene { number } => draw_number(ui, number, "Scene "),
+        CueAction::SetVolume { channel, value } => draw_volume(ui, index, channel, value),
+        CueAction::Fade {
+            channel,
+            value,
+            seconds,
+        } => {
+            let is_volume_edited = draw_volume(ui, index, channel, value);
+            draw_seconds(ui, seconds, "in ") || is_volume_edited
+        }
+        CueAction::Wait { seconds } => draw_seconds(ui, seconds, ""),
+        CueAction::StopMusic | CueAction::StopAmbience => false,
+    }
+}
+
+fn draw_cue_item(
+    ui: &mut egui::Ui,
+    index: usize,
+    cue: &mut Cue,
+    is_standby: bool,
+    albums: &[String],
+) -> Vec<CueEditAction> {
+    let mut actions = vec![];
+    let (fill, stroke) = if is_standby {
+        (
+            Color32::from_rgb(40, 60, 45),
+            Color32::from_rgb(90, 180, 110),
+        )
+    } else {
+        (Color32::from_rgb(40, 40, 50), Color32::from_rgb(60, 60, 75))
+    };
+
+    Frame::new()
+        .fill(fill)
+        .stroke(egui::Stroke::new(1.0, stroke))
+        .corner_radius(8.0)
+        .inner_margin(egui::vec2(8.0, 6.0))
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .selectable_label(is_standby, format!("{:>2}", index + 1))
+                    .on_hover_text("Set as the next cue fired by GO")
+                    .clicked()
+                {
+                    actions.push(CueEditAction::SetPlayhead(index));
+                }
+                if ui
+                    .add(egui::TextEdit::singleline(&mut cue.name).desired_width(140.0))
+                    .lost_focus()
+                {
+                    actions.push(CueEditAction::Changed);
+                }
+
+                let old_kind = cue.action.get_kind_name();
+                egui::ComboBox::from_id_salt(("cue_kind", index))
+                    .selected_text(old_kind)
+                    .show_ui(ui, |ui| {
+                        for action in CueAction::ALL {
+                            if ui
+                                .selectable_label(
+                                    action.get_kind_name() == old_kind,
+                                    action.get_kind_name(),
+                                )
+                                .clicked()
+                                && action.get_kind_name() != old_kind
+                            {
+                                cue.action = action;
+                                actions.push(CueEditAction::Changed);
+                            }
+                        }
+                    });
+                if draw_action_values(ui, index, &mut cue.action, albums) {
+                    actions.push(CueEditAction::Changed);
+                }
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("✖").on_hover_text("Delete cue").clicked() {
+                        actions.push(CueEditAction::Delete(index));
+                    }
+                    if ui.small_button("⏷").on_hover_text("Move down").clicked() {
+                        actions.push(CueEditAction::MoveDown(index));
+                    }
+                    if ui.small_button("⏶").on_hover_text("Move up").clicked() {
+                        actions.push(CueEditAction::MoveUp(index));
+                    }
+                    let auto_follow = ui
+                        .checkbox(&mut cue.auto_follow, "Auto follow")
+                        .on_hover_text("Fire the next cue once the post-wait is over");
+                    let post_wait = draw_seconds(ui, &mut cue.post_wait, "post ");
+                    let pre_wait = draw_seconds(ui, &mut cue.pre_wait, "pre ");
+                    if auto_follow.changed() || post_wait || pre_wait {
+                        actions.push(CueEditAction::Changed);
+                    }
+                });
+            });
+        });
+
+    actions
+}
+
+impl AkaiVisualizer {
+    pub(crate) fn render_cue_list_tab(&mut self, ui: &mut egui::Ui) {
+        let albums = self
+            .gui_data
+            .lock()
+            .map(|x| x.data.pad_labels.clone())
+            .unwrap_or_default();
+        self.draw_adventure_bar(ui);
+        ui.add_space(8.0);
+
+        let cue_list = self.cue_panel_data.cue_list.clone();
+        let Ok(mut cue_list) = cue_list.lock() else {
+            warn!("Failed to lock the cue list, will not show it");
+            return;
+        };
+        if let Some(error) = cue_list.load_error.clone() {
+            ui.label(RichText::new(format!("⚠ {error}")).color(Color32::from_rgb(255, 100, 100)));
+            if ui
+                .button("Reload")
+                .on_hover_text("Load the cue list again once its file is fixed")
+                .clicked()
+            {
+                drop(cue_list);
+                self.switch_adventure(self.settings_data.adventure.clone());
+            }
+            return;
+        }
+        let mut actions = vec![];
+        ScrollArea::vertical()
+            .id_salt("cue_list")
+            .max_height(ui.available_height() - 40.0)
+            .show(ui, |ui| {
+                let playhead = cue_list.playhead;
+                for (i, cue) in cue_list.cues.iter_mut().enumerate() {
+                    actions.extend(draw_cue_item(ui, i, cue, i == playhead, &albums));
+                    ui.add_space(4.0);
+                }
+            });
+
+        ui.add_space(8.0);
+        if ui.button("+ Add cue").clicked() {
+            cue_list.cues.push(Cue::default());
+            actions.push(CueEditAction::Changed);
+        }
+
+        if Self::apply_cue_actions(&mut cue_list, actions)
+            && let Err(e) = cue_list.write_to_file()
+        {
+            warn!("Failed to write the cue list to file: {e}");
+        }
+    }
+
+    fn draw_adventure_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            let go_button = ui.add(
+                egui::Button::new(RichText::new("GO").size(20.0).strong())
+                    .fill(Color32::from_rgb(40, 120, 60))
+                    .min_size(egui::vec2(80.0, 36.0)),
+            );
+            if go_button.on_hover_text("Fire the next cue").clicked() {
+                self.send_command_to_backend(CommsCommand::CueGoPressed);
+            }
+            if ui
+                .button("Reset")
+                .on_hover_text("Back to the first cue, stops the cues still waiting")
+                .clicked()
+            {
+                self.send_command_to_backend(CommsCommand::CueListReset);
+            }
+            ui.separator();
+
+            ui.label("Adventure");
+            let mut adventure = self.settings_data.adventure.clone();
+            egui::ComboBox::from_id_salt("adventure")
+                .selected_text(&adventure)
+                .show_ui(ui, |ui| {
+                    for x in CueList::get_adventures(&self.cue_panel_data.folder) {
+                        ui.selectable_value(&mut adventure, x.clone(), x);
+                    }
+                });
+            ui.add(
+                egui::TextEdit::singleline(&mut self.cue_panel_data.new_adventure)
+                    .hint_text("New adventure")
+                    .desired_width(140.0),
+            );
+            let new_adventure = self.cue_panel_data.new_adventure.trim().to_string();
+            if ui
+                .add_enabled(
+                    CueList::is_valid_adventure(&new_adventure),
+                    egui::Button::new("Create"),
+                )
+                .on_disabled_hover_text("The name cannot hold a path")
+                .clicked()
+            {
+                adventure = new_adventure;
+                self.cue_panel_data.new_adventure.clear();
+            }
+            if adventure != self.settings_data.adventure {
+                self.switch_adventure(adventure);
+            }
+        });
+    }
+
+    /// Loads the cue list of the adventure and remembers it, other unsaved settings are left alone
+    fn switch_adventure(&mut self, adventure: String) {
+        if !CueList::is_valid_adventure(&adventure) {
+            warn!("Adventure {adventure} is not a valid file name, will not switch to it");
+            return;
+        }
+        // A file that does not load is shown to the user, never replaced by an empty list
+        let cue_list = CueList::load_adventure(&self.cue_panel_data.folder, &adventure)
+            .unwrap_or_else(|e| CueList::unloaded(&e));
+        if !cue_list.path.is_empty()
+            && !Path::new(&cue_list.path).exists()
+            && let Err(e) = cue_list.write_to_file()
+        {
+            warn!("Failed to create the cue list of {adventure}: {e}");
+        }
+        if let Ok(mut shared) = self.cue_panel_data.cue_list.lock() {
+            *shared = cue_list;
+        }
+        self.send_command_to_backend(CommsCommand::CueListReset);
+
+        // A config that does not load is left for the user to fix, not replaced by the defaults
+        match SettingsData::load_from_config(&self.config_path) {
+            Ok((mut saved, _)) => {
+                saved.adventure.clone_from(&adventure);
+                if let Err(e) = saved.write_to_config(&self.config_path) {
+                    warn!("Failed to save the adventure: {e:?}");
+                }
+            }
+            Err(e) => warn!("Will not save the adventure, the settings did not load: {e:#}"),
+        }
+        if let Ok(g_d) = self.gui_data.lock()
+            && let Ok(mut s) = g_d.data.settings_data.lock()
+        {
+            s.adventure.clone_from(&adventure);
+        }
+        self.saved_settings.adventure.clone_from(&adventure);
+        self.settings_data.adventure = adventure;
+    }
+
+    /// Returns true if the cue list has to be saved
+    fn apply_cue_actions(cue_list: &mut CueList, actions: Vec<CueEditAction>) -> bool {
+        let mut has_changed = false;
+        for action in actions {
+            match action {
+                CueEditAction::SetPlayhead(idx) => cue_list.playhead = idx,
+                CueEditAction::MoveUp(idx) if idx > 0 => {
+                    has_changed = true;
+                    cue_list.cues.swap(idx, idx - 1);
+                }
+                CueEditAction::MoveDown(idx) if idx + 1 < cue_list.cues.len() => {
+                    has_changed = true;
+                    cue_list.cues.swap(idx, idx + 1);
+                }
+                CueEditAction::Delete(idx) => {
+                    has_changed = true;
+                    cue_list.cues.remove(idx);
+                    if cue_list.playhead > idx {
+                        cue_list.playhead -= 1;
+                    }
+                }
+                CueEditAction::Changed => has_changed = true,
+                CueEditAction::MoveUp(_) | CueEditAction::MoveDown(_) => {}
+            }
+        }
+        has_changed
+    }
+}
@@ -0,0 +1,91 @@
+use crate::comms::command::CommsCommand;
+use crate::gui::ui::AkaiVisualizer;
+use egui::{Event, Key, Modifiers};
+
+/// Parses shortcuts such as "Ctrl+Shift+1", returns None if a part is not a known modifier or key
+pub fn parse_shortcut(shortcut: &str) -> Option<(Modifiers, Key)> {
+    let mut parts: Vec<&str> = shortcut.split('+').map(str::trim).collect();
+    // "Ctrl++" binds the plus key
+    if shortcut.ends_with("++") {
+        parts.truncate(parts.len().saturating_sub(2));
+        parts.push("+");
+    }
+    let key = Key::from_name(parts.pop()?)?;
+    let mut modifiers = Modifiers::NONE;
+    for part in parts {
+        modifiers |= match part.to_lowercase().as_str() {
+            "ctrl" | "control" => Modifiers::CTRL,
+            "alt" | "option" => Modifiers::ALT,
+            "shift" => Modifiers::SHIFT,
+            "cmd" | "command" => Modifiers::COMMAND,
+            _ => return None,
+        };
+    }
+    Some((modifiers, key))
+}
+
+/// Label of a command in the keymap editor
+pub fn describe_command(command: &CommsCommand) -> String {
+    match *command {
+        CommsCommand::PadPressed { key } => format!("Pad {}", key + 1),
+        CommsCommand::WhiteKeyPressed { key, .. } | CommsCommand::BlackKeyPressed { key, .. } => {
+            format!("Key {key}")
+        }
+        CommsCommand::PausePressed => "Pause".to_string(),
+        CommsCommand::SkipTrackPressed => "Skip track".to_string(),
+        CommsCommand::StopAllPressed => "Stop all".to_string(),
+        CommsCommand::PreviousPadPagePressed => "Previous pad bank".to_string(),
+        CommsCommand::NextPadPagePressed => "Next pad bank".to_string(),
+        CommsCommand::PreviousKeyboardBankPressed => "Previous keys bank".to_string(),
+        CommsCommand::NextKeyboardBankPressed => "Next keys bank".to_string(),
+        CommsCommand::CueGoPressed => "Cue GO".to_string(),
+        _ => format!("{command:?}"),
+    }
+}
+
+impl AkaiVisualizer {
+    /// Sends the command bound to each shortcut pressed this frame, keyboard keys are also released
+    pub fn handle_hotkeys(&self, ctx: &egui::Context) {
+        if ctx.wants_keyboard_input() {
+            return;
+        }
+        let key_events: Vec<(Key, bool, Modifiers)> = ctx.input(|i| {
+            i.events
+                .iter()
+                .filter_map(|event| match event {
+                    Event::Key {
+                        key,
+                        physical_key,
+                        pressed,
+                        repeat: false,
+                        modifiers,
+                    } => Some((physical_key.unwrap_or(*key), *pressed, *modifiers)),
+                    _ => None,
+                })
+                .collect()
+        });
+        for (key, pressed, modifiers) in key_events {
+            for hotkey in &self.settings_data.keymap {
+                let Some((hotkey_modifiers, hotkey_key)) = parse_shortcut(&hotkey.shortcut) else {
+                    continue;
+                };
+                if hotkey_key != key {
+                    continue;
+                }
+                match &hotkey.command {
+                    CommsCommand::WhiteKeyPressed { key, .. }
+                    | CommsCommand::BlackKeyPressed { key, .. }
+                        if !pressed =>
+                    {
+                        // Released whatever the modifiers, they may have been let go first
+                        self.send_command_to_backend(CommsCommand::KeyReleased { key: *key });
+                    }
+                    command if pressed && modifiers.matches_exact(hotkey_modifiers) => {
+                        self.send_command_to_backend(command.clone());
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
@@ -323,7 +323,7 @@ impl AkaiVisualizer {
                 let mut actions = vec![];
 
                 ScrollArea::vertical()
-                    .max_height(400.0 * scale)
+                    .max_height(280.0 * scale)
                     .show(ui, |ui| {
                         let list_length = self.info_panel_data.information_list.len();
                         for i in 0..list_length {
@@ -0,0 +1,265 @@
+use crate::backend::keyboard_handler::{get_key_number, is_ambience_key};
+use crate::comms::command::CommsCommand;
+use crate::gui::settings_view::settings::get_default_file_dialog;
+use crate::gui::ui::AkaiVisualizer;
+use eframe::epaint::Color32;
+use egui::{Frame, RichText, ScrollArea};
+use log::warn;
+use std::path::PathBuf;
+
+/// Folder or file dragged from the library browser
+pub struct LibraryItem(PathBuf);
+
+pub enum AssignmentAction {
+    /// Pad counted across the pages, no folder to clear it
+    Pad(usize, Option<String>),
+    /// Key of the current bank, no file to give it back to its folder
+    Key(u8, Option<String>),
+}
+
+/// Widget that files dragged from the file manager will be dropped on. The drop itself has no
+/// position and some platforms stop reporting the pointer during the drag: the widget is the one
+/// under the pointer when the files start hovering, it only changes if the pointer is seen moving
+fn is_os_drop_target(ui: &egui::Ui, response: &egui::Response) -> bool {
+    let key = egui::Id::new("os_drop_target");
+    let (is_hovering, is_dropping, pos, is_moving) = ui.input(|i| {
+        (
+            !i.raw.hovered_files.is_empty(),
+            !i.raw.dropped_files.is_empty(),
+            i.pointer.latest_pos(),
+            i.pointer.delta() != egui::Vec2::ZERO,
+        )
+    });
+    ui.data_mut(|data| {
+        let target = data.get_temp::<egui::Id>(key);
+        if !is_hovering && !is_dropping {
+            data.remove::<egui::Id>(key);
+            return false;
+        }
+        if is_hovering
+            && (target.is_none() || is_moving)
+            && pos.is_some_and(|x| response.rect.contains(x))
+        {
+            data.insert_temp(key, response.id);
+            return true;
+        }
+        target == Some(response.id)
+    })
+}
+
+/// Path dropped on the target, from the library browser or from the file manager
+fn get_dropped_path(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    is_os_target: bool,
+) -> Option<PathBuf> {
+    if let Some(item) = response.dnd_release_payload::<LibraryItem>() {
+        return Some(item.0.clone());
+    }
+    if !is_os_target {
+        return None;
+    }
+    ui.input(|i| i.raw.dropped_files.first()?.path.clone())
+}
+
+/// Outlines the target while something is dragged over it
+fn draw_drop_highlight(ui: &egui::Ui, response: &egui::Response, is_os_target: bool, scale: f32) {
+    let is_hovered = response.dnd_hover_payload::<LibraryItem>().is_some()
+        || (is_os_target && ui.input(|i| !i.raw.hovered_files.is_empty()));
+    if is_hovered {
+        ui.painter().rect_stroke(
+            response.rect.expand(3.0 * scale),
+            4.0 * scale,
+            egui::Stroke::new(2.0 * scale, Color32::from_rgb(250, 200, 90)),
+            egui::StrokeKind::Outside,
+        );
+    }
+}
+
+/// Folder dropped on the pad or picked from its context menu, a file dropped gives its folder
+pub fn get_pad_action(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    album: usize,
+    scale: f32,
+) -> Option<AssignmentAction> {
+    let is_os_target = is_os_drop_target(ui, response);
+    draw_drop_highlight(ui, response, is_os_target, scale);
+    if let Some(path) = get_dropped_path(ui, response, is_os_target) {
+        let folder = if path.is_dir() {
+            path
+        } else {
+            path.parent()?.to_path_buf()
+        };
+        return Some(AssignmentAction::Pad(
+            album,
+            Some(folder.display().to_string()),
+        ));
+    }
+    let mut action = None;
+    response.context_menu(|ui| {
+        if ui.button("Assign folder...").clicked() {
+            action = get_default_file_dialog()
+                .pick_folder()
+                .map(|x| AssignmentAction::Pad(album, Some(x.display().to_string())));
+            ui.close();
+        }
+        if ui.button("Clear").clicked() {
+            action = Some(AssignmentAction::Pad(album, None));
+            ui.close();
+        }
+    });
+    action
+}
+
+/// File dropped on the key or picked from its context menu, folders are not playable by a key
+pub fn get_key_action(
+    ui: &egui::Ui,
+    response: &egui::Response,
+    key: u8,
+    scale: f32,
+) -> Option<AssignmentAction> {
+    let is_os_target = is_os_drop_target(ui, response);
+    draw_drop_highlight(ui, response, is_os_target, scale);
+    if let Some(path) = get_dropped_path(ui, response, is_os_target) {
+        if path.is_dir() {
+            warn!(
+                "Only a file can be assigned to a key, {} is a folder",
+                path.display()
+            );
+            return None;
+        }
+        return Some(AssignmentAction::Key(key, Some(path.display().to_string())));
+    }
+    let mut action = None;
+    response.context_menu(|ui| {
+        if ui.button("Assign file...").clicked() {
+            action = get_default_file_dialog()
+                .pick_file()
+                .map(|x| AssignmentAction::Key(key, Some(x.display().to_string())));
+            ui.close();
+        }
+        if ui
+            .button("Clear")
+            .on_hover_text("The key plays the file of its folder again")
+            .clicked()
+        {
+            action = Some(AssignmentAction::Key(key, None));
+            ui.close();
+        }
+    });
+    action
+}
+
+fn draw_library_items(ui: &mut egui::Ui, title: &str, items: &[PathBuf]) {
+    egui::CollapsingHeader::new(format!("{title} ({})", items.len()))
+        .id_salt(title)
+        .show(ui, |ui| {
+            for item in items {
+                let name = item
+                    .file_name()
+                    .map_or_else(String::new, |x| x.to_string_lossy().into_owned());
+                ui.dnd_drag_source(
+                    egui::Id::new(("library_item", item)),
+                    LibraryItem(item.clone()),
+                    |ui| {
+                        ui.label(RichText::new(name).color(Color32::from_rgb(220, 220, 235)));
+                    },
+                )
+                .response
+                .on_hover_text(item.display().to_string());
+            }
+        });
+}
+
+impl AkaiVisualizer {
+    /// Indexed folders and files, ready to be dragged onto the pads and the keys
+    pub(crate) fn draw_library_browser(&self, ui: &mut egui::Ui, scale: f32) {
+        Frame::new()
+            .fill(Color32::from_rgb(25, 25, 30))
+            .stroke(egui::Stroke::new(1.5, Color32::from_rgb(70, 70, 85)))
+            .corner_radius(12.0)
+            .inner_margin(16.0)
+            .show(ui, |ui| {
+                ui.set_width(280.0 * scale);
+                ui.label(
+                    RichText::new("Library")
+                        .size(18.0 * scale)
+                        .strong()
+                        .color(Color32::from_rgb(230, 230, 240)),
+                )
+                .on_hover_text("Drag a folder onto a pad or a file onto a key to assign it");
+                ui.add_space(4.0);
+                ui.separator();
+
+                let Some((index, (music, ambience, sound_effect))) =
+                    self.gui_data.lock().ok().and_then(|x| {
+                        let folders = x.data.settings_data.lock().ok().map(|s| {
+                            (
+                                s.music_folder.clone(),
+                                s.ambience_folder.clone(),
+                                s.sound_effect_folder.clone(),
+                            )
+                        })?;
+                        Some((x.data.library_index.clone(), folders))
+                    })
+                else {
+                    return;
+                };
+                let Ok(index) = index.lock() else {
+                    warn!("Failed to lock the library index, will not show it");
+                    return;
+                };
+                ScrollArea::vertical()
+                    .id_salt("library_browser")
+                    .max_height(150.0 * scale)
+                    .show(ui, |ui| {
+                        draw_library_items(ui, "Music", &index.get_subfolders(&music));
+                        draw_library_items(ui, "Ambiences", &index.get_files(&ambience));
+                        draw_library_items(ui, "Sound effects", &index.get_files(&sound_effect));
+                    });
+            });
+    }
+
+    /// Saves the new assignments to the config right away, the unsaved settings stay unsaved
+    pub(crate) fn apply_assignment_actions(&mut self, actions: Vec<AssignmentAction>) {
+        if actions.is_empty() {
+            return;
+        }
+        let bank = self
+            .gui_data
+            .lock()
+            .map(|x| x.data.keyboard_bank)
+            .unwrap_or_default();
+        let mut saved = self.saved_settings.clone();
+        for action in actions {
+            match action {
+                AssignmentAction::Pad(pad, folder) => {
+                    self.settings_data.assign_pad(pad, folder.clone());
+                    saved.assign_pad(pad, folder);
+                }
+                AssignmentAction::Key(key, file) => {
+                    let Some(number) = get_key_number(key, bank) else {
+                        continue;
+                    };
+                    let is_ambience = is_ambience_key(key);
+                    self.settings_data
+                        .assign_key(is_ambience, number, file.clone());
+                    saved.assign_key(is_ambience, number, file);
+                }
+            }
+        }
+        if let Err(e) = saved.write_to_config(&self.config_path) {
+            warn!("Failed to save the assignments: {e}");
+            return;
+        }
+        self.config_issues = saved.validate();
+        if let Ok(g_d) = self.gui_data.lock()
+            && let Ok(mut s) = g_d.data.settings_data.lock()
+        {
+            s.copy_data(&saved);
+        }
+        self.saved_settings = saved;
+        self.send_command_to_watchdog(CommsCommand::Refresh {});
+    }
+}
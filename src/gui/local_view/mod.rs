@@ -1,4 +1,8 @@
 mod audio_player;
 pub mod audio_player_states;
+mod cue_list_editor;
+pub mod hotkeys;
 mod information_listing;
+mod library_browser;
+mod scene_listing;
 pub mod teatro_view;
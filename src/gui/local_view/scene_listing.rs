@@ -0,0 +1,212 @@
+use crate::comms::command::CommsCommand;
+use crate::gui::ui::AkaiVisualizer;
+use crate::states::scene::Scene;
+use crate::states::visualizer::PADS_PER_PAGE;
+use eframe::epaint::Color32;
+use egui::{Frame, RichText, ScrollArea};
+use log::warn;
+
+enum SceneAction {
+    Recall(usize),
+    StartEditing(usize),
+    StopEditing,
+    BindPad(usize, Option<u8>),
+    Delete(usize),
+    Changed,
+}
+
+fn draw_scene_name(
+    ui: &mut egui::Ui,
+    index: usize,
+    scene: &mut Scene,
+    is_editing: bool,
+    scale: f32,
+) -> Option<SceneAction> {
+    if is_editing {
+        let edit_response = ui.add(
+            egui::TextEdit::singleline(&mut scene.name)
+                .desired_width(100.0 * scale)
+                .text_color(Color32::from_rgb(240, 240, 250)),
+        );
+        edit_response.request_focus();
+        (edit_response.lost_focus()
+            && ui.input(|i| i.key_pressed(egui::Key::Enter) || i.key_pressed(egui::Key::Escape)))
+        .then_some(SceneAction::StopEditing)
+    } else {
+        let label_response = ui.add(
+            egui::Label::new(
+                RichText::new(&scene.name)
+                    .color(Color32::from_rgb(220, 220, 235))
+                    .size(14.0 * scale),
+            )
+            .truncate()
+            .sense(egui::Sense::click()),
+        );
+        let double_clicked = label_response.double_clicked();
+        label_response.on_hover_text("Double-click to rename");
+        double_clicked.then_some(SceneAction::StartEditing(index))
+    }
+}
+
+fn draw_scene_item(
+    ui: &mut egui::Ui,
+    index: usize,
+    scene: &mut Scene,
+    is_editing: bool,
+    scale: f32,
+) -> Vec<SceneAction> {
+    let mut actions = vec![];
+
+    Frame::new()
+        .fill(Color32::from_rgb(40, 40, 50))
+        .stroke(egui::Stroke::new(1.0, Color32::from_rgb(60, 60, 75)))
+        .corner_radius(8.0)
+        .inner_margin(egui::vec2(8.0, 6.0))
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("▶").on_hover_text("Recall scene").clicked() {
+                    actions.push(SceneAction::Recall(index));
+                }
+                actions.extend(draw_scene_name(ui, index, scene, is_editing, scale));
+
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("✖").on_hover_text("Delete scene").clicked() {
+                        actions.push(SceneAction::Delete(index));
+                    }
+
+                    let morph_response = ui
+                        .add(
+                            egui::DragValue::new(&mut scene.morph_seconds)
+                                .range(0.0..=60.0)
+                                .speed(0.1)
+                                .suffix(" s"),
+                        )
+                        .on_hover_text("Morph time");
+                    if morph_response.drag_stopped()
+                        || (morph_response.changed() && !morph_response.dragged())
+                    {
+                        actions.push(SceneAction::Changed);
+                    }
+
+                    let mut pad = scene.pad;
+                    egui::ComboBox::from_id_salt(("scene_pad", index))
+                        .width(60.0 * scale)
+                        .selected_text(
+                            pad.map_or_else(|| "No pad".to_string(), |x| format!("Pad {x}")),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut pad, None, "No pad");
+                            for p in 0..PADS_PER_PAGE as u8 {
+                                ui.selectable_value(&mut pad, Some(p), format!("Pad {p}"));
+                            }
+                        });
+                    if pad != scene.pad {
+                        actions.push(SceneAction::BindPad(index, pad));
+                    }
+                });
+            });
+        });
+
+    actions
+}
+
+impl AkaiVisualizer {
+    pub(crate) fn draw_scene_list(&mut self, ui: &mut egui::Ui, scale: f32) {
+        Frame::new()
+            .fill(Color32::from_rgb(25, 25, 30))
+            .stroke(egui::Stroke::new(1.5, Color32::from_rgb(70, 70, 85)))
+            .corner_radius(12.0)
+            .inner_margin(16.0)
+            .show(ui, |ui| {
+                ui.set_width(280.0 * scale);
+                ui.label(
+                    RichText::new("Scenes")
+                        .size(18.0 * scale)
+                        .strong()
+                        .color(Color32::from_rgb(230, 230, 240)),
+                );
+                ui.add_space(4.0);
+                ui.separator();
+                ui.add_space(8.0);
+
+                let library = self.scene_panel_data.library.clone();
+                let Ok(mut library) = library.lock() else {
+                    warn!("Failed to lock the scenes, will not show them");
+                    return;
+                };
+                let mut actions = vec![];
+                ScrollArea::vertical()
+                    .id_salt("scene_list")
+                    .max_height(150.0 * scale)
+                    .show(ui, |ui| {
+                        for (i, scene) in library.scenes.iter_mut().enumerate() {
+                            let is_editing = self.scene_panel_data.editing_index == Some(i);
+                            actions.extend(draw_scene_item(ui, i, scene, is_editing, scale));
+                            ui.add_space(6.0);
+                        }
+                    });
+
+                ui.add_space(8.0);
+                let save_button = ui.add(
+                    egui::Button::new(
+                        RichText::new("+ Save Current Mix")
+                            .size(14.0 * scale)
+                            .color(Color32::from_rgb(200, 220, 255)),
+                    )
+                    .fill(Color32::from_rgb(50, 80, 120))
+                    .stroke(egui::Stroke::new(1.0, Color32::from_rgb(100, 140, 220)))
+                    .corner_radius(6.0)
+                    .min_size(egui::vec2(ui.available_width(), 28.0 * scale)),
+                );
+                if save_button.clicked() {
+                    self.send_command_to_backend(CommsCommand::SceneCaptured { pad: None });
+                }
+
+                if self.apply_scene_actions(&mut library.scenes, actions)
+                    && let Err(e) = library.write_to_file()
+                {
+                    warn!("Failed to write scenes to file: {e}");
+                }
+            });
+    }
+
+    /// Returns true if the scenes have to be saved
+    fn apply_scene_actions(&mut self, scenes: &mut Vec<Scene>, actions: Vec<SceneAction>) -> bool {
+        let mut has_changed = false;
+        for action in actions {
+            match action {
+                SceneAction::Recall(idx) => {
+                    self.send_command_to_backend(CommsCommand::SceneRecalled { index: idx });
+                }
+                SceneAction::StartEditing(idx) => {
+                    self.scene_panel_data.editing_index = Some(idx);
+                }
+                SceneAction::StopEditing => {
+                    has_changed = true;
+                    self.scene_panel_data.editing_index = None;
+                }
+                SceneAction::BindPad(idx, pad) => {
+                    has_changed = true;
+                    // A pad recalls a single scene
+                    for scene in scenes.iter_mut().filter(|x| pad.is_some() && x.pad == pad) {
+                        scene.pad = None;
+                    }
+                    scenes[idx].pad = pad;
+                }
+                SceneAction::Delete(idx) => {
+                    has_changed = true;
+                    scenes.remove(idx);
+                    if let Some(editing) = self.scene_panel_data.editing_index {
+                        if editing == idx {
+                            self.scene_panel_data.editing_index = None;
+                        } else if editing > idx {
+                            self.scene_panel_data.editing_index = Some(editing - 1);
+                        }
+                    }
+                }
+                SceneAction::Changed => has_changed = true,
+            }
+        }
+        has_changed
+    }
+}
@@ -1,9 +1,59 @@
-use crate::gui::comms::command::CommsCommand;
+use crate::backend::keyboard_handler::KEY_PATTERN;
+use crate::comms::command::CommsCommand;
+use crate::gui::local_view::library_browser::{AssignmentAction, get_key_action, get_pad_action};
 use crate::gui::ui::AkaiVisualizer;
 use crate::states::knob_value_update::KnobValueUpdate;
+use crate::states::visualizer::PADS_PER_PAGE;
 use eframe::emath::{Pos2, Rect, Vec2};
-use eframe::epaint::{Color32, FontFamily, FontId};
+use eframe::epaint::{Color32, FontFamily, FontId, TextShape};
 use egui::RichText;
+use std::path::Path;
+
+/// Stand-in for the key velocity: the closer the click to the bottom of the key, the harder the press
+#[allow(clippy::cast_sign_loss)] // depth is clamped, the velocity is always between 1 and 127
+fn get_click_velocity(key_response: &egui::Response) -> u8 {
+    key_response.interact_pointer_pos().map_or(127, |pos| {
+        let depth = ((pos.y - key_response.rect.min.y) / key_response.rect.height()).clamp(0., 1.);
+        126.0f32.mul_add(depth, 1.).round() as u8
+    })
+}
+
+/// Some(true) the frame the key gets pressed, Some(false) the frame it gets released.
+/// The secondary button opens the context menu instead
+fn get_key_transition(ui: &egui::Ui, key_response: &egui::Response) -> Option<bool> {
+    let is_down =
+        key_response.is_pointer_button_down_on() && ui.input(|i| i.pointer.primary_down());
+    let was_down = ui.data_mut(|d| {
+        let was_down = d.get_temp(key_response.id).unwrap_or(false);
+        d.insert_temp(key_response.id, is_down);
+        was_down
+    });
+    (is_down != was_down).then_some(is_down)
+}
+
+/// Draws the label bottom-up along the key, cut to `max_chars` characters
+fn draw_key_label(
+    ui: &egui::Ui,
+    key_rect: Rect,
+    label: &str,
+    max_chars: usize,
+    color: Color32,
+    scale: f32,
+) {
+    if label.is_empty() {
+        return;
+    }
+    let text: String = label.chars().take(max_chars).collect();
+    let galley = ui
+        .painter()
+        .layout_no_wrap(text, FontId::proportional(10.0 * scale), color);
+    let pos = Pos2::new(
+        key_rect.center().x - galley.size().y / 2.0,
+        6.0f32.mul_add(-scale, key_rect.max.y),
+    );
+    ui.painter()
+        .add(TextShape::new(pos, galley, color).with_angle(-std::f32::consts::FRAC_PI_2));
+}
 
 impl AkaiVisualizer {
     pub(crate) fn render_visualizer_tab(&mut self, ui: &mut egui::Ui) {
@@ -23,6 +73,7 @@ impl AkaiVisualizer {
                     family: FontFamily::Name("Pixelify".into()),
                 }),
         );
+        self.draw_resume_offer(ui);
         ui.add_space(10.0);
 
         let available_size = ui.available_size();
@@ -32,6 +83,7 @@ impl AkaiVisualizer {
             .min(available_size.y / base_height)
             .min(2.0);
 
+        let mut actions = vec![];
         ui.horizontal(|ui| {
             let controller_size = Vec2::new(900.0 * scale, base_height * scale);
             let (response, painter) = ui.allocate_painter(controller_size, egui::Sense::hover());
@@ -39,17 +91,22 @@ impl AkaiVisualizer {
             let rect = response.rect;
             painter.rect_filled(rect, 5.0 * scale, Color32::from_rgb(30, 30, 35));
 
-            self.draw_pads(ui, rect, scale);
+            actions.extend(self.draw_pads(ui, rect, scale));
             self.draw_knobs(ui, rect, scale);
-            self.draw_keyboard(ui, rect, scale);
+            actions.extend(self.draw_keyboard(ui, rect, scale));
             self.draw_audio_player(ui, rect, scale);
 
             ui.add_space(82.0);
 
             ui.vertical(|ui| {
                 self.draw_information_list(ui, scale);
+                ui.add_space(10.0);
+                self.draw_scene_list(ui, scale);
+                ui.add_space(10.0);
+                self.draw_library_browser(ui, scale);
             })
         });
+        self.apply_assignment_actions(actions);
 
         ui.add_space(5.0);
         ui.label(
@@ -71,6 +128,36 @@ impl AkaiVisualizer {
             }),
         );
     }
+    /// Offers to play the music of the previous session again, until it is answered
+    fn draw_resume_offer(&self, ui: &mut egui::Ui) {
+        let Some((offer, album)) = self.gui_data.lock().ok().and_then(|x| {
+            let offer = x.data.resume_offer.clone()?;
+            let album = x.data.pad_labels.get(offer.album).cloned();
+            Some((offer, album.unwrap_or_default()))
+        }) else {
+            return;
+        };
+        let track = Path::new(&offer.track)
+            .file_stem()
+            .map_or_else(|| offer.track.clone(), |x| x.to_string_lossy().into_owned());
+        ui.horizontal(|ui| {
+            ui.label(
+                RichText::new(format!(
+                    "Resume {album} - {track} at {}:{:02}?",
+                    offer.elapsed_seconds / 60,
+                    offer.elapsed_seconds % 60
+                ))
+                .color(Color32::from_rgb(200, 220, 255)),
+            );
+            if ui.button("Resume").clicked() {
+                self.send_command_to_backend(CommsCommand::SessionResumed);
+            }
+            if ui.button("Dismiss").clicked() {
+                self.send_command_to_backend(CommsCommand::SessionResumeDismissed);
+            }
+        });
+    }
+
     pub(crate) fn update_local_progress(&self, delta_time_ms: u64) {
         if let Ok(mut gui_data) = self.gui_data.lock()
             && gui_data.player_info.status.is_music_playable()
@@ -93,33 +180,79 @@ impl AkaiVisualizer {
         }
     }
 
-    fn draw_pads(&self, ui: &mut egui::Ui, rect: Rect, scale: f32) {
+    fn draw_pads(&self, ui: &mut egui::Ui, rect: Rect, scale: f32) -> Vec<AssignmentAction> {
         let pad_size = 50.0 * scale;
         let pad_spacing = 10.0 * scale;
         let start_x = 20.0f32.mul_add(scale, rect.min.x);
         let start_y = 10.0f32.mul_add(scale, rect.min.y);
+        let (pad_page, page_count) = self.gui_data.lock().map_or((0, 1), |gui_data| {
+            (gui_data.data.pad_page, gui_data.data.get_pad_page_count())
+        });
+
+        self.draw_bank_selector(
+            ui,
+            Pos2::new(
+                540.0f32.mul_add(scale, rect.min.x),
+                20.0f32.mul_add(scale, rect.min.y),
+            ),
+            scale,
+            "BANK",
+            (pad_page, page_count),
+            (
+                ("▲", CommsCommand::PreviousPadPagePressed),
+                ("▼", CommsCommand::NextPadPagePressed),
+            ),
+        );
 
+        let mut actions = vec![];
         for row in 0..5 {
             for col in 0..8 {
                 let idx = (4 - row) * 8 + col;
+                let album = pad_page * PADS_PER_PAGE + idx as usize;
                 let x = f32::from(col).mul_add(pad_size + pad_spacing, start_x);
                 let y = f32::from(row).mul_add(pad_size + pad_spacing, start_y);
 
                 let pad_rect = Rect::from_min_size(Pos2::new(x, y), Vec2::new(pad_size, pad_size));
 
-                let pad_response = ui.allocate_rect(pad_rect, egui::Sense::click());
+                let (label, is_missing, is_pressed) =
+                    self.gui_data
+                        .lock()
+                        .map_or((None, false, false), |gui_data| {
+                            (
+                                gui_data.data.pad_labels.get(album).cloned(),
+                                gui_data.data.missing_pads.contains(&album),
+                                gui_data.data.last_pad_pressed == Some(album),
+                            )
+                        });
+                let is_unassigned = label.as_ref().is_some_and(String::is_empty);
+
+                let mut pad_response = ui.allocate_rect(pad_rect, egui::Sense::click());
+                if is_missing {
+                    pad_response = pad_response
+                        .on_hover_text("The folder assigned to this pad does not exist");
+                } else if is_unassigned {
+                    pad_response = pad_response.on_hover_text("No folder is assigned to this pad");
+                }
                 if pad_response.clicked() {
                     self.send_command_to_backend(CommsCommand::PadPressed { key: idx });
                 }
 
                 // Enhanced pad colors with glow effect
-                let (base_color, glow_color) = if let Ok(gui_data) = self.gui_data.lock()
-                    && gui_data.data.last_pad_pressed.is_some_and(|x| x == idx)
-                {
+                let (base_color, glow_color) = if is_pressed {
                     (
                         Color32::from_rgb(140, 60, 180),
                         Color32::from_rgba_premultiplied(180, 100, 220, 80),
                     )
+                } else if is_missing {
+                    (
+                        Color32::from_rgb(180, 60, 60),
+                        Color32::from_rgba_premultiplied(220, 80, 80, 60),
+                    )
+                } else if label.as_ref().is_none_or(String::is_empty) {
+                    (
+                        Color32::from_rgb(70, 70, 78),
+                        Color32::from_rgba_premultiplied(90, 90, 100, 40),
+                    )
                 } else {
                     (
                         Color32::from_rgb(80, 180, 100),
@@ -141,25 +274,62 @@ impl AkaiVisualizer {
                 ui.painter().text(
                     Pos2::new(6.0f32.mul_add(scale, x), 6.0f32.mul_add(scale, y)),
                     egui::Align2::LEFT_TOP,
-                    format!("{idx}"),
+                    format!("{album}"),
                     FontId::proportional(11.0 * scale),
                     Color32::from_rgba_premultiplied(255, 255, 255, 180),
                 );
 
-                if let Ok(gui_data) = self.gui_data.lock()
-                    && let Some(label) = gui_data.data.pad_labels.get(idx as usize)
-                    && !label.is_empty()
-                {
-                    ui.painter().text(
-                        pad_rect.center(),
-                        egui::Align2::CENTER_CENTER,
-                        label,
-                        FontId::proportional(12.0 * scale),
-                        Color32::WHITE,
-                    );
-                }
+                draw_pad_label(ui, pad_rect, scale, label.as_deref(), is_missing);
+                actions.extend(get_pad_action(ui, &pad_response, album, scale));
             }
         }
+        actions
+    }
+
+    fn draw_bank_selector(
+        &self,
+        ui: &mut egui::Ui,
+        pos: Pos2,
+        scale: f32,
+        title: &str,
+        (bank, bank_count): (usize, usize),
+        (previous, next): ((&str, CommsCommand), (&str, CommsCommand)),
+    ) {
+        let button_size = Vec2::new(28.0 * scale, 22.0 * scale);
+
+        ui.painter().text(
+            pos,
+            egui::Align2::LEFT_TOP,
+            format!("{title} {}/{bank_count}", bank + 1),
+            FontId {
+                size: 16.0 * scale,
+                family: FontFamily::Name("Pixelify".into()),
+            },
+            Color32::from_rgb(200, 200, 210),
+        );
+
+        let previous_rect =
+            Rect::from_min_size(Pos2::new(pos.x, 26.0f32.mul_add(scale, pos.y)), button_size);
+        if ui
+            .put(previous_rect, egui::Button::new(previous.0).small())
+            .clicked()
+        {
+            self.send_command_to_backend(previous.1);
+        }
+
+        let next_rect = Rect::from_min_size(
+            Pos2::new(
+                6.0f32.mul_add(scale, pos.x + button_size.x),
+                26.0f32.mul_add(scale, pos.y),
+            ),
+            button_size,
+        );
+        if ui
+            .put(next_rect, egui::Button::new(next.0).small())
+            .clicked()
+        {
+            self.send_command_to_backend(next.1);
+        }
     }
 
     fn draw_knobs(&self, ui: &mut egui::Ui, rect: Rect, scale: f32) {
@@ -261,21 +431,52 @@ impl AkaiVisualizer {
         }
     }
 
-    fn draw_keyboard(&self, ui: &mut egui::Ui, rect: Rect, scale: f32) {
+    fn draw_keyboard(&self, ui: &mut egui::Ui, rect: Rect, scale: f32) -> Vec<AssignmentAction> {
+        let start = Pos2::new(
+            20.0f32.mul_add(scale, rect.min.x),
+            450.0f32.mul_add(scale, rect.min.y),
+        );
+        let (key_labels, keyboard_bank, bank_count) =
+            self.gui_data.lock().map_or((vec![], 0, 1), |gui_data| {
+                (
+                    gui_data.data.key_labels.clone(),
+                    gui_data.data.keyboard_bank,
+                    gui_data.data.keyboard_bank_count,
+                )
+            });
+
+        self.draw_bank_selector(
+            ui,
+            Pos2::new(565.0f32.mul_add(scale, rect.min.x), start.y),
+            scale,
+            "KEYS BANK",
+            (keyboard_bank, bank_count),
+            (
+                ("◀", CommsCommand::PreviousKeyboardBankPressed),
+                ("▶", CommsCommand::NextKeyboardBankPressed),
+            ),
+        );
+
+        let mut actions = self.draw_white_keys(ui, start, scale, &key_labels);
+        actions.extend(self.draw_black_keys(ui, start, scale, &key_labels));
+        actions
+    }
+
+    fn draw_white_keys(
+        &self,
+        ui: &mut egui::Ui,
+        start: Pos2,
+        scale: f32,
+        key_labels: &[String],
+    ) -> Vec<AssignmentAction> {
+        let mut actions = vec![];
         let white_key_width = 35.0 * scale;
         let white_key_height = 100.0 * scale;
-        let black_key_width = 22.0 * scale;
-        let black_key_height = 65.0 * scale;
-        let start_x = 20.0f32.mul_add(scale, rect.min.x);
-        let start_y = 450.0f32.mul_add(scale, rect.min.y);
-
-        let pattern = [
-            true, false, true, false, true, true, false, true, false, true, false, true,
-        ];
+        let (start_x, start_y) = (start.x, start.y);
 
         let mut white_idx = 0;
         for i in 0..25u8 {
-            let is_white = pattern[i as usize % 12];
+            let is_white = KEY_PATTERN[i as usize % 12];
             if is_white {
                 let x = (white_idx as f32).mul_add(white_key_width, start_x);
                 let key_rect = Rect::from_min_size(
@@ -283,10 +484,17 @@ impl AkaiVisualizer {
                     Vec2::new(white_key_width, white_key_height),
                 );
 
-                let key_response = ui.allocate_rect(key_rect, egui::Sense::click());
-
-                if key_response.clicked() {
-                    self.send_command_to_backend(CommsCommand::WhiteKeyPressed { key: i + 1 });
+                let key_response = ui.allocate_rect(key_rect, egui::Sense::click_and_drag());
+
+                match get_key_transition(ui, &key_response) {
+                    Some(true) => self.send_command_to_backend(CommsCommand::WhiteKeyPressed {
+                        key: i + 1,
+                        velocity: get_click_velocity(&key_response),
+                    }),
+                    Some(false) => {
+                        self.send_command_to_backend(CommsCommand::KeyReleased { key: i + 1 });
+                    }
+                    None => (),
                 }
 
                 ui.painter()
@@ -308,13 +516,37 @@ impl AkaiVisualizer {
                     egui::Stroke::new(2.0 * scale, Color32::from_rgb(40, 40, 45)),
                     egui::StrokeKind::Outside,
                 );
+                draw_key_label(
+                    ui,
+                    key_rect,
+                    key_labels.get(i as usize).map_or("", String::as_str),
+                    14,
+                    Color32::from_rgb(60, 60, 70),
+                    scale,
+                );
+                actions.extend(get_key_action(ui, &key_response, i + 1, scale));
                 white_idx += 1;
             }
         }
+        actions
+    }
+
+    fn draw_black_keys(
+        &self,
+        ui: &mut egui::Ui,
+        start: Pos2,
+        scale: f32,
+        key_labels: &[String],
+    ) -> Vec<AssignmentAction> {
+        let mut actions = vec![];
+        let white_key_width = 35.0 * scale;
+        let black_key_width = 22.0 * scale;
+        let black_key_height = 65.0 * scale;
+        let (start_x, start_y) = (start.x, start.y);
 
-        white_idx = 0;
+        let mut white_idx = 0;
         for i in 0..25_u8 {
-            let is_white = pattern[i as usize % 12];
+            let is_white = KEY_PATTERN[i as usize % 12];
             if is_white {
                 white_idx += 1;
             } else {
@@ -325,10 +557,17 @@ impl AkaiVisualizer {
                     Vec2::new(black_key_width, black_key_height),
                 );
 
-                let key_response = ui.allocate_rect(key_rect, egui::Sense::click());
-
-                if key_response.clicked() {
-                    self.send_command_to_backend(CommsCommand::BlackKeyPressed { key: i + 1 });
+                let key_response = ui.allocate_rect(key_rect, egui::Sense::click_and_drag());
+
+                match get_key_transition(ui, &key_response) {
+                    Some(true) => self.send_command_to_backend(CommsCommand::BlackKeyPressed {
+                        key: i + 1,
+                        velocity: get_click_velocity(&key_response),
+                    }),
+                    Some(false) => {
+                        self.send_command_to_backend(CommsCommand::KeyReleased { key: i + 1 });
+                    }
+                    None => (),
                 }
 
                 ui.painter()
@@ -350,7 +589,57 @@ impl AkaiVisualizer {
                     egui::Stroke::new(1.5 * scale, Color32::BLACK),
                     egui::StrokeKind::Outside,
                 );
+                draw_key_label(
+                    ui,
+                    key_rect,
+                    key_labels.get(i as usize).map_or("", String::as_str),
+                    9,
+                    Color32::from_rgb(200, 200, 210),
+                    scale,
+                );
+                actions.extend(get_key_action(ui, &key_response, i + 1, scale));
             }
         }
+        actions
+    }
+}
+
+/// Folder name of the pad, or what is wrong with its folder
+fn draw_pad_label(
+    ui: &egui::Ui,
+    pad_rect: Rect,
+    scale: f32,
+    label: Option<&str>,
+    is_missing: bool,
+) {
+    match label {
+        Some("") => {
+            ui.painter().text(
+                pad_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                "unassigned",
+                FontId::proportional(9.0 * scale),
+                Color32::from_rgb(250, 200, 90),
+            );
+        }
+        Some(label) => {
+            ui.painter().text(
+                pad_rect.center(),
+                egui::Align2::CENTER_CENTER,
+                label,
+                FontId::proportional(12.0 * scale),
+                Color32::WHITE,
+            );
+        }
+        None => {}
+    }
+    if is_missing {
+        ui.painter().text(
+            Pos2::new(pad_rect.center().x, 4.0f32.mul_add(-scale, pad_rect.max.y)),
+            egui::Align2::CENTER_BOTTOM,
+            "missing",
+            FontId::proportional(9.0 * scale),
+            Color32::WHITE,
+        );
     }
 }
@@ -0,0 +1,44 @@
+use crate::gui::ui::AkaiVisualizer;
+use eframe::epaint::Color32;
+use egui::{Frame, RichText};
+
+impl AkaiVisualizer {
+    /// Problems found in the settings, kept on top of every tab until dismissed or fixed
+    pub(crate) fn draw_config_issues(&mut self, ui: &mut egui::Ui) {
+        if self.config_issues.is_empty() {
+            return;
+        }
+        Frame::new()
+            .fill(Color32::from_rgb(70, 45, 30))
+            .stroke(egui::Stroke::new(1.0, Color32::from_rgb(200, 140, 60)))
+            .corner_radius(8.0)
+            .inner_margin(egui::vec2(8.0, 6.0))
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label(RichText::new("Settings need attention").strong());
+                    ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                        if ui.button("Dismiss").clicked() {
+                            self.config_issues.clear();
+                        }
+                        // A config of a newer version is only replaced once the user agrees
+                        if self.saved_settings.is_read_only()
+                            && ui
+                                .button("Allow saving")
+                                .on_hover_text(
+                                    "Saving replaces the config with one of this version, \
+                                     the settings it does not know are lost",
+                                )
+                                .clicked()
+                        {
+                            self.saved_settings.allow_overwrite();
+                            self.settings_data.allow_overwrite();
+                        }
+                    });
+                });
+                for issue in &self.config_issues {
+                    ui.label(format!("• {issue}"));
+                }
+            });
+        ui.add_space(4.0);
+    }
+}
@@ -1 +1,3 @@
+pub mod config_issues;
+pub mod profiles;
 pub mod settings;
@@ -0,0 +1,86 @@
+use crate::comms::command::CommsCommand;
+use crate::gui::ui::{AkaiVisualizer, load_information_list};
+use crate::states::app_paths::AppPaths;
+use crate::states::settings_data::SettingsData;
+use log::warn;
+
+impl AkaiVisualizer {
+    pub(crate) fn draw_profile_switcher(&mut self, ui: &mut egui::Ui) {
+        let new_profile = self.new_profile.trim().to_string();
+        if ui
+            .add_enabled(
+                !new_profile.is_empty() && !new_profile.contains(['/', '\\']),
+                egui::Button::new("Create"),
+            )
+            .clicked()
+        {
+            if let Err(e) = AppPaths::create_profile(&new_profile) {
+                warn!("Failed to create the profile {new_profile}: {e}");
+            } else {
+                self.select_profile(&new_profile);
+                self.new_profile.clear();
+            }
+        }
+        ui.add(
+            egui::TextEdit::singleline(&mut self.new_profile)
+                .hint_text("New profile")
+                .desired_width(120.0),
+        );
+
+        let mut selected = None;
+        egui::ComboBox::from_id_salt("profile")
+            .selected_text(self.profile.as_deref().unwrap_or("No profile"))
+            .show_ui(ui, |ui| {
+                for profile in AppPaths::get_profiles() {
+                    if ui
+                        .selectable_label(self.profile.as_ref() == Some(&profile), &profile)
+                        .clicked()
+                    {
+                        selected = Some(profile);
+                    }
+                }
+            });
+        ui.label("Profile");
+        if let Some(profile) = selected
+            && self.profile.as_ref() != Some(&profile)
+        {
+            self.select_profile(&profile);
+        }
+    }
+
+    /// The backend loads the profile, the window follows once it is done
+    fn select_profile(&self, profile: &str) {
+        self.send_command_to_backend(CommsCommand::ProfileSelected {
+            name: profile.to_string(),
+        });
+    }
+
+    /// Reloads the files shown by the window when the backend switched profile
+    pub(crate) fn follow_profile(&mut self) {
+        let profile = self
+            .gui_data
+            .lock()
+            .map_or_else(|_| self.profile.clone(), |x| x.data.profile.clone());
+        if profile == self.profile {
+            return;
+        }
+        let paths = AppPaths::builder().maybe_profile(profile.clone()).build();
+        match SettingsData::load_from_config(&paths.config) {
+            Ok((settings, issues)) => {
+                self.saved_settings = settings.clone();
+                self.settings_data = settings;
+                self.config_issues = issues;
+            }
+            Err(e) => {
+                self.config_issues = vec![format!("Could not load the settings: {e:#}")];
+            }
+        }
+        self.config_path = paths.config;
+        self.info_panel_data.information_list = load_information_list(&paths.data);
+        self.info_panel_data.data_file_path = paths.data;
+        self.info_panel_data.editing_index = None;
+        self.scene_panel_data.editing_index = None;
+        self.cue_panel_data.folder = paths.cue_lists;
+        self.profile = profile;
+    }
+}
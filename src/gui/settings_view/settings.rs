@@ -1,9 +1,13 @@
-use crate::gui::comms::command::CommsCommand;
+use crate::comms::command::CommsCommand;
+use crate::gui::local_view::hotkeys::{describe_command, parse_shortcut};
 use crate::gui::ui::AkaiVisualizer;
+use crate::states::hotkey::Hotkey;
+use crate::states::knob_mode::KnobMode;
+use crate::states::pad_assignment::{MAX_PAD_COUNT, PadAssignment};
 use crate::states::settings_data::SettingsData;
-use log::{debug, warn};
+use crate::states::velocity_curve::VelocityCurve;
+use log::{debug, info, warn};
 use rfd::FileDialog;
-use std::env;
 use std::path::PathBuf;
 
 fn music_dir() -> anyhow::Result<PathBuf> {
@@ -24,7 +28,7 @@ fn music_dir() -> anyhow::Result<PathBuf> {
     )
 }
 
-fn get_default_file_dialog() -> FileDialog {
+pub fn get_default_file_dialog() -> FileDialog {
     music_dir().map_or_else(
         |_| FileDialog::new(),
         |path| FileDialog::new().set_directory(path),
@@ -32,15 +36,208 @@ fn get_default_file_dialog() -> FileDialog {
 }
 
 impl AkaiVisualizer {
-    fn save_settings(&mut self, settings_data: SettingsData) {
-        let config_path = env::var("CONFIG_PATH").unwrap_or_else(|_| "config.yml".to_string());
-        if let Err(e) = settings_data.write_to_config(&config_path) {
+    fn save_settings(&mut self, mut settings_data: SettingsData) {
+        settings_data.pad_assignments.sort_by_key(|x| x.pad);
+        if let Err(e) = settings_data.write_to_config(&self.config_path) {
             warn!("Failed to save settings {e:?}");
         } else {
+            self.config_issues = settings_data.validate();
+            self.saved_settings = settings_data.clone();
             self.settings_data = settings_data;
         }
     }
 
+    /// Takes the settings reloaded from disk, keeping the values edited and not saved yet
+    pub(crate) fn follow_config(&mut self) {
+        let Some((revision, reloaded)) = self.gui_data.lock().ok().and_then(|x| {
+            let settings = x.data.settings_data.lock().ok()?.clone();
+            Some((x.data.config_revision, settings))
+        }) else {
+            return;
+        };
+        if revision == self.config_revision {
+            return;
+        }
+        self.config_revision = revision;
+        match SettingsData::merge_edits(&self.saved_settings, &self.settings_data, &reloaded) {
+            Ok(merged) => self.settings_data = merged,
+            Err(e) => warn!("Failed to keep the unsaved settings: {e}"),
+        }
+        self.saved_settings = reloaded;
+        self.config_issues = match SettingsData::load_from_config(&self.config_path) {
+            Ok((_, issues)) => issues,
+            Err(e) => vec![format!("Keeping the current settings, {e:#}")],
+        };
+    }
+
+    fn render_midi_settings(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Knob mode:");
+            egui::ComboBox::from_id_salt("knob_mode")
+                .selected_text(self.settings_data.knob_mode.to_string())
+                .show_ui(ui, |ui| {
+                    for mode in KnobMode::ALL {
+                        ui.selectable_value(
+                            &mut self.settings_data.knob_mode,
+                            mode,
+                            mode.to_string(),
+                        );
+                    }
+                });
+            ui.add_enabled(
+                self.settings_data.knob_mode == KnobMode::Relative,
+                egui::Checkbox::new(
+                    &mut self.settings_data.knob_acceleration,
+                    "Accelerate fast spins",
+                ),
+            );
+        });
+
+        ui.add_space(5.0);
+
+        ui.checkbox(
+            &mut self.settings_data.virtual_midi_ports,
+            "Expose virtual MIDI ports for other software (applied on restart)",
+        );
+
+        ui.add_space(5.0);
+
+        ui.horizontal(|ui| {
+            ui.label("Cue output port:");
+            ui.text_edit_singleline(&mut self.settings_data.cue_output_port)
+                .on_hover_text(
+                    "Name of the MIDI port receiving the cues, empty to disable them (applied on restart)",
+                );
+            ui.label(format!(
+                "{} cue mappings",
+                self.settings_data.cue_mappings.len()
+            ));
+        });
+    }
+
+    #[cfg(feature = "remote")]
+    fn render_remote_settings(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("Remote address:");
+            ui.text_edit_singleline(&mut self.settings_data.remote_address)
+                .on_hover_text("Address of the remote control page, use 0.0.0.0 to reach it from other devices (applied on restart)");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Remote token:");
+            ui.add(egui::TextEdit::singleline(&mut self.settings_data.remote_token).password(true))
+                .on_hover_text("Required by remotes when not empty");
+        });
+    }
+
+    #[cfg(feature = "osc")]
+    fn render_osc_settings(&mut self, ui: &mut egui::Ui) {
+        ui.add_space(5.0);
+        ui.horizontal(|ui| {
+            ui.label("OSC address:");
+            ui.text_edit_singleline(&mut self.settings_data.osc_address)
+                .on_hover_text("UDP address receiving OSC messages, use 0.0.0.0 to reach it from other devices (applied on restart)");
+        });
+    }
+
+    fn render_keymap_editor(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new("Keyboard shortcuts").show(ui, |ui| {
+            if ui.button("Reset to defaults").clicked() {
+                self.settings_data.keymap = Hotkey::get_default_keymap();
+            }
+            egui::ScrollArea::vertical()
+                .max_height(250.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("keymap").striped(true).show(ui, |ui| {
+                        for hotkey in &mut self.settings_data.keymap {
+                            ui.label(describe_command(&hotkey.command));
+                            let is_valid = parse_shortcut(&hotkey.shortcut).is_some();
+                            ui.add(
+                                egui::TextEdit::singleline(&mut hotkey.shortcut).text_color_opt(
+                                    (!is_valid).then_some(egui::Color32::LIGHT_RED),
+                                ),
+                            )
+                            .on_hover_text("Modifiers and key joined by '+', e.g. Ctrl+Shift+1");
+                            ui.end_row();
+                        }
+                    });
+                });
+        });
+    }
+
+    fn render_pad_assignments(&mut self, ui: &mut egui::Ui) {
+        egui::CollapsingHeader::new(format!(
+            "Pads ({} assigned)",
+            self.settings_data.pad_assignments.len()
+        ))
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .button("Fill from folder names")
+                    .on_hover_text("Assigns the NN_Name folders of the music folder to pad NN, the pads already assigned are kept")
+                    .clicked()
+                {
+                    let count = self.settings_data.fill_pad_assignments();
+                    info!("{count} pads assigned from the folder names");
+                }
+                if ui.button("+ Add pad").clicked() {
+                    let pad = self
+                        .settings_data
+                        .pad_assignments
+                        .iter()
+                        .map(|x| x.pad + 1)
+                        .max()
+                        .unwrap_or_default();
+                    self.settings_data.pad_assignments.push(PadAssignment {
+                        pad,
+                        folder: String::new(),
+                    });
+                }
+            });
+            let mut removed = None;
+            egui::ScrollArea::vertical()
+                .id_salt("pad_assignments")
+                .max_height(250.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("pad_assignments").striped(true).show(ui, |ui| {
+                        for (i, assignment) in
+                            self.settings_data.pad_assignments.iter_mut().enumerate()
+                        {
+                            ui.add(
+                                egui::DragValue::new(&mut assignment.pad)
+                                    .range(0..=MAX_PAD_COUNT - 1)
+                                    .prefix("Pad "),
+                            );
+                            let is_missing = assignment.is_missing();
+                            ui.add(
+                                egui::TextEdit::singleline(&mut assignment.folder)
+                                    .text_color_opt(
+                                        is_missing.then_some(egui::Color32::LIGHT_RED),
+                                    ),
+                            )
+                            .on_hover_text(if is_missing {
+                                "This folder does not exist"
+                            } else {
+                                "Folder played by the pad, sub folders included"
+                            });
+                            if ui.button("Browse...").clicked()
+                                && let Some(path) = get_default_file_dialog().pick_folder()
+                            {
+                                assignment.folder = path.display().to_string();
+                            }
+                            if ui.button("🗑").clicked() {
+                                removed = Some(i);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+            if let Some(i) = removed {
+                self.settings_data.pad_assignments.remove(i);
+            }
+        });
+    }
+
     pub fn render_settings_tab(&mut self, ui: &mut egui::Ui) {
         ui.vertical(|ui| {
             ui.heading("Settings");
@@ -80,6 +277,41 @@ impl AkaiVisualizer {
                 }
             });
 
+            ui.add_space(5.0);
+
+            ui.horizontal(|ui| {
+                ui.label("Key velocity curve:");
+                egui::ComboBox::from_id_salt("velocity_curve")
+                    .selected_text(self.settings_data.velocity_curve.to_string())
+                    .show_ui(ui, |ui| {
+                        for curve in VelocityCurve::ALL {
+                            ui.selectable_value(
+                                &mut self.settings_data.velocity_curve,
+                                curve,
+                                curve.to_string(),
+                            );
+                        }
+                    });
+            });
+
+            ui.add_space(5.0);
+
+            self.render_midi_settings(ui);
+
+            #[cfg(feature = "remote")]
+            self.render_remote_settings(ui);
+
+            #[cfg(feature = "osc")]
+            self.render_osc_settings(ui);
+
+            ui.add_space(5.0);
+
+            self.render_pad_assignments(ui);
+
+            ui.add_space(5.0);
+
+            self.render_keymap_editor(ui);
+
             ui.add_space(10.0);
 
             ui.with_layout(egui::Layout::bottom_up(egui::Align::Center), |ui| {
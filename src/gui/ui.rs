@@ -1,6 +1,9 @@
-use crate::gui::comms::command::CommsCommand;
+use crate::comms::command::CommsCommand;
 use crate::gui::local_view::audio_player_states::PlayerInfo;
+use crate::states::app_paths::AppPaths;
+use crate::states::cue_list::CueList;
 use crate::states::information_data::InformationEntry;
+use crate::states::scene::SceneLibrary;
 use crate::states::settings_data::SettingsData;
 use crate::states::visualizer::RuntimeData;
 use eframe::egui;
@@ -14,6 +17,7 @@ use std::time::Instant;
 pub enum CurrentTab {
     Visualizer,
     WebView,
+    CueList,
     Settings,
 }
 
@@ -42,9 +46,22 @@ impl GuiData {
 pub struct AkaiVisualizer {
     pub(crate) gui_data: Arc<Mutex<GuiData>>,
     pub(crate) info_panel_data: InfoPanelData,
+    pub(crate) scene_panel_data: ScenePanelData,
+    pub(crate) cue_panel_data: CuePanelData,
+    /// Settings tab values, saved on request
     pub(crate) settings_data: SettingsData,
+    /// Settings as last loaded or saved, to tell the unsaved edits apart
+    pub(crate) saved_settings: SettingsData,
+    /// Config reload last applied to the settings tab
+    pub(crate) config_revision: usize,
     pub(crate) current_tab: CurrentTab,
     pub(crate) webview_error: Option<String>,
+    pub(crate) config_path: String,
+    /// Problems found in the settings, shown until dismissed
+    pub(crate) config_issues: Vec<String>,
+    /// Profile whose files are shown, follows the one loaded by the backend
+    pub(crate) profile: Option<String>,
+    pub(crate) new_profile: String,
 }
 
 pub struct InfoPanelData {
@@ -53,14 +70,27 @@ pub struct InfoPanelData {
     pub(crate) editing_index: Option<usize>,
 }
 
+pub struct ScenePanelData {
+    pub(crate) library: Arc<Mutex<SceneLibrary>>,
+    pub(crate) editing_index: Option<usize>,
+}
+
+pub struct CuePanelData {
+    pub(crate) cue_list: Arc<Mutex<CueList>>,
+    pub(crate) folder: String,
+    pub(crate) new_adventure: String,
+}
+
 impl AkaiVisualizer {
     pub fn new(
         cc: &eframe::CreationContext<'_>,
         settings_data: &Arc<Mutex<SettingsData>>,
         gui_data: Arc<Mutex<GuiData>>,
-        font_folder: &str,
-        data_path: &str,
+        (scene_library, cue_list): (Arc<Mutex<SceneLibrary>>, Arc<Mutex<CueList>>),
+        paths: &AppPaths,
+        config_issues: Vec<String>,
     ) -> Self {
+        let font_folder = &paths.fonts;
         let fonts = vec![
             LoaderFontData {
                 name: "GoodTimesRg".into(),
@@ -72,27 +102,47 @@ impl AkaiVisualizer {
             },
         ];
         load_fonts(&cc.egui_ctx, fonts).expect("Font should be readable.");
+        let settings = settings_data
+            .lock()
+            .map_or_else(|_| SettingsData::default(), |s| s.clone());
 
         Self {
             gui_data,
             info_panel_data: InfoPanelData {
-                data_file_path: data_path.to_string(),
-                information_list: InformationEntry::load_from_file(data_path)
-                    .unwrap_or_default()
-                    .iter()
-                    .map(|x| x.data.clone())
-                    .collect(),
+                data_file_path: paths.data.clone(),
+                information_list: load_information_list(&paths.data),
+                editing_index: None,
+            },
+            scene_panel_data: ScenePanelData {
+                library: scene_library,
                 editing_index: None,
             },
-            settings_data: settings_data
-                .lock()
-                .map_or_else(|_| SettingsData::default(), |s| s.clone()),
+            cue_panel_data: CuePanelData {
+                cue_list,
+                folder: paths.cue_lists.clone(),
+                new_adventure: String::new(),
+            },
+            settings_data: settings.clone(),
+            saved_settings: settings,
+            config_revision: 0,
             current_tab: CurrentTab::Visualizer,
             webview_error: None,
+            config_path: paths.config.clone(),
+            config_issues,
+            profile: paths.profile.clone(),
+            new_profile: String::new(),
         }
     }
 }
 
+pub fn load_information_list(path: &str) -> Vec<String> {
+    InformationEntry::load_from_file(path)
+        .unwrap_or_default()
+        .iter()
+        .map(|x| x.data.clone())
+        .collect()
+}
+
 impl eframe::App for AkaiVisualizer {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         let now = Instant::now();
@@ -110,6 +160,8 @@ impl eframe::App for AkaiVisualizer {
         if need_refresh {
             self.send_command_to_backend(CommsCommand::Refresh {});
         }
+        self.follow_profile();
+        self.follow_config();
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.selectable_value(&mut self.current_tab, CurrentTab::Visualizer, "Teatro core");
@@ -121,18 +173,30 @@ impl eframe::App for AkaiVisualizer {
                         "web BYBE - Shop & Encounters",
                     );
                 };
+                ui.selectable_value(&mut self.current_tab, CurrentTab::CueList, "Cue list");
                 ui.selectable_value(&mut self.current_tab, CurrentTab::Settings, "Settings");
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    self.draw_profile_switcher(ui);
+                });
             });
             ui.separator();
+            self.draw_config_issues(ui);
 
             match self.current_tab {
-                CurrentTab::Visualizer => self.render_visualizer_tab(ui),
+                CurrentTab::Visualizer => {
+                    self.handle_hotkeys(ctx);
+                    self.render_visualizer_tab(ui);
+                }
                 CurrentTab::WebView => {
                     #[cfg(feature = "bybe")]
                     {
                         self.render_webview_tab(ui);
                     }
                 }
+                CurrentTab::CueList => {
+                    self.handle_hotkeys(ctx);
+                    self.render_cue_list_tab(ui);
+                }
                 CurrentTab::Settings => self.render_settings_tab(ui),
             }
         });
@@ -150,7 +214,7 @@ impl AkaiVisualizer {
 
     fn send_command(tx: &Sender<CommsCommand>, command: CommsCommand) {
         if let Err(e) = tx.send(command) {
-            warn!("Failed to send {command:?} command: {e}");
+            warn!("Failed to send {:?} command", e.into_inner());
         }
     }
 
@@ -0,0 +1,101 @@
+use crate::comms::command::CommsCommand;
+use crate::states::playlist_data::PlaylistData;
+use crate::states::visualizer::RuntimeData;
+use flume::{Receiver, Sender};
+use log::{debug, info, warn};
+use std::thread;
+use std::time::Duration;
+
+/// How often the state is refreshed, it also moves the track progress shown by the meters
+const REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Runs without a window: the state is refreshed on a timer and every change is logged.
+/// Commands come from MIDI and from the control surfaces (control socket, remote, OSC).
+/// Returns on SIGINT or SIGTERM, so that the session can be saved
+pub fn run_headless(rx_data: &Receiver<RuntimeData>, tx_command: &Sender<CommsCommand>) {
+    info!("Running headless");
+    let (tx_stop, rx_stop) = flume::bounded::<()>(1);
+    if let Err(e) = ctrlc::set_handler(move || {
+        let _ = tx_stop.try_send(());
+    }) {
+        warn!(
+            "Failed to handle the termination signals, the session is only saved periodically: {e}"
+        );
+    }
+    let tx_refresh = tx_command.clone();
+    thread::spawn(move || {
+        while tx_refresh.send(CommsCommand::Refresh).is_ok() {
+            thread::sleep(REFRESH_INTERVAL);
+        }
+        warn!("Command channel closed, state will not be refreshed anymore");
+    });
+
+    let mut previous: Option<RuntimeData> = None;
+    loop {
+        let data = flume::Selector::new()
+            .recv(rx_data, Result::ok)
+            .recv(&rx_stop, |_| None)
+            .wait();
+        let Some(data) = data else {
+            info!("Stopping");
+            break;
+        };
+        if let Some(previous) = &previous {
+            log_changes(previous, &data);
+        }
+        previous = Some(data);
+    }
+}
+
+fn log_changes(previous: &RuntimeData, current: &RuntimeData) {
+    if previous.last_pad_pressed != current.last_pad_pressed
+        && let Some(album) = current
+            .last_pad_pressed
+            .and_then(|x| current.pad_labels.get(x))
+    {
+        info!("Playing album {album}");
+    }
+    let get_track = |data: &RuntimeData| {
+        data.current_playlist
+            .as_ref()
+            .and_then(PlaylistData::get_current_track)
+            .map(|x| x.file_path)
+    };
+    let track = get_track(current);
+    if get_track(previous) != track
+        && let Some(track) = track
+    {
+        info!("Playing track {track}");
+    }
+    if previous.button_states.bits() != current.button_states.bits() {
+        let toggled: Vec<&str> = current
+            .button_states
+            .iter_names()
+            .map(|(name, _)| name)
+            .collect();
+        info!("Toggled buttons: [{}]", toggled.join(", "));
+    }
+    if previous.pad_page != current.pad_page {
+        info!(
+            "Pad page {}/{}",
+            current.pad_page + 1,
+            current.get_pad_page_count()
+        );
+    }
+    if previous.keyboard_bank != current.keyboard_bank {
+        info!(
+            "Keys bank {}/{}",
+            current.keyboard_bank + 1,
+            current.keyboard_bank_count
+        );
+    }
+    if previous.sounding_ambience != current.sounding_ambience {
+        info!("Sounding ambience: {:?}", current.sounding_ambience);
+    }
+    if previous.sounding_sound_effect != current.sounding_sound_effect {
+        info!("Sounding sound effect: {:?}", current.sounding_sound_effect);
+    }
+    if previous.knob_values != current.knob_values {
+        debug!("Knobs: {:?}", current.knob_values);
+    }
+}
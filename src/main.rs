@@ -1,28 +1,54 @@
 mod audio;
 mod backend;
+mod cli;
+mod comms;
+#[cfg(unix)]
+mod control;
 #[cfg(feature = "gui")]
 mod gui;
+mod headless;
 mod os_explorer;
+#[cfg(feature = "remote")]
+mod remote;
 mod states;
 
+#[cfg(feature = "midi")]
+use crate::backend::cue_output::start_cue_output;
+use crate::backend::keyboard_handler::KeyboardHandler;
+use crate::backend::library_handler::LibraryHandler;
+#[cfg(feature = "midi")]
 use crate::backend::listener_initializer::{prepare_midi_channels, run};
+use crate::backend::macro_handler::MacroHandler;
+#[cfg(feature = "osc")]
+use crate::backend::osc_server::start_osc_server;
 use crate::backend::pad_handler::PadHandler;
+use crate::backend::profile_handler::ProfileHandler;
+use crate::backend::session_handler::SessionHandler;
+#[cfg(feature = "midi")]
+use crate::backend::virtual_input::{MidiInjector, start_virtual_input};
+use crate::backend::watchdog_handler::handle_watchdog;
+use crate::cli::CliOptions;
+use crate::comms::command::CommsCommand;
+use crate::comms::command_relay::relay_commands_to_backend;
+#[cfg(feature = "gui")]
 use crate::gui::initializer::gui_initializer;
+use crate::states::app_paths::AppPaths;
 use crate::states::audio_sinks::AudioSinks;
+use crate::states::cue_event::CueEvent;
 use crate::states::filter_data::FilterData;
+use crate::states::knob_tracker::KnobTracker;
+use crate::states::midi_macro::MacroRecorder;
 use crate::states::music_state::MusicState;
 use crate::states::settings_data::SettingsData;
 use crate::states::sound_state::SoundState;
 use crate::states::visualizer::RuntimeData;
 use biquad::{Coefficients, DirectForm1, Q_BUTTERWORTH_F32, ToHertz, Type};
 use dotenvy::dotenv;
-use flume::Sender;
-use gui::comms::command::CommsCommand;
+use flume::{Receiver, Sender};
 use log::warn;
 use ramidier::io::input::InputChannel;
 use ramidier::io::output::ChannelOutput;
 use rodio::Sink;
-use std::env;
 use std::sync::{Arc, Mutex};
 
 #[derive(Clone)]
@@ -38,31 +64,30 @@ pub struct MidiInputChannels {
 
 fn main() {
     dotenv().ok();
+    let options = cli::parse_or_exit();
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
 
-    let config_path = env::var("CONFIG_PATH").unwrap_or_else(|_| "config.yml".to_string());
-
-    let settings_data = SettingsData::load_from_config(&config_path).unwrap_or_default();
-    let pad_labels =
-        PadHandler::get_pad_albums_list(&settings_data.music_folder).unwrap_or_else(|e| {
-            warn!("Could not load pads, music path is not readable: {e}");
-            vec![]
-        });
+    #[cfg_attr(not(feature = "gui"), allow(unused_variables))]
+    let (settings_data, config_issues) = load_settings(&options.paths.config);
     let settings = Arc::new(Mutex::new(settings_data));
-    let _watchdog_settings = settings.clone();
 
-    #[cfg(feature = "gui")]
     let watchdog_settings = settings.clone();
+    #[cfg(feature = "gui")]
+    let gui_settings = settings.clone();
 
-    let backend_data = RuntimeData::builder()
+    let mut backend_data = RuntimeData::builder()
         .settings_data(settings)
-        .pad_labels(pad_labels)
+        .library_index(LibraryHandler::load(&options.paths.library_index))
+        .maybe_profile(options.paths.profile.clone())
         .build();
+    PadHandler::update_pad_labels(&mut backend_data);
+    KeyboardHandler::update_key_labels(&mut backend_data);
 
     let hw_data = Arc::new(Mutex::new(backend_data.clone()));
     let (tx_data, rx_data) = flume::unbounded::<RuntimeData>();
-    let (gui_command_tx, gui_command_rx) = flume::unbounded::<CommsCommand>();
-    let (watchgod_tx, watchdog_rx) = flume::unbounded::<CommsCommand>();
+    let (command_tx, command_rx) = flume::unbounded::<CommsCommand>();
+    let (cue_tx, cue_rx) = flume::unbounded::<CueEvent>();
+    let (watchdog_tx, watchdog_rx) = flume::unbounded::<CommsCommand>();
 
     let stream_handle = rodio::OutputStreamBuilder::open_default_stream()
         .expect("Audio stream should be writable and readable");
@@ -70,86 +95,206 @@ fn main() {
     let ambience_queue = Sink::connect_new(stream_handle.mixer());
     let sound_effect_queue = Sink::connect_new(stream_handle.mixer());
 
-    let states = prepare_audio_states(
+    let (states, macro_rx) = prepare_audio_states(
         music_queue,
         ambience_queue,
         sound_effect_queue,
         hw_data,
         &tx_data,
+        (&cue_tx, &watchdog_tx),
+        &options.paths,
     );
+    start_background_services(&states.0, &command_tx, cue_rx, &options);
 
-    cfg_if::cfg_if! {
-        if #[cfg(all(feature = "midi", not(feature = "gui")))] {
-            if let Ok(midi_channels) = prepare_midi_channels() {
-                if let Err(err) = run(&states.0, &states.1, midi_channels.0, midi_channels.1) {
-                    eprintln!("MIDI Error: {err}");
-                };
-            };
-        } else if #[cfg(all(feature = "midi", feature = "gui"))]{
-            let m_state = states.0.clone();
-            let s_state = states.1.clone();
-            let midi_out_channels = if let Ok((in_channels, out_channels)) = prepare_midi_channels() {
-                let inner_out_channels = out_channels.clone();
-                std::thread::spawn(move || {
-                    if let Err(err) = run(&m_state, &s_state, in_channels, inner_out_channels) {
-                        eprintln!("MIDI Error: {err}");
-                    }
-                });
-                Some(out_channels)
-            } else {
-                warn!("Could not create midi channels, change ports or connect the midi keyboard");
-                None
-            };
-        } else {
-            let midi_out_channels = None;
-        }
-    }
+    // The injector has to outlive the virtual input, its sender is what keeps it running
+    #[cfg(feature = "midi")]
+    let (midi_out_channels, _midi_injector) = if options.no_midi {
+        (None, None)
+    } else {
+        let (midi_out_channels, injector) = spawn_midi_listeners(&states);
+        (midi_out_channels, Some(injector))
+    };
+    #[cfg(not(feature = "midi"))]
+    let midi_out_channels: Option<MidiOutputChannels> = None;
+    start_macro_replay(&states, macro_rx, &command_tx, midi_out_channels.as_ref());
+    start_output_services(&states, midi_out_channels.as_ref(), &options);
+    let session_state = states.0.clone();
 
+    start_watchdog(
+        watchdog_settings,
+        &states.0,
+        (&watchdog_tx, watchdog_rx),
+        &tx_data,
+        &options.paths,
+    );
     #[cfg(feature = "gui")]
-    {
-        use crate::gui::comms::to_backend_from_gui::handle_gui_command_and_relay_them_to_backend;
-        use crate::gui::comms::watchdog_handler::handle_watchdog;
-
-        let music_state = states.0;
-        let sound_state = states.1;
-        let m_state_watchdog = music_state.clone();
-
-        let gui_tx_data = tx_data.clone();
-        let watchdog_tx_data = tx_data;
+    let libraries = (states.0.scene_library.clone(), states.0.cue_list.clone());
 
-        let sync_tx_command = gui_command_tx.clone();
-
-        let gui_settings = watchdog_settings.clone();
-
-        std::thread::spawn(move || {
-            handle_gui_command_and_relay_them_to_backend(
-                &gui_command_rx,
-                &sync_tx_command,
-                &gui_tx_data,
-                &music_state,
-                &sound_state,
-                midi_out_channels,
-            );
-        });
-
-        std::thread::spawn(move || {
-            handle_watchdog(
-                &watchdog_settings,
-                &watchdog_rx,
-                &watchdog_tx_data,
-                &m_state_watchdog,
-            );
-        });
+    let relay_tx_command = command_tx.clone();
+    std::thread::spawn(move || {
+        relay_commands_to_backend(
+            &command_rx,
+            &relay_tx_command,
+            &tx_data,
+            &states.0,
+            &states.1,
+            midi_out_channels,
+        );
+    });
 
+    if options.is_headless() {
+        headless::run_headless(&rx_data, &command_tx);
+    } else {
+        #[cfg(feature = "gui")]
         gui_initializer(
             backend_data,
             gui_settings,
-            gui_command_tx,
+            libraries,
+            &options.paths,
+            (command_tx, watchdog_tx),
             rx_data,
-            watchgod_tx,
+            config_issues,
         )
         .expect("Application did not complete run correctly");
     }
+    SessionHandler::save(&session_state);
+}
+
+/// Settings with the problems found in them, the defaults are used when they cannot be read
+fn load_settings(path: &str) -> (SettingsData, Vec<String>) {
+    let (settings, issues) = SettingsData::load_from_config(path).unwrap_or_else(|e| {
+        let issue =
+            format!("Could not load the settings, the defaults are used until it is fixed: {e:#}");
+        let mut settings = SettingsData::default();
+        settings.fill_pad_assignments();
+        (settings, vec![issue])
+    });
+    for issue in &issues {
+        warn!("{issue}");
+    }
+    (settings, issues)
+}
+
+/// Listens to the controller and to the virtual ports in the background,
+/// returns the controller output if found and the injector of the virtual input
+#[cfg(feature = "midi")]
+fn spawn_midi_listeners(
+    states: &(MusicState, SoundState),
+) -> (Option<MidiOutputChannels>, MidiInjector) {
+    let (m_state, s_state) = states.clone();
+    let midi_out_channels = if let Ok((in_channels, out_channels)) = prepare_midi_channels() {
+        let inner_out_channels = out_channels.clone();
+        std::thread::spawn(move || {
+            if let Err(err) = run(&m_state, &s_state, in_channels, inner_out_channels) {
+                eprintln!("MIDI Error: {err}");
+            }
+        });
+        Some(out_channels)
+    } else {
+        warn!("Could not create midi channels, change ports or connect the midi keyboard");
+        None
+    };
+    let midi_injector = start_virtual_input(
+        &states.0,
+        &states.1,
+        midi_out_channels.as_ref().map(|x| x.midi_out.clone()),
+    );
+    (midi_out_channels, midi_injector)
+}
+
+/// Replays the macros asked by the GUI, the remote and the control socket, with or without a controller
+fn start_macro_replay(
+    states: &(MusicState, SoundState),
+    rx_macro: Receiver<u8>,
+    tx_command: &Sender<CommsCommand>,
+    midi_out_channels: Option<&MidiOutputChannels>,
+) {
+    let (m_state, s_state) = states.clone();
+    let (tx_command, midi_out) = (
+        tx_command.clone(),
+        midi_out_channels.map(|x| x.midi_out.clone()),
+    );
+    std::thread::spawn(move || {
+        MacroHandler::handle_macro_requests(
+            &rx_macro,
+            &tx_command,
+            &m_state,
+            &s_state,
+            midi_out.as_ref(),
+        );
+    });
+}
+
+/// Watches the config and the library folders in the background, the sender tells the watchdog
+/// that the settings were saved or the profile switched
+fn start_watchdog(
+    settings: Arc<Mutex<SettingsData>>,
+    music_state: &MusicState,
+    (watchdog_tx, watchdog_rx): (&Sender<CommsCommand>, Receiver<CommsCommand>),
+    tx_data: &Sender<RuntimeData>,
+    paths: &AppPaths,
+) {
+    let (self_tx, music_state, tx_data, paths) = (
+        watchdog_tx.clone(),
+        music_state.clone(),
+        tx_data.clone(),
+        paths.clone(),
+    );
+    std::thread::spawn(move || {
+        handle_watchdog(
+            &settings,
+            (&self_tx, &watchdog_rx),
+            &tx_data,
+            &music_state,
+            &paths,
+        );
+    });
+}
+
+/// Starts what drives the controller output: the OSC feedback and the LEDs of the restored session
+fn start_output_services(
+    states: &(MusicState, SoundState),
+    midi_out_channels: Option<&MidiOutputChannels>,
+    options: &CliOptions,
+) {
+    #[cfg(feature = "osc")]
+    start_osc_server(
+        &states.0,
+        &states.1,
+        midi_out_channels.map(|x| x.midi_out.clone()),
+    );
+    SessionHandler::restore(
+        &options.paths.session,
+        &states.0,
+        midi_out_channels
+            .and_then(|x| x.midi_out.lock().ok())
+            .as_deref_mut(),
+    );
+}
+
+/// Starts the services that only need the states: cue output and the control servers
+fn start_background_services(
+    music_state: &MusicState,
+    tx_command: &Sender<CommsCommand>,
+    cue_rx: Receiver<CueEvent>,
+    options: &CliOptions,
+) {
+    #[cfg(feature = "midi")]
+    if !options.no_midi {
+        start_cue_output(cue_rx, music_state);
+    }
+    #[cfg(not(feature = "midi"))]
+    drop(cue_rx);
+    SessionHandler::start_autosave(music_state);
+    LibraryHandler::start_scan(music_state);
+    #[cfg(feature = "remote")]
+    remote::start_remote_server(music_state, tx_command.clone());
+    #[cfg(unix)]
+    control::start_control_server(
+        &options.paths.control_socket,
+        music_state,
+        tx_command.clone(),
+    );
 }
 
 fn get_base_filter_data(coeffs: Coefficients<f32>) -> FilterData {
@@ -166,13 +311,27 @@ fn prepare_audio_states(
     sound_effect_queue: Sink,
     data: Arc<Mutex<RuntimeData>>,
     tx_data: &Sender<RuntimeData>,
-) -> (MusicState, SoundState) {
+    (tx_cue, tx_watchdog): (&Sender<CueEvent>, &Sender<CommsCommand>),
+    paths: &AppPaths,
+) -> ((MusicState, SoundState), Receiver<u8>) {
+    let (tx_macro, rx_macro) = flume::unbounded::<u8>();
     let audio_sinks = Arc::new(Mutex::new(AudioSinks {
         music_queue,
         ambience_queue,
         sound_effect_queue,
+        ambience_fade: Arc::default(),
+        sound_effect_fade: Arc::default(),
     }));
 
+    let macro_recorder = Arc::new(Mutex::new(MacroRecorder::default()));
+    let adventure = data
+        .lock()
+        .ok()
+        .and_then(|x| x.settings_data.lock().ok().map(|s| s.adventure.clone()))
+        .unwrap_or_default();
+    let (macro_library, scene_library, cue_list) =
+        ProfileHandler::load_libraries(paths, &adventure);
+
     let sample_rate = 44100.0;
     let coeffs = Coefficients::<f32>::from_params(
         Type::AllPass,
@@ -186,20 +345,67 @@ fn prepare_audio_states(
     let sound_effect_filter = Arc::new(Mutex::new(get_base_filter_data(coeffs)));
 
     (
-        MusicState {
-            audio_sinks: audio_sinks.clone(),
-            music_filter: music_filter_data,
-            ambience_filter: ambience_filter.clone(),
-            tx_data: tx_data.clone(),
-            data: data.clone(),
-            sound_effect_filter: sound_effect_filter.clone(),
-        },
-        SoundState {
-            data,
-            audio_sinks,
-            ambience_filter,
-            sound_effect_filter,
-            tx_data: tx_data.clone(),
-        },
+        (
+            MusicState {
+                audio_sinks: audio_sinks.clone(),
+                music_filter: music_filter_data,
+                ambience_filter: ambience_filter.clone(),
+                tx_data: tx_data.clone(),
+                data: data.clone(),
+                sound_effect_filter: sound_effect_filter.clone(),
+                knob_tracker: Arc::new(Mutex::new(KnobTracker::default())),
+                macro_recorder: macro_recorder.clone(),
+                macro_library: Arc::new(Mutex::new(macro_library)),
+                scene_library: Arc::new(Mutex::new(scene_library)),
+                cue_list: Arc::new(Mutex::new(cue_list)),
+                tx_macro,
+                tx_cue: tx_cue.clone(),
+                tx_watchdog: tx_watchdog.clone(),
+            },
+            SoundState {
+                data,
+                audio_sinks,
+                ambience_filter,
+                sound_effect_filter,
+                tx_data: tx_data.clone(),
+                macro_recorder,
+                tx_cue: tx_cue.clone(),
+            },
+        ),
+        rx_macro,
     )
 }
+
+/// States without an audio device or a controller, the receiver gets what is sent to the GUI
+#[cfg(test)]
+fn get_test_states() -> (MusicState, SoundState, Receiver<RuntimeData>) {
+    let data = RuntimeData::builder()
+        .settings_data(Arc::new(Mutex::new(SettingsData::default())))
+        .library_index(Arc::default())
+        .build();
+    let (tx_data, rx_data) = flume::unbounded();
+    let (tx_cue, _) = flume::unbounded();
+    let (tx_watchdog, _) = flume::unbounded();
+    let paths = AppPaths::builder()
+        .config("missing/config.toml".to_string())
+        .build();
+    let ((music_state, sound_state), _) = prepare_audio_states(
+        Sink::new().0,
+        Sink::new().0,
+        Sink::new().0,
+        Arc::new(Mutex::new(data)),
+        &tx_data,
+        (&tx_cue, &tx_watchdog),
+        &paths,
+    );
+    (music_state, sound_state, rx_data)
+}
+
+/// Empty folder for the files of a test, named after it so that tests do not share one
+#[cfg(test)]
+fn get_test_folder(name: &str) -> std::path::PathBuf {
+    let folder = std::env::temp_dir().join(format!("teatro-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&folder);
+    std::fs::create_dir_all(&folder).expect("The temporary folder should be writable");
+    folder
+}
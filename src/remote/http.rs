@@ -0,0 +1,216 @@
+use anyhow::bail;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+/// Requests carry everything in the line and headers, anything bigger is refused
+const MAX_HEADER_LINES: usize = 64;
+const MAX_LINE_LENGTH: u64 = 8 * 1024;
+const MAX_HEADER_BYTES: u64 = 16 * 1024;
+
+#[derive(Debug)]
+pub struct Request {
+    pub method: String,
+    pub path: String,
+    pub query: HashMap<String, String>,
+    /// Header names are lowercase
+    pub headers: HashMap<String, String>,
+}
+
+impl Request {
+    pub fn read_from(stream: impl Read) -> anyhow::Result<Self> {
+        let mut reader = BufReader::new(stream.take(MAX_HEADER_BYTES));
+        let mut line = String::new();
+        read_line(&mut reader, &mut line)?;
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+            bail!("Malformed request line: {line}");
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut request = Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            query: parse_query(query),
+            headers: HashMap::new(),
+        };
+        for _ in 0..MAX_HEADER_LINES {
+            read_line(&mut reader, &mut line)?;
+            let header = line.trim_end();
+            if header.is_empty() {
+                return Ok(request);
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                request
+                    .headers
+                    .insert(name.trim().to_lowercase(), value.trim().to_string());
+            }
+        }
+        bail!("Too many headers")
+    }
+
+    pub fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+
+    /// Path split on '/', without the empty segments
+    pub fn get_segments(&self) -> Vec<&str> {
+        self.path.split('/').filter(|x| !x.is_empty()).collect()
+    }
+}
+
+/// Reads a whole line into `line`, refusing lines without an end within the limits
+fn read_line(reader: &mut impl BufRead, line: &mut String) -> anyhow::Result<()> {
+    line.clear();
+    reader.by_ref().take(MAX_LINE_LENGTH).read_line(line)?;
+    if !line.ends_with('\n') {
+        bail!("Request line is too long or was cut");
+    }
+    Ok(())
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    pub fn json(status: u16, body: String) -> Self {
+        Self {
+            status,
+            content_type: "application/json",
+            body: body.into_bytes(),
+        }
+    }
+
+    pub fn error(status: u16, message: &str) -> Self {
+        Self::json(status, serde_json::json!({ "error": message }).to_string())
+    }
+
+    pub fn write_to(&self, stream: &mut impl Write) -> io::Result<()> {
+        write!(
+            stream,
+            "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            self.status,
+            get_reason(self.status),
+            self.content_type,
+            self.body.len()
+        )?;
+        stream.write_all(&self.body)?;
+        stream.flush()
+    }
+}
+
+const fn get_reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+fn parse_query(query: &str) -> HashMap<String, String> {
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            (!key.is_empty()).then(|| (percent_decode(key), percent_decode(value)))
+        })
+        .collect()
+}
+
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| text.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        if let Some(byte) = escaped {
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(if bytes[i] == b'+' { b' ' } else { bytes[i] });
+            i += 1;
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn request_keeps_path_query_and_lowercase_headers() {
+        let request = Request::read_from(
+            "POST /api/keys/3?velocity=90&name=a%20b+c HTTP/1.1\r\nHost: 127.0.0.1:7878\r\nAuthorization: Bearer secret\r\n\r\n"
+                .as_bytes(),
+        )
+        .expect("The request is valid");
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.get_segments(), ["api", "keys", "3"]);
+        assert_eq!(request.query["velocity"], "90");
+        assert_eq!(request.query["name"], "a b c");
+        assert_eq!(request.get_header("host"), Some("127.0.0.1:7878"));
+        assert_eq!(request.get_header("authorization"), Some("Bearer secret"));
+    }
+
+    #[test]
+    fn request_without_target_is_refused() {
+        assert!(Request::read_from(&b"GET\r\n\r\n"[..]).is_err());
+        assert!(Request::read_from(&b""[..]).is_err());
+    }
+
+    #[test]
+    fn request_with_too_many_headers_is_refused() {
+        let request = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-A: b\r\n".repeat(MAX_HEADER_LINES)
+        );
+        assert!(Request::read_from(request.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn endless_lines_are_refused() {
+        assert!(Request::read_from(io::repeat(b'a')).is_err());
+        let header = format!("X-A: {}\r\n", "b".repeat(MAX_LINE_LENGTH as usize));
+        let request = format!("GET / HTTP/1.1\r\n{header}\r\n");
+        assert!(Request::read_from(request.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn headers_over_the_total_size_are_refused() {
+        let header = format!("X-A: {}\r\n", "b".repeat(MAX_LINE_LENGTH as usize / 2));
+        let request = format!("GET / HTTP/1.1\r\n{}\r\n", header.repeat(4));
+        assert!(Request::read_from(request.as_bytes()).is_err());
+        let request = format!("GET / HTTP/1.1\r\n{}\r\n", header.repeat(2));
+        assert!(Request::read_from(request.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn invalid_escapes_are_kept_as_is() {
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%41"), "%zzA");
+    }
+
+    #[test]
+    fn response_has_status_and_length() {
+        let mut written = Vec::new();
+        Response::error(404, "Unknown route")
+            .write_to(&mut written)
+            .expect("Writing to a vector should not fail");
+        let written = String::from_utf8_lossy(&written);
+        assert!(written.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(written.contains("Content-Length: 25\r\n"));
+        assert!(written.ends_with("\r\n\r\n{\"error\":\"Unknown route\"}"));
+    }
+}
@@ -0,0 +1,504 @@
+mod http;
+mod websocket;
+
+use crate::backend::keyboard_handler::{N_OF_KEYS, is_ambience_key};
+use crate::backend::state_snapshot::StateSnapshot;
+use crate::comms::command::CommsCommand;
+use crate::remote::http::{Request, Response};
+use crate::states::knob_value_update::KnobValueUpdate;
+use crate::states::music_state::MusicState;
+use crate::states::visualizer::PADS_PER_PAGE;
+use anyhow::anyhow;
+use flume::Sender;
+use log::{debug, info, warn};
+use std::collections::HashMap;
+use std::io::ErrorKind;
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tungstenite::Message;
+
+const INDEX_PAGE: &str = include_str!("../../ui/remote/index.html");
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(500);
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+const DEFAULT_VELOCITY: u8 = 100;
+const N_OF_KNOBS: u8 = 8;
+/// Each connection has its own thread, the ones above this are refused
+const MAX_CONNECTIONS: usize = 32;
+
+/// Serves the remote page, the REST api and the state websocket on the configured address
+pub fn start_remote_server(state: &MusicState, tx_command: Sender<CommsCommand>) {
+    let Some(address) = state.data.lock().ok().and_then(|data| {
+        data.settings_data
+            .lock()
+            .ok()
+            .map(|x| x.remote_address.clone())
+    }) else {
+        warn!("Failed to read settings, remote control will not be started");
+        return;
+    };
+    let listener = match TcpListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(e) => {
+            warn!("Could not listen on {address}, remote control will not be available: {e}");
+            return;
+        }
+    };
+    info!("Remote control listening on http://{address}");
+    let state = state.clone();
+    let open_connections = Arc::new(AtomicUsize::new(0));
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(mut stream) => {
+                    let Some(slot) = ConnectionSlot::take(&open_connections) else {
+                        debug!("Too many remote connections, refusing one");
+                        let _ = Response::error(503, "Too many connections").write_to(&mut stream);
+                        continue;
+                    };
+                    let state = state.clone();
+                    let tx_command = tx_command.clone();
+                    let address = address.clone();
+                    std::thread::spawn(move || {
+                        // Read for each request, so that a changed token applies at once
+                        let result = get_remote_token(&state).and_then(|token| {
+                            let access = Access {
+                                address: &address,
+                                token: &token,
+                            };
+                            handle_connection(stream, &state, &tx_command, &access)
+                        });
+                        if let Err(e) = result {
+                            debug!("Remote connection closed: {e}");
+                        }
+                        drop(slot);
+                    });
+                }
+                Err(e) => warn!("Failed to accept remote connection: {e}"),
+            }
+        }
+    });
+}
+
+/// Token required by remotes in the current settings, empty when none is
+fn get_remote_token(state: &MusicState) -> anyhow::Result<String> {
+    let data = state
+        .data
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock the data to read the remote token"))?;
+    let token = data
+        .settings_data
+        .lock()
+        .map_err(|_| anyhow!("Failed to lock the settings to read the remote token"))?
+        .remote_token
+        .clone();
+    Ok(token)
+}
+
+/// One of the open connections, given back when dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn take(open_connections: &Arc<AtomicUsize>) -> Option<Self> {
+        open_connections
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |x| {
+                (x < MAX_CONNECTIONS).then_some(x + 1)
+            })
+            .ok()
+            .map(|_| Self(open_connections.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// What a request is checked against before reaching the api
+struct Access<'a> {
+    /// Address from the settings, the server may also be reached by IP or as localhost
+    address: &'a str,
+    /// Empty when no token is required
+    token: &'a str,
+}
+
+fn handle_connection(
+    mut stream: TcpStream,
+    state: &MusicState,
+    tx_command: &Sender<CommsCommand>,
+    access: &Access,
+) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let request = Request::read_from(&stream)?;
+    debug!("{} {}", request.method, request.path);
+    // The page itself holds no data, it asks for the token when the api refuses it
+    if request.path != "/" {
+        if !is_same_origin(&request, access) {
+            Response::error(403, "Requests from other sites are refused").write_to(&mut stream)?;
+            return Ok(());
+        }
+        if !is_authorized(&request, access.token) {
+            Response::error(401, "Missing or invalid token").write_to(&mut stream)?;
+            return Ok(());
+        }
+    }
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => Response {
+            status: 200,
+            content_type: "text/html; charset=utf-8",
+            body: INDEX_PAGE.as_bytes().to_vec(),
+        }
+        .write_to(&mut stream)?,
+        ("GET", "/ws") => {
+            if let Some(key) = request.get_header("sec-websocket-key") {
+                websocket::write_handshake(&mut stream, key)?;
+                stream_snapshots(stream, state, access.token)?;
+            } else {
+                Response::error(400, "Expected a websocket upgrade").write_to(&mut stream)?;
+            }
+        }
+        ("GET", "/api/state") => match StateSnapshot::take(state) {
+            Some(snapshot) => Response::json(200, serde_json::to_string(&snapshot)?),
+            None => Response::error(500, "Failed to read the current state"),
+        }
+        .write_to(&mut stream)?,
+        ("POST", _) => match get_command(&request.get_segments(), &request.query) {
+            Ok(command) => {
+                let body = serde_json::to_string(&command)?;
+                tx_command.send(command)?;
+                Response::json(202, body)
+            }
+            Err(response) => response,
+        }
+        .write_to(&mut stream)?,
+        _ => Response::error(404, "Unknown route").write_to(&mut stream)?,
+    }
+    Ok(())
+}
+
+fn is_authorized(request: &Request, token: &str) -> bool {
+    token.is_empty()
+        || request
+            .get_header("authorization")
+            .and_then(|x| x.strip_prefix("Bearer "))
+            .or_else(|| request.query.get("token").map(String::as_str))
+            .is_some_and(|given| is_token_equal(given, token))
+}
+
+/// Takes the same time wherever the first difference is, so the token cannot be guessed
+/// one byte at a time
+fn is_token_equal(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+/// Browsers send the origin of the page with each POST and websocket upgrade, it has to be
+/// this server. Without a token the host is checked too, so that another site whose name
+/// points to this address (DNS rebinding) cannot read the state either
+fn is_same_origin(request: &Request, access: &Access) -> bool {
+    let Some(host) = request.get_header("host") else {
+        return false;
+    };
+    let is_origin_valid = request
+        .get_header("origin")
+        .is_none_or(|origin| origin.strip_prefix("http://") == Some(host));
+    is_origin_valid && (!access.token.is_empty() || is_local_host(host, access.address))
+}
+
+/// Host given as an IP address, as localhost or as in the settings
+fn is_local_host(host: &str, address: &str) -> bool {
+    let name = host
+        .rsplit_once(':')
+        .filter(|(_, port)| port.parse::<u16>().is_ok())
+        .map_or(host, |(name, _)| name);
+    let name = name.trim_start_matches('[').trim_end_matches(']');
+    name.eq_ignore_ascii_case("localhost")
+        || name.parse::<IpAddr>().is_ok()
+        || address
+            .rsplit_once(':')
+            .is_some_and(|(configured, _)| name.eq_ignore_ascii_case(configured))
+}
+
+/// Pushes the state each time it changes until the client closes or goes away,
+/// pings are answered while waiting for the next change. A new token in the settings
+/// closes the socket, it was opened with the old one
+fn stream_snapshots(stream: TcpStream, state: &MusicState, token: &str) -> anyhow::Result<()> {
+    stream.set_read_timeout(Some(SNAPSHOT_INTERVAL))?;
+    let mut socket = websocket::accept(stream);
+    let mut last_sent = String::new();
+    loop {
+        if get_remote_token(state)? != token {
+            debug!("The remote token changed, closing a websocket");
+            socket.close(None)?;
+            return Ok(());
+        }
+        if let Some(snapshot) = StateSnapshot::take(state) {
+            let json = serde_json::to_string(&snapshot)?;
+            if json != last_sent {
+                socket.send(Message::text(json.clone()))?;
+                last_sent = json;
+            }
+        }
+        match socket.read() {
+            // Sends back the close frame queued by the read
+            Ok(Message::Close(_)) => {
+                return match socket.flush() {
+                    Ok(()) | Err(tungstenite::Error::ConnectionClosed) => Ok(()),
+                    Err(e) => Err(e.into()),
+                };
+            }
+            Ok(_) => {}
+            Err(tungstenite::Error::Io(e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {}
+            Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn get_command(
+    segments: &[&str],
+    query: &HashMap<String, String>,
+) -> Result<CommsCommand, Response> {
+    match segments {
+        ["api", "pads", "next-bank"] => Ok(CommsCommand::NextPadPagePressed),
+        ["api", "pads", "previous-bank"] => Ok(CommsCommand::PreviousPadPagePressed),
+        ["api", "pads", pad] => parse_in_range(pad, 0, PADS_PER_PAGE as u8 - 1)
+            .map(|key| CommsCommand::PadPressed { key }),
+        ["api", "keys", "next-bank"] => Ok(CommsCommand::NextKeyboardBankPressed),
+        ["api", "keys", "previous-bank"] => Ok(CommsCommand::PreviousKeyboardBankPressed),
+        ["api", "keys", key] => {
+            let key = parse_in_range(key, 1, N_OF_KEYS)?;
+            let velocity = query
+                .get("velocity")
+                .map_or(Ok(DEFAULT_VELOCITY), |x| parse_in_range(x, 1, 127))?;
+            Ok(if is_ambience_key(key) {
+                CommsCommand::BlackKeyPressed { key, velocity }
+            } else {
+                CommsCommand::WhiteKeyPressed { key, velocity }
+            })
+        }
+        ["api", "keys", key, "release"] => {
+            parse_in_range(key, 1, N_OF_KEYS).map(|key| CommsCommand::KeyReleased { key })
+        }
+        ["api", "knobs", knob] => {
+            let knob = parse_in_range(knob, 1, N_OF_KNOBS)?;
+            query
+                .get("value")
+                .and_then(|x| x.parse::<f32>().ok())
+                .filter(|x| (0.0..=1.0).contains(x))
+                .map(|value| CommsCommand::KnobValueSet { knob, value })
+                .ok_or_else(|| Response::error(400, "Expected a value between 0 and 1"))
+        }
+        [
+            "api",
+            "knobs",
+            knob,
+            direction @ ("increment" | "decrement"),
+        ] => {
+            let knob = parse_in_range(knob, 1, N_OF_KNOBS)?;
+            let value = if *direction == "increment" {
+                KnobValueUpdate::Increment
+            } else {
+                KnobValueUpdate::Decrement
+            };
+            Ok(CommsCommand::KnobPercentageChanged { knob, value })
+        }
+        ["api", "pause"] => Ok(CommsCommand::PausePressed),
+        ["api", "skip"] => Ok(CommsCommand::SkipTrackPressed),
+        ["api", "stop-all"] => Ok(CommsCommand::StopAllPressed),
+        ["api", "mute"] => Ok(CommsCommand::MutePressed),
+        ["api", "solo"] => Ok(CommsCommand::SoloPressed),
+        ["api", "loop"] => Ok(CommsCommand::LoopPressed),
+        ["api", "shuffle"] => Ok(CommsCommand::ShufflePressed),
+        _ => Err(Response::error(404, "Unknown route")),
+    }
+}
+
+fn parse_in_range(text: &str, min: u8, max: u8) -> Result<u8, Response> {
+    text.parse::<u8>()
+        .ok()
+        .filter(|x| (min..=max).contains(x))
+        .ok_or_else(|| Response::error(400, &format!("Expected a number between {min} and {max}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::thread;
+
+    fn get_request(headers: &str) -> Request {
+        Request::read_from(format!("POST /api/pause HTTP/1.1\r\n{headers}\r\n").as_bytes())
+            .expect("The request is valid")
+    }
+
+    #[test]
+    fn requests_from_other_sites_are_refused() {
+        let access = Access {
+            address: "0.0.0.0:7878",
+            token: "",
+        };
+        for headers in [
+            "Host: 192.168.1.5:7878\r\n",
+            "Host: 192.168.1.5:7878\r\nOrigin: http://192.168.1.5:7878\r\n",
+            "Host: localhost:7878\r\nOrigin: http://localhost:7878\r\n",
+            "Host: [::1]:7878\r\n",
+        ] {
+            assert!(is_same_origin(&get_request(headers), &access), "{headers}");
+        }
+        for headers in [
+            "",
+            "Host: 127.0.0.1:7878\r\nOrigin: http://evil.example\r\n",
+            "Host: 127.0.0.1:7878\r\nOrigin: null\r\n",
+            "Host: evil.example:7878\r\nOrigin: http://evil.example:7878\r\n",
+        ] {
+            assert!(!is_same_origin(&get_request(headers), &access), "{headers}");
+        }
+    }
+
+    #[test]
+    fn token_is_read_from_the_header_or_the_query() {
+        assert!(is_authorized(&get_request(""), ""));
+        assert!(is_authorized(
+            &get_request("Authorization: Bearer secret\r\n"),
+            "secret"
+        ));
+        for headers in [
+            "",
+            "Authorization: Bearer secreT\r\n",
+            "Authorization: secret\r\n",
+        ] {
+            assert!(!is_authorized(&get_request(headers), "secret"), "{headers}");
+        }
+        let request = Request::read_from(&b"POST /api/pause?token=secret HTTP/1.1\r\n\r\n"[..])
+            .expect("The request is valid");
+        assert!(is_authorized(&request, "secret"));
+        assert!(!is_token_equal("secret", "secret2"));
+        assert!(!is_token_equal("", "secret"));
+    }
+
+    #[test]
+    fn connections_over_the_limit_get_no_slot() {
+        let open_connections = Arc::new(AtomicUsize::new(0));
+        let mut slots: Vec<_> = (0..MAX_CONNECTIONS)
+            .map_while(|_| ConnectionSlot::take(&open_connections))
+            .collect();
+        assert_eq!(slots.len(), MAX_CONNECTIONS);
+        assert!(ConnectionSlot::take(&open_connections).is_none());
+        slots.pop();
+        assert!(ConnectionSlot::take(&open_connections).is_some());
+    }
+
+    #[test]
+    fn named_hosts_need_a_token_or_the_settings() {
+        let request = get_request("Host: teatro.lan:7878\r\nOrigin: http://teatro.lan:7878\r\n");
+        let mut access = Access {
+            address: "0.0.0.0:7878",
+            token: "",
+        };
+        assert!(!is_same_origin(&request, &access));
+        access.address = "teatro.lan:7878";
+        assert!(is_same_origin(&request, &access));
+        access = Access {
+            address: "0.0.0.0:7878",
+            token: "secret",
+        };
+        assert!(is_same_origin(&request, &access));
+        let other_site = get_request("Host: teatro.lan:7878\r\nOrigin: http://evil.example\r\n");
+        assert!(!is_same_origin(&other_site, &access));
+    }
+
+    /// Opcode and payload of the next frame sent by the server, which never masks them
+    fn read_server_frame(stream: &mut TcpStream) -> (u8, Vec<u8>) {
+        let mut header = [0u8; 2];
+        stream
+            .read_exact(&mut header)
+            .expect("The server should send a frame");
+        let len = match header[1] {
+            126 => {
+                let mut len = [0u8; 2];
+                stream.read_exact(&mut len).expect("The length follows");
+                usize::from(u16::from_be_bytes(len))
+            }
+            len => usize::from(len),
+        };
+        let mut payload = vec![0u8; len];
+        stream
+            .read_exact(&mut payload)
+            .expect("The payload follows");
+        (header[0] & 0x0F, payload)
+    }
+
+    #[test]
+    fn websocket_answers_ping_and_ends_on_close() {
+        let (music_state, _, _) = crate::get_test_states();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("A local port should be free");
+        let address = listener.local_addr().expect("The listener has an address");
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("The client connects");
+            stream_snapshots(stream, &music_state, "")
+        });
+        let mut client = TcpStream::connect(address).expect("The server is listening");
+        client
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .expect("The timeout is valid");
+
+        let (opcode, snapshot) = read_server_frame(&mut client);
+        assert_eq!(opcode, 0x1);
+        assert!(serde_json::from_slice::<serde_json::Value>(&snapshot).is_ok());
+
+        // Masked "Hello" of RFC 6455 section 5.7 as a ping, then a close with status 1000
+        client
+            .write_all(&[
+                0x89, 0x85, 0x37, 0xFA, 0x21, 0x3D, 0x7F, 0x9F, 0x4D, 0x51, 0x58,
+            ])
+            .expect("The server is connected");
+        assert_eq!(read_server_frame(&mut client), (0xA, b"Hello".to_vec()));
+        client
+            .write_all(&[0x88, 0x82, 0, 0, 0, 0, 0x03, 0xE8])
+            .expect("The server is connected");
+        assert_eq!(read_server_frame(&mut client), (0x8, vec![0x03, 0xE8]));
+
+        let result = server.join().expect("The server should not panic");
+        assert!(result.is_ok());
+        let mut rest = Vec::new();
+        assert_eq!(client.read_to_end(&mut rest).ok(), Some(0));
+    }
+
+    #[test]
+    fn websocket_closes_once_the_token_changes() {
+        let (music_state, _, _) = crate::get_test_states();
+        let set_token = |state: &MusicState, token: &str| {
+            if let Ok(data) = state.data.lock()
+                && let Ok(mut settings) = data.settings_data.lock()
+            {
+                settings.remote_token = token.to_string();
+            }
+        };
+        set_token(&music_state, "secret");
+        let listener = TcpListener::bind("127.0.0.1:0").expect("A local port should be free");
+        let address = listener.local_addr().expect("The listener has an address");
+        let server_state = music_state.clone();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().expect("The client connects");
+            stream_snapshots(stream, &server_state, "secret")
+        });
+        let mut client = TcpStream::connect(address).expect("The server is listening");
+        client
+            .set_read_timeout(Some(READ_TIMEOUT))
+            .expect("The timeout is valid");
+        assert_eq!(read_server_frame(&mut client).0, 0x1);
+
+        set_token(&music_state, "revoked");
+        assert_eq!(read_server_frame(&mut client), (0x8, vec![]));
+        let result = server.join().expect("The server should not panic");
+        assert!(result.is_ok());
+    }
+}
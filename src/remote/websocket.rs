@@ -0,0 +1,41 @@
+use std::io::{self, Read, Write};
+use tungstenite::WebSocket;
+use tungstenite::handshake::derive_accept_key;
+use tungstenite::protocol::{Role, WebSocketConfig};
+
+/// The page only sends control frames, anything bigger is refused
+const MAX_CLIENT_PAYLOAD: usize = 4096;
+
+/// Answers the upgrade request, it was already read and checked by the HTTP server
+pub fn write_handshake(stream: &mut impl Write, client_key: &str) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {}\r\n\r\n",
+        derive_accept_key(client_key.trim().as_bytes())
+    )?;
+    stream.flush()
+}
+
+/// Websocket on a stream whose handshake is done
+pub fn accept<S: Read + Write>(stream: S) -> WebSocket<S> {
+    let config = WebSocketConfig::default()
+        .max_message_size(Some(MAX_CLIENT_PAYLOAD))
+        .max_frame_size(Some(MAX_CLIENT_PAYLOAD));
+    WebSocket::from_raw_socket(stream, Role::Server, Some(config))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accept_key_matches_the_rfc_6455_handshake() {
+        let mut response = Vec::new();
+        write_handshake(&mut response, " dGhlIHNhbXBsZSBub25jZQ==\r\n")
+            .expect("Writing to a vector should not fail");
+        assert!(
+            String::from_utf8_lossy(&response)
+                .contains("Sec-WebSocket-Accept: s3pPLMBiTxaQ9kYGzzhZRbK+xOo=\r\n")
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use bon::bon;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Files and folders used by the application. Command line values come first,
+/// then the profile folder, then the environment variables and the defaults
+#[derive(Debug, Clone)]
+pub struct AppPaths {
+    /// Profile whose folder holds the files, if any
+    pub profile: Option<String>,
+    pub config: String,
+    #[cfg(feature = "gui")]
+    pub data: String,
+    pub macros: String,
+    pub scenes: String,
+    /// Folder with a cue list file per adventure
+    pub cue_lists: String,
+    /// Runtime state restored on startup
+    pub session: String,
+    /// Cache of the library index, shared by the profiles
+    pub library_index: String,
+    #[cfg(feature = "gui")]
+    pub fonts: String,
+    #[cfg(feature = "gui")]
+    pub icons: String,
+    pub control_socket: PathBuf,
+}
+
+#[bon]
+impl AppPaths {
+    #[builder]
+    #[cfg_attr(not(feature = "gui"), allow(clippy::needless_pass_by_value))]
+    pub fn new(
+        config: Option<String>,
+        // The information list is only shown by the GUI
+        #[cfg_attr(not(feature = "gui"), allow(unused_variables))] data: Option<String>,
+        profile: Option<String>,
+    ) -> Self {
+        let profile_folder = profile
+            .as_ref()
+            .map(|name| get_profiles_folder().join(name));
+        let in_profile = |file: &str, var: &str, default: &str| {
+            profile_folder.as_ref().map_or_else(
+                || env::var(var).unwrap_or_else(|_| default.to_string()),
+                |folder| folder.join(file).to_string_lossy().into_owned(),
+            )
+        };
+        Self {
+            profile,
+            config: config.unwrap_or_else(|| {
+                get_legacy_config_path(in_profile("config.toml", "CONFIG_PATH", "config.toml"))
+            }),
+            #[cfg(feature = "gui")]
+            data: data.unwrap_or_else(|| in_profile("data.json", "DATA_PATH", "data.json")),
+            macros: in_profile("macros.json", "MACROS_PATH", "macros.json"),
+            scenes: in_profile("scenes.json", "SCENES_PATH", "scenes.json"),
+            cue_lists: in_profile("cue_lists", "CUE_LISTS_PATH", "cue_lists"),
+            session: in_profile("session.json", "SESSION_PATH", "session.json"),
+            library_index: env::var("LIBRARY_INDEX_PATH")
+                .unwrap_or_else(|_| "library_index.json".to_string()),
+            #[cfg(feature = "gui")]
+            fonts: env::var("FONT_FOLDER").unwrap_or_else(|_| "ui/fonts".to_string()),
+            #[cfg(feature = "gui")]
+            icons: env::var("ICON_PATH").unwrap_or_else(|_| "ui/icons".to_string()),
+            control_socket: get_control_socket_path(),
+        }
+    }
+}
+
+impl AppPaths {
+    /// Profiles found in the profiles folder, sorted by name
+    pub fn get_profiles() -> Vec<String> {
+        let mut profiles: Vec<String> = std::fs::read_dir(get_profiles_folder())
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .filter(|x| x.path().is_dir())
+                    .map(|x| x.file_name().to_string_lossy().into_owned())
+                    .collect()
+            })
+            .unwrap_or_default();
+        profiles.sort();
+        profiles
+    }
+
+    /// Creates the folder of the profile if missing
+    #[cfg(feature = "gui")]
+    pub fn create_profile(name: &str) -> std::io::Result<()> {
+        std::fs::create_dir_all(get_profiles_folder().join(name))
+    }
+}
+
+fn get_profiles_folder() -> PathBuf {
+    PathBuf::from(env::var("PROFILES_PATH").unwrap_or_else(|_| "profiles".to_string()))
+}
+
+/// Older versions saved the TOML settings as config.yml, it is still read when there is
+/// no config.toml next to it
+fn get_legacy_config_path(path: String) -> String {
+    let legacy = Path::new(&path).with_extension("yml");
+    if path.ends_with("config.toml") && !Path::new(&path).exists() && legacy.exists() {
+        legacy.to_string_lossy().into_owned()
+    } else {
+        path
+    }
+}
+
+/// Socket shared by the running instance and `teatro ctl`, overridden by `CONTROL_SOCKET_PATH`
+fn get_control_socket_path() -> PathBuf {
+    env::var("CONTROL_SOCKET_PATH").map_or_else(
+        |_| {
+            env::var("XDG_RUNTIME_DIR").map_or_else(
+                |_| {
+                    env::temp_dir().join(format!(
+                        "teatro-{}.sock",
+                        env::var("USER").unwrap_or_default()
+                    ))
+                },
+                |dir| PathBuf::from(dir).join("teatro.sock"),
+            )
+        },
+        PathBuf::from,
+    )
+}
@@ -1,7 +1,13 @@
+use crate::audio::fading_source::FadeControl;
 use rodio::Sink;
+use std::sync::Arc;
 
 pub struct AudioSinks {
     pub music_queue: Sink,
     pub ambience_queue: Sink,
     pub sound_effect_queue: Sink,
+    /// Fade outs of the files played by the black keys
+    pub ambience_fade: Arc<FadeControl>,
+    /// Fade outs of the files played by the white keys
+    pub sound_effect_fade: Arc<FadeControl>,
 }
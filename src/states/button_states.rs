@@ -21,6 +21,37 @@ bitflags! {
     }
 }
 
+impl ToggleStates {
+    /// Toggles that change what is heard, saved and restored by scenes
+    pub const SCENE_STATES: Self = Self::CLIP_STOP
+        .union(Self::MUTE)
+        .union(Self::SELECT)
+        .union(Self::STOP_ALL)
+        .union(Self::SEND)
+        .union(Self::FILTER);
+
+    /// Toggles restored on startup, buttons only held for a moment or arming a recording are left out
+    pub const SESSION_STATES: Self = Self::all()
+        .difference(Self::SHIFT)
+        .difference(Self::REC_ARM)
+        .difference(Self::START);
+
+    /// Names of the toggles, as they are saved to disk
+    pub fn get_names(self) -> Vec<String> {
+        self.iter_names()
+            .map(|(name, _)| name.to_string())
+            .collect()
+    }
+
+    /// Toggles read back from their names, unknown names are skipped
+    pub fn from_names(names: &[String]) -> Self {
+        names
+            .iter()
+            .filter_map(|name| Self::from_name(name))
+            .fold(Self::empty(), |acc, x| acc | x)
+    }
+}
+
 impl From<SoftKey> for ToggleStates {
     fn from(value: SoftKey) -> Self {
         match value {
@@ -0,0 +1,154 @@
+use midi_msg::{Channel, ChannelVoiceMsg, ControlChange, MidiMsg};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// Something that happened during the session and can be forwarded to external gear
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CueEvent {
+    PadPressed { album: usize },
+    AmbienceStarted { key: u8 },
+    SoundEffectStarted { key: u8 },
+    TrackChanged { track: u64 },
+}
+
+/// Events a mapping reacts to, fields left empty match any value
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum CueTrigger {
+    PadPressed { album: Option<usize> },
+    AmbienceStarted { key: Option<u8> },
+    SoundEffectStarted { key: Option<u8> },
+    TrackChanged,
+}
+
+impl CueTrigger {
+    pub fn matches(&self, event: CueEvent) -> bool {
+        match (self, event) {
+            (Self::PadPressed { album }, CueEvent::PadPressed { album: a }) => {
+                album.is_none_or(|x| x == a)
+            }
+            (Self::AmbienceStarted { key }, CueEvent::AmbienceStarted { key: k })
+            | (Self::SoundEffectStarted { key }, CueEvent::SoundEffectStarted { key: k }) => {
+                key.is_none_or(|x| x == k)
+            }
+            (Self::TrackChanged, CueEvent::TrackChanged { .. }) => true,
+            _ => false,
+        }
+    }
+}
+
+/// MIDI message sent on a cue, channels go from 1 to 16
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CueMessage {
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    ControlChange {
+        channel: u8,
+        control: u8,
+        value: u8,
+    },
+    /// Note on, followed by its note off after `length_ms`
+    Note {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+        #[serde(default = "default_note_length")]
+        length_ms: u64,
+    },
+}
+
+const fn default_note_length() -> u64 {
+    500
+}
+
+/// Message on the channel, counted from 1 like on the gear
+fn get_channel_message(channel: u8, msg: ChannelVoiceMsg) -> Vec<u8> {
+    MidiMsg::ChannelVoice {
+        channel: Channel::from_u8(channel.saturating_sub(1)),
+        msg,
+    }
+    .to_midi()
+}
+
+impl CueMessage {
+    pub fn to_midi(self) -> Vec<u8> {
+        match self {
+            Self::ProgramChange { channel, program } => {
+                get_channel_message(channel, ChannelVoiceMsg::ProgramChange { program })
+            }
+            Self::ControlChange {
+                channel,
+                control,
+                value,
+            } => get_channel_message(
+                channel,
+                ChannelVoiceMsg::ControlChange {
+                    control: ControlChange::CC { control, value },
+                },
+            ),
+            Self::Note {
+                channel,
+                note,
+                velocity,
+                ..
+            } => get_channel_message(channel, ChannelVoiceMsg::NoteOn { note, velocity }),
+        }
+    }
+
+    /// Message that ends this one and how long to wait before sending it
+    pub fn get_release(self) -> Option<(Vec<u8>, Duration)> {
+        match self {
+            Self::Note {
+                channel,
+                note,
+                length_ms,
+                ..
+            } => Some((
+                get_channel_message(channel, ChannelVoiceMsg::NoteOff { note, velocity: 0 }),
+                Duration::from_millis(length_ms),
+            )),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CueMapping {
+    pub on: CueTrigger,
+    pub send: Vec<CueMessage>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_sent_on_the_channel_counted_from_1() {
+        let note = CueMessage::Note {
+            channel: 2,
+            note: 60,
+            velocity: 100,
+            length_ms: 250,
+        };
+        assert_eq!(note.to_midi(), [0x91, 60, 100]);
+        assert_eq!(
+            note.get_release(),
+            Some((vec![0x81, 60, 0], Duration::from_millis(250)))
+        );
+        let program = CueMessage::ProgramChange {
+            channel: 16,
+            program: 5,
+        };
+        assert_eq!(program.to_midi(), [0xCF, 5]);
+        let control = CueMessage::ControlChange {
+            channel: 1,
+            control: 7,
+            value: 90,
+        };
+        assert_eq!(control.to_midi(), [0xB0, 7, 90]);
+        assert_eq!(control.get_release(), None);
+    }
+}
@@ -0,0 +1,274 @@
+use anyhow::{Context, bail};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fs;
+#[cfg(feature = "gui")]
+use std::io::Write;
+use std::path::{Component, Path};
+
+/// Playback channel whose volume a cue changes
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CueChannel {
+    #[default]
+    Music,
+    Ambience,
+    SoundEffect,
+}
+
+impl CueChannel {
+    #[cfg(feature = "gui")]
+    pub const ALL: [Self; 3] = [Self::Music, Self::Ambience, Self::SoundEffect];
+
+    /// Knob controlling the channel volume
+    pub const fn get_knob(self) -> u8 {
+        match self {
+            Self::Music => 1,
+            Self::Ambience => 5,
+            Self::SoundEffect => 7,
+        }
+    }
+}
+
+impl std::fmt::Display for CueChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Music => write!(f, "Music"),
+            Self::Ambience => write!(f, "Ambience"),
+            Self::SoundEffect => write!(f, "Sound effect"),
+        }
+    }
+}
+
+/// What a cue does when it fires. Ambiences and sound effects are numbered from 1
+/// in folder order, across all the keyboard banks
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum CueAction {
+    PlayAlbum {
+        album: usize,
+    },
+    StopMusic,
+    StartAmbience {
+        number: usize,
+    },
+    StopAmbience,
+    FireSoundEffect {
+        number: usize,
+    },
+    SetVolume {
+        channel: CueChannel,
+        value: f32,
+    },
+    /// Moves the volume to the value in the given time, the next cues do not wait for it
+    Fade {
+        channel: CueChannel,
+        value: f32,
+        seconds: f32,
+    },
+    Wait {
+        seconds: f32,
+    },
+    RecallScene {
+        number: usize,
+    },
+}
+
+impl CueAction {
+    /// One action of each kind, with default values, offered by the editor
+    #[cfg(feature = "gui")]
+    pub const ALL: [Self; 9] = [
+        Self::PlayAlbum { album: 0 },
+        Self::StopMusic,
+        Self::StartAmbience { number: 1 },
+        Self::StopAmbience,
+        Self::FireSoundEffect { number: 1 },
+        Self::SetVolume {
+            channel: CueChannel::Music,
+            value: 0.5,
+        },
+        Self::Fade {
+            channel: CueChannel::Music,
+            value: 0.,
+            seconds: 3.,
+        },
+        Self::Wait { seconds: 1. },
+        Self::RecallScene { number: 1 },
+    ];
+
+    #[cfg(feature = "gui")]
+    pub const fn get_kind_name(self) -> &'static str {
+        match self {
+            Self::PlayAlbum { .. } => "Play album",
+            Self::StopMusic => "Stop music",
+            Self::StartAmbience { .. } => "Start ambience",
+            Self::StopAmbience => "Stop ambience",
+            Self::FireSoundEffect { .. } => "Fire sound effect",
+            Self::SetVolume { .. } => "Set volume",
+            Self::Fade { .. } => "Fade",
+            Self::Wait { .. } => "Wait",
+            Self::RecallScene { .. } => "Recall scene",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cue {
+    pub name: String,
+    pub action: CueAction,
+    /// Seconds between GO and the action
+    #[serde(default)]
+    pub pre_wait: f32,
+    /// Seconds between the action and the next cue, only used with auto follow
+    #[serde(default)]
+    pub post_wait: f32,
+    /// The next cue fires on its own once this one is done
+    #[serde(default)]
+    pub auto_follow: bool,
+}
+
+impl Default for Cue {
+    fn default() -> Self {
+        Self {
+            name: "New cue".to_string(),
+            action: CueAction::StopMusic,
+            pre_wait: 0.,
+            post_wait: 0.,
+            auto_follow: false,
+        }
+    }
+}
+
+/// Cues of an adventure, fired in order by GO and saved to disk as JSON
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct CueList {
+    pub cues: Vec<Cue>,
+    /// Next cue fired by GO
+    #[serde(skip)]
+    pub playhead: usize,
+    /// File the list is saved to, only the editor writes it
+    #[serde(skip)]
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub path: String,
+    /// Why the file of the adventure did not load, the list stays empty and unsaved until it is fixed
+    #[serde(skip)]
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    pub load_error: Option<String>,
+}
+
+impl CueList {
+    /// Adventures are file names in the cue lists folder, paths could reach outside of it
+    pub fn is_valid_adventure(adventure: &str) -> bool {
+        let mut components = Path::new(adventure).components();
+        !adventure.contains(['/', '\\'])
+            && matches!(components.next(), Some(Component::Normal(_)))
+            && components.next().is_none()
+    }
+
+    /// File holding the cue list of the adventure
+    pub fn get_path(folder: &str, adventure: &str) -> String {
+        Path::new(folder)
+            .join(format!("{adventure}.json"))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Adventures with a cue list in the folder, sorted by name
+    #[cfg(feature = "gui")]
+    pub fn get_adventures(folder: &str) -> Vec<String> {
+        let mut adventures: Vec<String> = fs::read_dir(folder)
+            .map(|entries| {
+                entries
+                    .filter_map(Result::ok)
+                    .map(|entry| entry.path())
+                    .filter(|path| path.extension().is_some_and(|x| x == "json"))
+                    .filter_map(|path| Some(path.file_stem()?.to_str()?.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        adventures.sort();
+        adventures
+    }
+
+    #[cfg(feature = "gui")]
+    pub fn write_to_file(&self) -> anyhow::Result<()> {
+        if let Some(folder) = Path::new(&self.path).parent() {
+            fs::create_dir_all(folder)?;
+        }
+        let serialized = serde_json::to_string_pretty(self)?;
+        let mut file = fs::File::create(&self.path)?;
+        file.write_all(serialized.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            ..serde_json::from_str(&contents)?
+        })
+    }
+
+    /// Loads the list of the adventure, an adventure without a file starts empty
+    pub fn load_adventure(folder: &str, adventure: &str) -> anyhow::Result<Self> {
+        if !Self::is_valid_adventure(adventure) {
+            bail!("Adventure {adventure} is not a valid file name");
+        }
+        let path = Self::get_path(folder, adventure);
+        if !Path::new(&path).exists() {
+            return Ok(Self {
+                path,
+                ..Self::default()
+            });
+        }
+        Self::load_from_file(&path)
+            .with_context(|| format!("Failed to load the cue list of {adventure} from {path}"))
+    }
+
+    /// Empty list used while the file of the adventure does not load, it has no path to be saved to
+    pub fn unloaded(error: &anyhow::Error) -> Self {
+        warn!("{error:#}");
+        Self {
+            load_error: Some(format!("{error:#}")),
+            ..Self::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn adventures_cannot_leave_the_cue_lists_folder() {
+        assert!(CueList::is_valid_adventure("Curse of Strahd"));
+        assert!(CueList::is_valid_adventure("session..2"));
+        for adventure in ["", ".", "..", "../notes", "/etc/passwd", "act/1", "act\\1"] {
+            assert!(!CueList::is_valid_adventure(adventure), "{adventure}");
+        }
+    }
+
+    #[test]
+    fn adventures_load_only_from_a_valid_file() {
+        let folder = crate::get_test_folder("load_adventure");
+        let folder = folder.to_string_lossy();
+
+        let missing = CueList::load_adventure(&folder, "Tomb of Horrors")
+            .expect("An adventure without a file should start empty");
+        assert!(missing.cues.is_empty());
+        assert_eq!(missing.path, CueList::get_path(&folder, "Tomb of Horrors"));
+
+        let path = CueList::get_path(&folder, "Curse of Strahd");
+        fs::write(
+            &path,
+            r#"{"cues": [{"name": "Intro", "action": "stop_music"}"#,
+        )
+        .expect("The cue list should be writable");
+        assert!(CueList::load_adventure(&folder, "Curse of Strahd").is_err());
+        assert!(CueList::load_adventure(&folder, "../Curse of Strahd").is_err());
+
+        let unloaded = CueList::unloaded(&anyhow::anyhow!("broken"));
+        assert!(unloaded.path.is_empty());
+        assert_eq!(unloaded.load_error.as_deref(), Some("broken"));
+    }
+}
@@ -0,0 +1,72 @@
+use crate::backend::keyboard_handler::KEY_PATTERN;
+use crate::comms::command::CommsCommand;
+use serde::{Deserialize, Serialize};
+
+/// Modifiers selecting the pad row, the number row selects the column
+const PAD_ROW_MODIFIERS: [&str; 5] = ["Ctrl", "Alt", "Shift", "Ctrl+Shift", "Alt+Shift"];
+/// Tracker-like layout: the bottom letter row plays the first octave, the top rows the second one
+const KEYBOARD_KEYS: [&str; 25] = [
+    "Z", "S", "X", "D", "C", "V", "G", "B", "H", "N", "J", "M", "Q", "2", "W", "3", "E", "R", "5",
+    "T", "6", "Y", "7", "U", "I",
+];
+const HOTKEY_VELOCITY: u8 = 100;
+
+/// Computer keyboard shortcut, written as modifiers and key joined by '+' (e.g. "Ctrl+1")
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Hotkey {
+    pub shortcut: String,
+    pub command: CommsCommand,
+}
+
+impl Hotkey {
+    fn new(shortcut: &str, command: CommsCommand) -> Self {
+        Self {
+            shortcut: shortcut.to_string(),
+            command,
+        }
+    }
+
+    pub fn get_default_keymap() -> Vec<Self> {
+        let pads = PAD_ROW_MODIFIERS
+            .iter()
+            .enumerate()
+            .flat_map(|(row, modifier)| {
+                (1..=8u8).map(move |column| {
+                    Self::new(
+                        &format!("{modifier}+{column}"),
+                        CommsCommand::PadPressed {
+                            key: row as u8 * 8 + column - 1,
+                        },
+                    )
+                })
+            });
+        let keys = KEYBOARD_KEYS.iter().enumerate().map(|(i, shortcut)| {
+            let key = i as u8 + 1;
+            Self::new(
+                shortcut,
+                if KEY_PATTERN[i % 12] {
+                    CommsCommand::WhiteKeyPressed {
+                        key,
+                        velocity: HOTKEY_VELOCITY,
+                    }
+                } else {
+                    CommsCommand::BlackKeyPressed {
+                        key,
+                        velocity: HOTKEY_VELOCITY,
+                    }
+                },
+            )
+        });
+        let transport = [
+            Self::new("Space", CommsCommand::PausePressed),
+            Self::new("Right", CommsCommand::SkipTrackPressed),
+            Self::new("Escape", CommsCommand::StopAllPressed),
+            Self::new("Up", CommsCommand::PreviousPadPagePressed),
+            Self::new("Down", CommsCommand::NextPadPagePressed),
+            Self::new("Shift+Left", CommsCommand::PreviousKeyboardBankPressed),
+            Self::new("Shift+Right", CommsCommand::NextKeyboardBankPressed),
+            Self::new("Enter", CommsCommand::CueGoPressed),
+        ];
+        pads.chain(keys).chain(transport).collect()
+    }
+}
@@ -0,0 +1,16 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Audio file played by a key, numbered from 1 across the banks like the files of its folder.
+/// It takes the place of the folder file with the same number
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct KeyAssignment {
+    pub number: usize,
+    pub file: String,
+}
+
+impl KeyAssignment {
+    pub fn is_missing(&self) -> bool {
+        !Path::new(&self.file).is_file()
+    }
+}
@@ -0,0 +1,26 @@
+use serde::{Deserialize, Serialize};
+
+/// What the knobs of the controller send when turned
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KnobMode {
+    /// Endless encoders, each message is a step (1..=63 up, 65..=127 down)
+    #[default]
+    Relative,
+    /// Pots with a start and an end, each message is the position (0..=127)
+    Absolute,
+}
+
+impl KnobMode {
+    #[cfg(feature = "gui")]
+    pub const ALL: [Self; 2] = [Self::Relative, Self::Absolute];
+}
+
+impl std::fmt::Display for KnobMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Relative => write!(f, "Relative"),
+            Self::Absolute => write!(f, "Absolute"),
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Steps closer than this are considered part of a fast spin
+const ACCELERATION_WINDOW: Duration = Duration::from_millis(40);
+const MAX_ACCELERATION: f32 = 5.;
+/// A pot this close to the value takes over even if it did not cross it
+const TAKEOVER_THRESHOLD: f32 = 0.02;
+
+#[derive(Debug, Default, Clone)]
+struct KnobHistory {
+    last_step: Option<Instant>,
+    last_position: Option<f32>,
+    taken_over: bool,
+}
+
+/// Remembers how each knob moved, so that relative encoders can accelerate and absolute pots
+/// can softly take over the value they control
+#[derive(Debug, Default, Clone)]
+pub struct KnobTracker {
+    knobs: HashMap<u8, KnobHistory>,
+}
+
+impl KnobTracker {
+    /// Steps of a relative encoder message, multiplied when the knob is spun fast
+    pub fn get_relative_steps(&mut self, knob: u8, value: u8, acceleration: bool) -> f32 {
+        let steps = match value {
+            1..=63 => f32::from(value),
+            65..=127 => f32::from(value) - 128.,
+            _ => 0.,
+        };
+        let history = self.knobs.entry(knob).or_default();
+        let now = Instant::now();
+        let multiplier = match history.last_step {
+            Some(last) if acceleration => {
+                let elapsed = now.duration_since(last).max(Duration::from_millis(1));
+                (ACCELERATION_WINDOW.as_secs_f32() / elapsed.as_secs_f32())
+                    .clamp(1., MAX_ACCELERATION)
+            }
+            _ => 1.,
+        };
+        history.last_step = Some(now);
+        steps * multiplier
+    }
+
+    /// Change to apply to `current` for an absolute pot message.
+    /// The pot is ignored until it reaches or crosses the current value
+    pub fn get_absolute_delta(&mut self, knob: u8, value: u8, current: f32) -> Option<f32> {
+        let position = f32::from(value.min(127)) / 127.;
+        let history = self.knobs.entry(knob).or_default();
+        let crossed = history
+            .last_position
+            .is_some_and(|last| (last - current).signum() != (position - current).signum());
+        history.taken_over =
+            history.taken_over || crossed || (position - current).abs() <= TAKEOVER_THRESHOLD;
+        history.last_position = Some(position);
+        history.taken_over.then_some(position - current)
+    }
+
+    /// The value changed without the knob, it will have to be picked up again
+    pub fn release(&mut self, knob: u8) {
+        if let Some(history) = self.knobs.get_mut(&knob) {
+            history.taken_over = false;
+        }
+    }
+}
@@ -0,0 +1,379 @@
+use crate::states::playlist_data::PlaylistData;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use symphonia::core::formats::FormatOptions;
+use symphonia::core::io::{MediaSourceStream, MediaSourceStreamOptions};
+use symphonia::core::meta::{MetadataOptions, MetadataRevision, StandardTagKey};
+use symphonia::core::probe::Hint;
+
+/// Extensions worth probing, covers, playlists and notes in the folders are skipped
+const AUDIO_EXTENSIONS: [&str; 8] = ["mp3", "flac", "ogg", "oga", "wav", "m4a", "mp4", "aac"];
+
+/// Audio file of a library folder, with what its tags say
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IndexedFile {
+    /// Last change of the file in seconds since the epoch, the tags are read again when it moves
+    pub modified: u64,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub duration_seconds: Option<u64>,
+}
+
+impl IndexedFile {
+    /// Name to show for the file, from its tags if it has a title
+    pub fn get_display_name(&self) -> Option<String> {
+        let title = self.title.as_ref()?;
+        Some(
+            self.artist
+                .as_ref()
+                .map_or_else(|| title.clone(), |artist| format!("{artist} - {title}")),
+        )
+    }
+
+    /// Tags already found are kept, the ones before the audio come first
+    fn fill_tags(&mut self, revision: &MetadataRevision) {
+        for tag in revision.tags() {
+            let field = match tag.std_key {
+                Some(StandardTagKey::TrackTitle) => &mut self.title,
+                Some(StandardTagKey::Artist) => &mut self.artist,
+                Some(StandardTagKey::Album) => &mut self.album,
+                _ => continue,
+            };
+            field.get_or_insert_with(|| tag.value.to_string());
+        }
+    }
+}
+
+/// Audio files Teatro can play in the library folders, kept in a cache file between runs
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct LibraryIndex {
+    pub files: BTreeMap<PathBuf, IndexedFile>,
+    #[serde(skip)]
+    pub path: String,
+    /// Scans reading the folders without holding the index
+    #[serde(skip)]
+    running_scans: usize,
+    /// Paths changed while a scan was running, its results do not know about them
+    #[serde(skip)]
+    changed_during_scan: Vec<PathBuf>,
+}
+
+impl LibraryIndex {
+    pub fn write_to_file(&self) -> anyhow::Result<()> {
+        let serialized = serde_json::to_string(self)?;
+        let mut file = fs::File::create(&self.path)?;
+        file.write_all(serialized.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            ..serde_json::from_str(&contents)?
+        })
+    }
+
+    /// Audio files under the folder and its sub folders, unchanged files keep their entry
+    pub fn scan(&self, root: &Path) -> BTreeMap<PathBuf, IndexedFile> {
+        let mut found = BTreeMap::new();
+        self.scan_into(root, &mut HashSet::new(), &mut found);
+        found
+    }
+
+    /// Linked folders are followed once, a link back to a parent would be scanned over and over
+    fn scan_into(
+        &self,
+        dir: &Path,
+        visited: &mut HashSet<PathBuf>,
+        found: &mut BTreeMap<PathBuf, IndexedFile>,
+    ) {
+        if !fs::canonicalize(dir).is_ok_and(|x| visited.insert(x)) {
+            return;
+        }
+        let Ok(entries) = fs::read_dir(dir) else {
+            return;
+        };
+        for path in entries.filter_map(Result::ok).map(|x| x.path()) {
+            if path.is_dir() {
+                self.scan_into(&path, visited, found);
+            } else if let Some(file) = self.index_file(&path) {
+                found.insert(path, file);
+            }
+        }
+    }
+
+    /// Changes from now on are kept until the scan is applied
+    pub const fn begin_scan(&mut self) {
+        self.running_scans += 1;
+    }
+
+    /// Replaces the entries under the roots with the ones of the scan, then brings
+    /// the paths changed meanwhile up to date again
+    pub fn apply_scan(&mut self, scans: Vec<(PathBuf, BTreeMap<PathBuf, IndexedFile>)>) {
+        for (root, files) in scans {
+            self.replace_folder(&root, files);
+        }
+        self.running_scans = self.running_scans.saturating_sub(1);
+        let changed = if self.running_scans == 0 {
+            std::mem::take(&mut self.changed_during_scan)
+        } else {
+            self.changed_during_scan.clone()
+        };
+        for path in changed {
+            self.update_path(&path);
+        }
+    }
+
+    /// Replaces the entries under the folder with the ones of a new scan
+    pub fn replace_folder(&mut self, root: &Path, files: BTreeMap<PathBuf, IndexedFile>) {
+        self.files.retain(|path, _| !path.starts_with(root));
+        self.files.extend(files);
+    }
+
+    /// Brings a changed path up to date, be it a file, a folder or something removed
+    pub fn update_path(&mut self, path: &Path) {
+        if self.running_scans > 0 && !self.changed_during_scan.iter().any(|x| x == path) {
+            self.changed_during_scan.push(path.to_path_buf());
+        }
+        if path.is_dir() {
+            let files = self.scan(path);
+            self.replace_folder(path, files);
+        } else {
+            let file = self.index_file(path);
+            self.files.retain(|x, _| !x.starts_with(path));
+            if let Some(file) = file {
+                self.files.insert(path.to_path_buf(), file);
+            }
+        }
+    }
+
+    /// Entry of the file, reused if it did not change, none if it is not playable audio
+    fn index_file(&self, path: &Path) -> Option<IndexedFile> {
+        let modified = fs::metadata(path)
+            .and_then(|x| x.modified())
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()?
+            .as_secs();
+        if let Some(known) = self.files.get(path)
+            && known.modified == modified
+        {
+            return Some(known.clone());
+        }
+        read_audio_file(path, modified)
+    }
+
+    /// Titles of the tracks, and the lengths the decoder could not tell
+    pub fn fill_playlist(&self, playlist: &mut PlaylistData) {
+        for track in &mut playlist.tracks {
+            if let Some(file) = self.files.get(Path::new(&track.file_path)) {
+                track.title = file.get_display_name();
+                if track.track_length == 0
+                    && let Some(duration) = file.duration_seconds
+                {
+                    track.track_length = duration;
+                }
+            }
+        }
+    }
+
+    /// Audio files of the folder and its sub folders, sorted
+    pub fn get_folder_files(&self, folder: &str) -> Vec<PathBuf> {
+        let root = get_root(folder);
+        self.files
+            .keys()
+            .filter(|x| x.starts_with(&root))
+            .cloned()
+            .collect()
+    }
+
+    /// Sub folders of the folder holding audio, sorted
+    #[cfg(feature = "gui")]
+    pub fn get_subfolders(&self, folder: &str) -> Vec<PathBuf> {
+        let root = get_root(folder);
+        let mut folders: Vec<PathBuf> = self
+            .files
+            .keys()
+            .filter_map(|x| x.strip_prefix(&root).ok())
+            .filter(|x| x.components().count() > 1)
+            .filter_map(|x| x.components().next())
+            .map(|x| root.join(x))
+            .collect();
+        folders.dedup();
+        folders
+    }
+
+    /// Audio files right inside the folder, sorted
+    pub fn get_files(&self, folder: &str) -> Vec<PathBuf> {
+        let root = get_root(folder);
+        self.files
+            .keys()
+            .filter(|x| x.parent() == Some(root.as_path()))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Folders are indexed by their canonical path, the one reported by the watchdog
+fn get_root(folder: &str) -> PathBuf {
+    fs::canonicalize(folder).unwrap_or_else(|_| PathBuf::from(folder))
+}
+
+/// Tags and duration of the file, none if no decoder can play it
+fn read_audio_file(path: &Path, modified: u64) -> Option<IndexedFile> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    if !AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+        return None;
+    }
+    let source = MediaSourceStream::new(
+        Box::new(fs::File::open(path).ok()?),
+        MediaSourceStreamOptions::default(),
+    );
+    let mut hint = Hint::new();
+    hint.with_extension(&extension);
+    let mut probed = symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .ok()?;
+    let track = probed.format.default_track()?;
+    symphonia::default::get_codecs().get_codec(track.codec_params.codec)?;
+    let duration_seconds = track
+        .codec_params
+        .time_base
+        .zip(track.codec_params.n_frames)
+        .map(|(base, frames)| base.calc_time(frames).seconds);
+
+    let mut file = IndexedFile {
+        modified,
+        title: None,
+        artist: None,
+        album: None,
+        duration_seconds,
+    };
+    if let Some(revision) = probed.metadata.get().as_ref().and_then(|x| x.current()) {
+        file.fill_tags(revision);
+    }
+    if let Some(revision) = probed.format.metadata().current() {
+        file.fill_tags(revision);
+    }
+    Some(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Tenth of a second of silence, 16 bit mono at 8 kHz
+    fn write_wav(path: &Path) {
+        let samples = vec![0u8; 1600];
+        let mut bytes = b"RIFF".to_vec();
+        bytes.extend(&(36 + samples.len() as u32).to_le_bytes());
+        bytes.extend(b"WAVEfmt ");
+        bytes.extend(&16u32.to_le_bytes());
+        bytes.extend(&1u16.to_le_bytes());
+        bytes.extend(&1u16.to_le_bytes());
+        bytes.extend(&8000u32.to_le_bytes());
+        bytes.extend(&16000u32.to_le_bytes());
+        bytes.extend(&2u16.to_le_bytes());
+        bytes.extend(&16u16.to_le_bytes());
+        bytes.extend(b"data");
+        bytes.extend(&(samples.len() as u32).to_le_bytes());
+        bytes.extend(samples);
+        fs::write(path, bytes).expect("The audio file should be writable");
+    }
+
+    #[test]
+    fn new_audio_files_are_indexed_and_covers_are_skipped() {
+        let root = crate::get_test_folder("index_new_files");
+        let mut index = LibraryIndex::default();
+        let (track, cover) = (root.join("01_Intro.wav"), root.join("cover.jpg"));
+        write_wav(&track);
+        fs::write(&cover, b"not audio").expect("The cover should be writable");
+        index.update_path(&track);
+        index.update_path(&cover);
+        assert_eq!(index.files.keys().collect::<Vec<_>>(), [&track]);
+        assert_eq!(index.files[&track].duration_seconds, Some(0));
+
+        let folder = root.join("02_Battle");
+        fs::create_dir(&folder).expect("The folder should be writable");
+        write_wav(&folder.join("drums.wav"));
+        fs::write(folder.join("cover.jpg"), b"not audio").expect("The cover should be writable");
+        index.update_path(&folder);
+        assert_eq!(index.files.len(), 2);
+        assert!(index.files.contains_key(&folder.join("drums.wav")));
+    }
+
+    #[test]
+    fn removed_folders_leave_the_index() {
+        let root = crate::get_test_folder("index_removed_folder");
+        let folder = root.join("01_Intro");
+        fs::create_dir(&folder).expect("The folder should be writable");
+        write_wav(&folder.join("theme.wav"));
+        write_wav(&root.join("tavern.wav"));
+        let mut index = LibraryIndex::default();
+        let files = index.scan(&root);
+        index.replace_folder(&root, files);
+        assert_eq!(index.files.len(), 2);
+
+        fs::remove_dir_all(&folder).expect("The folder should be removable");
+        index.update_path(&folder);
+        assert_eq!(
+            index.files.keys().collect::<Vec<_>>(),
+            [&root.join("tavern.wav")]
+        );
+        let files = index.scan(&root);
+        index.replace_folder(&root, files);
+        assert_eq!(index.files.len(), 1);
+    }
+
+    #[test]
+    fn changes_made_during_a_scan_are_kept() {
+        let root = crate::get_test_folder("index_changed_during_scan");
+        write_wav(&root.join("old.wav"));
+        let mut index = LibraryIndex::default();
+        index.begin_scan();
+        let scan = index.scan(&root);
+
+        let (new, old) = (root.join("new.wav"), root.join("old.wav"));
+        write_wav(&new);
+        index.update_path(&new);
+        fs::remove_file(&old).expect("The file should be removable");
+        index.update_path(&old);
+        index.apply_scan(vec![(root, scan)]);
+        assert_eq!(index.files.keys().collect::<Vec<_>>(), [&new]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn linked_folders_are_scanned_once() {
+        use std::os::unix::fs::symlink;
+        let root = crate::get_test_folder("index_linked_folders");
+        let (music, shared) = (root.join("music"), root.join("shared"));
+        let album = music.join("01_Intro");
+        fs::create_dir_all(&album).expect("The folder should be writable");
+        fs::create_dir(&shared).expect("The folder should be writable");
+        write_wav(&album.join("theme.wav"));
+        write_wav(&shared.join("rain.wav"));
+        symlink(&music, album.join("loop")).expect("The link should be writable");
+        symlink(&shared, music.join("02_Rain")).expect("The link should be writable");
+
+        let found = LibraryIndex::default().scan(&music);
+        assert_eq!(
+            found.keys().collect::<Vec<_>>(),
+            [
+                &album.join("theme.wav"),
+                &music.join("02_Rain").join("rain.wav")
+            ]
+        );
+    }
+}
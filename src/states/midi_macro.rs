@@ -0,0 +1,102 @@
+use crate::comms::command::CommsCommand;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::time::Instant;
+
+/// Something the user did while a macro was recorded
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum MacroAction {
+    /// Note or control change coming from the pads and knobs channel, a value of 0 is a release
+    Pad { control: u8, value: u8 },
+    /// Key of the keyboard channel, a velocity of 0 is a release
+    Keyboard { key: u8, velocity: u8 },
+    /// Command sent from the GUI
+    Command { command: CommsCommand },
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MacroEvent {
+    /// Milliseconds since the recording started
+    pub offset_ms: u64,
+    pub action: MacroAction,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MidiMacro {
+    pub events: Vec<MacroEvent>,
+    /// Multiplies every offset on replay: 1 keeps the original timing, 0.5 plays twice as fast
+    #[serde(default = "default_time_scale")]
+    pub time_scale: f32,
+}
+
+const fn default_time_scale() -> f32 {
+    1.
+}
+
+/// Macros bound to the pads, saved to disk as JSON
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct MacroLibrary {
+    pub macros: BTreeMap<u8, MidiMacro>,
+    /// File the library is saved to
+    #[serde(skip)]
+    pub path: String,
+}
+
+impl MacroLibrary {
+    pub fn write_to_file(&self) -> anyhow::Result<()> {
+        let serialized = serde_json::to_string_pretty(self)?;
+        let mut file = fs::File::create(&self.path)?;
+        file.write_all(serialized.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            ..serde_json::from_str(&contents)?
+        })
+    }
+}
+
+/// Collects the actions performed between two presses of REC ARM
+#[derive(Debug, Default)]
+pub struct MacroRecorder {
+    started: Option<Instant>,
+    pub(crate) events: Vec<MacroEvent>,
+    /// Last recording, waiting for a pad to be bound to
+    pending: Option<MidiMacro>,
+}
+
+impl MacroRecorder {
+    pub fn start(&mut self) {
+        self.started = Some(Instant::now());
+        self.events.clear();
+        self.pending = None;
+    }
+
+    pub fn stop(&mut self) {
+        if self.started.take().is_some() && !self.events.is_empty() {
+            self.pending = Some(MidiMacro {
+                events: std::mem::take(&mut self.events),
+                time_scale: default_time_scale(),
+            });
+        }
+    }
+
+    pub fn record(&mut self, action: MacroAction) {
+        if let Some(started) = self.started {
+            self.events.push(MacroEvent {
+                offset_ms: started.elapsed().as_millis() as u64,
+                action,
+            });
+        }
+    }
+
+    pub const fn take_pending(&mut self) -> Option<MidiMacro> {
+        self.pending.take()
+    }
+}
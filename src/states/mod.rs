@@ -1,10 +1,25 @@
+pub mod app_paths;
 pub mod audio_sinks;
 pub mod button_states;
+pub mod cue_event;
+pub mod cue_list;
 pub mod filter_data;
+pub mod hotkey;
+#[cfg(feature = "gui")]
 pub mod information_data;
+pub mod key_assignment;
+pub mod knob_mode;
+pub mod knob_tracker;
 pub mod knob_value_update;
+pub mod library_index;
+pub mod midi_macro;
 pub mod music_state;
+pub mod pad_assignment;
 pub mod playlist_data;
+pub mod scene;
+pub mod session;
 pub mod settings_data;
 pub mod sound_state;
+pub mod trigger_mode;
+pub mod velocity_curve;
 pub mod visualizer;
@@ -1,5 +1,11 @@
+use crate::comms::command::CommsCommand;
 use crate::states::audio_sinks::AudioSinks;
+use crate::states::cue_event::CueEvent;
+use crate::states::cue_list::CueList;
 use crate::states::filter_data::FilterData;
+use crate::states::knob_tracker::KnobTracker;
+use crate::states::midi_macro::{MacroLibrary, MacroRecorder};
+use crate::states::scene::SceneLibrary;
 use crate::states::visualizer::RuntimeData;
 use flume::Sender;
 use std::sync::{Arc, Mutex};
@@ -12,4 +18,14 @@ pub struct MusicState {
     pub data: Arc<Mutex<RuntimeData>>,
     pub audio_sinks: Arc<Mutex<AudioSinks>>,
     pub tx_data: Sender<RuntimeData>,
+    pub knob_tracker: Arc<Mutex<KnobTracker>>,
+    pub macro_recorder: Arc<Mutex<MacroRecorder>>,
+    pub macro_library: Arc<Mutex<MacroLibrary>>,
+    pub scene_library: Arc<Mutex<SceneLibrary>>,
+    pub cue_list: Arc<Mutex<CueList>>,
+    /// Pads whose macro has to be replayed
+    pub tx_macro: Sender<u8>,
+    pub tx_cue: Sender<CueEvent>,
+    /// Tells the watchdog to follow the folders and config of the settings in use
+    pub tx_watchdog: Sender<CommsCommand>,
 }
@@ -0,0 +1,35 @@
+use crate::os_explorer::explorer::split_number_prefix;
+use crate::states::visualizer::PADS_PER_PAGE;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Pads from this one on are refused, 25 pages are far more than a library fills
+pub const MAX_PAD_COUNT: usize = 25 * PADS_PER_PAGE;
+
+/// Folder played by a pad, pads are counted from 0 across the pages
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct PadAssignment {
+    pub pad: usize,
+    pub folder: String,
+}
+
+impl PadAssignment {
+    /// Name of the folder, without the `NN_` prefix
+    pub fn get_label(&self) -> String {
+        let name = Path::new(&self.folder)
+            .file_name()
+            .and_then(|x| x.to_str())
+            .unwrap_or(&self.folder);
+        split_number_prefix(name)
+            .map_or(name, |(_, x)| x)
+            .to_string()
+    }
+
+    pub const fn is_pad_valid(&self) -> bool {
+        self.pad < MAX_PAD_COUNT
+    }
+
+    pub fn is_missing(&self) -> bool {
+        !Path::new(&self.folder).is_dir()
+    }
+}
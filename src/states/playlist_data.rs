@@ -1,7 +1,8 @@
 use bon::bon;
+use serde::Serialize;
 use std::time::Duration;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct PlaylistData {
     pub tracks: Vec<Track>,
     pub current_track: u64,
@@ -24,9 +25,11 @@ impl PlaylistData {
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Track {
     pub file_path: String,
+    /// From the tags of the file, if it has a title
+    pub title: Option<String>,
     pub track_length: u64,
     pub elapsed_seconds: u64,
 }
@@ -36,11 +39,13 @@ impl Track {
     #[builder]
     pub fn new(
         file_path: &str,
+        title: Option<String>,
         track_length: Option<Option<Duration>>,
         current_position: Option<u64>,
     ) -> Self {
         Self {
             file_path: file_path.to_string(),
+            title,
             track_length: track_length.unwrap_or_default().map_or(0, |x| x.as_secs()),
             elapsed_seconds: current_position.unwrap_or(0),
         }
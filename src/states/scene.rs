@@ -0,0 +1,180 @@
+use crate::states::button_states::ToggleStates;
+use crate::states::filter_data::FilterData;
+use biquad::Type;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+
+/// Filter types the knobs can set, the only ones a scene has to restore
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterKind {
+    #[default]
+    AllPass,
+    LowPass,
+    HighPass,
+    SinglePoleLowPassApprox,
+}
+
+impl From<Type<f32>> for FilterKind {
+    fn from(value: Type<f32>) -> Self {
+        match value {
+            Type::LowPass => Self::LowPass,
+            Type::HighPass => Self::HighPass,
+            Type::SinglePoleLowPassApprox => Self::SinglePoleLowPassApprox,
+            _ => Self::AllPass,
+        }
+    }
+}
+
+impl From<FilterKind> for Type<f32> {
+    fn from(value: FilterKind) -> Self {
+        match value {
+            FilterKind::AllPass => Self::AllPass,
+            FilterKind::LowPass => Self::LowPass,
+            FilterKind::HighPass => Self::HighPass,
+            FilterKind::SinglePoleLowPassApprox => Self::SinglePoleLowPassApprox,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct SceneFilter {
+    pub percentage: f32,
+    pub kind: FilterKind,
+}
+
+impl From<&FilterData> for SceneFilter {
+    fn from(value: &FilterData) -> Self {
+        Self {
+            percentage: value.previous_filter_percentage,
+            kind: value.filter_type.into(),
+        }
+    }
+}
+
+/// Ambience key playing when the scene was saved, with the keyboard bank it belongs to
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SceneAmbience {
+    pub bank: usize,
+    pub key: u8,
+}
+
+/// Full mix state saved under a name
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Scene {
+    pub name: String,
+    /// Pad recalling the scene while PAN is toggled
+    #[serde(default)]
+    pub pad: Option<u8>,
+    /// Seconds taken to move knobs and filters to the scene values, 0 jumps straight to them
+    #[serde(default)]
+    pub morph_seconds: f32,
+    pub album: Option<usize>,
+    /// File of the track that was playing, the album restarts from it
+    pub track: Option<String>,
+    pub ambience: Option<SceneAmbience>,
+    pub knob_values: BTreeMap<u8, f32>,
+    pub music_filter: SceneFilter,
+    pub ambience_filter: SceneFilter,
+    pub sound_effect_filter: SceneFilter,
+    /// Names of the toggled playback buttons, see `ToggleStates::SCENE_STATES`
+    pub toggles: Vec<String>,
+}
+
+impl Scene {
+    pub fn get_toggle_states(&self) -> ToggleStates {
+        ToggleStates::from_names(&self.toggles) & ToggleStates::SCENE_STATES
+    }
+
+    pub fn set_toggle_states(&mut self, states: ToggleStates) {
+        self.toggles = (states & ToggleStates::SCENE_STATES).get_names();
+    }
+}
+
+/// Scenes listed in the GUI, saved to disk as JSON
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct SceneLibrary {
+    pub scenes: Vec<Scene>,
+    /// File the library is saved to
+    #[serde(skip)]
+    pub path: String,
+}
+
+impl SceneLibrary {
+    pub fn write_to_file(&self) -> anyhow::Result<()> {
+        let serialized = serde_json::to_string_pretty(self)?;
+        let mut file = fs::File::create(&self.path)?;
+        file.write_all(serialized.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            ..serde_json::from_str(&contents)?
+        })
+    }
+
+    pub fn get_scene_on_pad(&self, pad: u8) -> Option<usize> {
+        self.scenes.iter().position(|x| x.pad == Some(pad))
+    }
+
+    /// Adds a captured scene, a scene already bound to the same pad is replaced but keeps its name and morph
+    pub fn insert(&mut self, mut scene: Scene) -> usize {
+        if let Some(index) = scene.pad.and_then(|pad| self.get_scene_on_pad(pad)) {
+            let old = &self.scenes[index];
+            scene.name.clone_from(&old.name);
+            scene.morph_seconds = old.morph_seconds;
+            self.scenes[index] = scene;
+            index
+        } else {
+            self.scenes.push(scene);
+            self.scenes.len() - 1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get_scene(name: &str, pad: Option<u8>, morph_seconds: f32, album: usize) -> Scene {
+        let filter = SceneFilter {
+            percentage: 1.,
+            kind: FilterKind::AllPass,
+        };
+        Scene {
+            name: name.to_string(),
+            pad,
+            morph_seconds,
+            album: Some(album),
+            track: None,
+            ambience: None,
+            knob_values: BTreeMap::new(),
+            music_filter: filter,
+            ambience_filter: filter,
+            sound_effect_filter: filter,
+            toggles: vec![],
+        }
+    }
+
+    #[test]
+    fn captures_on_a_bound_pad_keep_the_name_and_morph() {
+        let mut library = SceneLibrary::default();
+        assert_eq!(library.insert(get_scene("Tavern", Some(3), 4., 1)), 0);
+        assert_eq!(library.insert(get_scene("Forest", None, 0., 2)), 1);
+        assert_eq!(library.insert(get_scene("Scene 3", Some(3), 0., 5)), 0);
+
+        assert_eq!(library.scenes.len(), 2);
+        let replaced = &library.scenes[0];
+        assert_eq!(replaced.name, "Tavern");
+        assert!((replaced.morph_seconds - 4.).abs() < f32::EPSILON);
+        assert_eq!(replaced.album, Some(5));
+        assert_eq!(library.get_scene_on_pad(3), Some(0));
+
+        assert_eq!(library.insert(get_scene("Cave", None, 0., 6)), 2);
+    }
+}
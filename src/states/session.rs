@@ -0,0 +1,52 @@
+use crate::states::button_states::ToggleStates;
+use crate::states::scene::SceneFilter;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+
+/// Music that was playing when the session was saved, offered for resume on startup
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ResumeOffer {
+    pub album: usize,
+    pub track: String,
+    pub elapsed_seconds: u64,
+}
+
+/// Runtime state saved periodically and on exit, restored on startup
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Session {
+    pub knob_values: BTreeMap<u8, f32>,
+    pub music_filter: SceneFilter,
+    pub ambience_filter: SceneFilter,
+    pub sound_effect_filter: SceneFilter,
+    /// Names of the toggled buttons, see `ToggleStates::SESSION_STATES`
+    pub toggles: Vec<String>,
+    pub last_pad_pressed: Option<usize>,
+    pub pad_page: usize,
+    pub keyboard_bank: usize,
+    pub playback: Option<ResumeOffer>,
+}
+
+impl Session {
+    pub fn get_toggle_states(&self) -> ToggleStates {
+        ToggleStates::from_names(&self.toggles) & ToggleStates::SESSION_STATES
+    }
+
+    /// Written next to the session file and renamed over it, a crash while saving
+    /// leaves the previous session whole
+    pub fn write_to_file(&self, path: &str) -> anyhow::Result<()> {
+        let serialized = serde_json::to_string_pretty(self)?;
+        let temp_path = format!("{path}.tmp");
+        let mut file = fs::File::create(&temp_path)?;
+        file.write_all(serialized.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp_path, path)?;
+        Ok(())
+    }
+
+    pub fn load_from_file(path: &str) -> anyhow::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&contents)?)
+    }
+}
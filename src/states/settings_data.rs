@@ -1,33 +1,254 @@
-use anyhow::bail;
-use log::debug;
+use crate::os_explorer::explorer::get_numbered_folders;
+use crate::states::cue_event::CueMapping;
+use crate::states::cue_list::CueList;
+use crate::states::hotkey::Hotkey;
+use crate::states::key_assignment::KeyAssignment;
+use crate::states::knob_mode::KnobMode;
+use crate::states::pad_assignment::{MAX_PAD_COUNT, PadAssignment};
+use crate::states::velocity_curve::VelocityCurve;
+use anyhow::{Context, anyhow, bail};
+use log::{debug, info, warn};
 use serde::{Deserialize, Serialize};
 use std::fs;
+#[cfg(any(feature = "remote", feature = "osc"))]
+use std::net::ToSocketAddrs;
+use std::path::Path;
+
+/// Version written by this build, older configs are migrated when loaded
+pub const CONFIG_VERSION: u32 = 3;
+
+/// Brings a config table to the next version
+type Migration = fn(&mut toml::Table);
+
+/// The step at index i upgrades a config of version i + 1
+const MIGRATIONS: [Migration; 2] = [migrate_unversioned, migrate_pad_prefixes];
+
+/// Configs written before the version key only had the three folders, all required
+fn migrate_unversioned(table: &mut toml::Table) {
+    let defaults = SettingsData::default();
+    for (key, folder) in [
+        ("music_folder", defaults.music_folder),
+        ("ambience_folder", defaults.ambience_folder),
+        ("sound_effect_folder", defaults.sound_effect_folder),
+    ] {
+        table
+            .entry(key)
+            .or_insert_with(|| toml::Value::String(folder));
+    }
+}
+
+/// Pads used to play the sorted sub folders of the music folder, labelled by their `NN_` prefix.
+/// The prefixed folders keep their pad, the others have to be assigned again
+fn migrate_pad_prefixes(table: &mut toml::Table) {
+    let mut settings = SettingsData::default();
+    if let Some(folder) = table.get("music_folder").and_then(|x| x.as_str()) {
+        folder.clone_into(&mut settings.music_folder);
+    }
+    settings.fill_pad_assignments();
+    match toml::Value::try_from(settings.pad_assignments) {
+        Ok(assignments) => {
+            table.insert("pad_assignments".to_string(), assignments);
+        }
+        Err(e) => warn!("Failed to assign the pads from the folder names: {e}"),
+    }
+}
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SettingsData {
+    #[serde(default)]
+    pub(crate) version: u32,
     pub(crate) music_folder: String,
     pub(crate) ambience_folder: String,
     pub(crate) sound_effect_folder: String,
+    #[serde(default)]
+    pub(crate) velocity_curve: VelocityCurve,
+    #[serde(default)]
+    pub(crate) knob_mode: KnobMode,
+    #[serde(default)]
+    pub(crate) knob_acceleration: bool,
+    #[serde(default)]
+    pub(crate) virtual_midi_ports: bool,
+    /// Output port (or part of its name) receiving the cues, empty to disable them
+    #[serde(default)]
+    pub(crate) cue_output_port: String,
+    #[serde(default)]
+    pub(crate) cue_mappings: Vec<CueMapping>,
+    #[serde(default = "Hotkey::get_default_keymap")]
+    pub(crate) keymap: Vec<Hotkey>,
+    /// Where the remote control api listens, only used with the remote feature
+    #[serde(default = "get_default_remote_address")]
+    pub(crate) remote_address: String,
+    /// Required by the remote control api when not empty
+    #[serde(default)]
+    pub(crate) remote_token: String,
+    /// Where the OSC server listens, only used with the osc feature
+    #[serde(default = "get_default_osc_address")]
+    pub(crate) osc_address: String,
+    /// Adventure whose cue list is loaded
+    #[serde(default = "get_default_adventure")]
+    pub(crate) adventure: String,
+    /// Folder played by each pad, sorted by pad
+    #[serde(default)]
+    pub(crate) pad_assignments: Vec<PadAssignment>,
+    /// Files played by the black keys instead of the ones of the ambience folder
+    #[serde(default)]
+    pub(crate) ambience_assignments: Vec<KeyAssignment>,
+    /// Files played by the white keys instead of the ones of the sound effect folder
+    #[serde(default)]
+    pub(crate) sound_effect_assignments: Vec<KeyAssignment>,
+}
+
+fn get_default_remote_address() -> String {
+    "127.0.0.1:7878".to_string()
+}
+
+fn get_default_osc_address() -> String {
+    "127.0.0.1:9000".to_string()
+}
+
+fn get_default_adventure() -> String {
+    "default".to_string()
 }
 
 impl Default for SettingsData {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             music_folder: "music".to_string(),
             ambience_folder: "ambience".to_string(),
             sound_effect_folder: "sound".to_string(),
+            velocity_curve: VelocityCurve::default(),
+            knob_mode: KnobMode::default(),
+            knob_acceleration: false,
+            virtual_midi_ports: false,
+            cue_output_port: String::new(),
+            cue_mappings: vec![],
+            keymap: Hotkey::get_default_keymap(),
+            remote_address: get_default_remote_address(),
+            remote_token: String::new(),
+            osc_address: get_default_osc_address(),
+            adventure: get_default_adventure(),
+            pad_assignments: vec![],
+            ambience_assignments: vec![],
+            sound_effect_assignments: vec![],
         }
     }
 }
 
 impl SettingsData {
     pub fn copy_data(&mut self, new_data: &Self) {
+        self.version = new_data.version;
         self.music_folder.clone_from(&new_data.music_folder);
         self.ambience_folder.clone_from(&new_data.ambience_folder);
         self.sound_effect_folder
             .clone_from(&new_data.sound_effect_folder);
+        self.velocity_curve = new_data.velocity_curve;
+        self.knob_mode = new_data.knob_mode;
+        self.knob_acceleration = new_data.knob_acceleration;
+        self.virtual_midi_ports = new_data.virtual_midi_ports;
+        self.cue_output_port.clone_from(&new_data.cue_output_port);
+        self.cue_mappings.clone_from(&new_data.cue_mappings);
+        self.keymap.clone_from(&new_data.keymap);
+        self.remote_address.clone_from(&new_data.remote_address);
+        self.remote_token.clone_from(&new_data.remote_token);
+        self.osc_address.clone_from(&new_data.osc_address);
+        self.adventure.clone_from(&new_data.adventure);
+        self.pad_assignments.clone_from(&new_data.pad_assignments);
+        self.ambience_assignments
+            .clone_from(&new_data.ambience_assignments);
+        self.sound_effect_assignments
+            .clone_from(&new_data.sound_effect_assignments);
+    }
+
+    /// Folder assigned to the pad
+    pub fn get_pad_folder(&self, pad: usize) -> Option<&str> {
+        self.pad_assignments
+            .iter()
+            .find(|x| x.pad == pad)
+            .map(|x| x.folder.as_str())
+    }
+
+    /// Gives the pad to the folder, or clears it
+    #[cfg(feature = "gui")]
+    pub fn assign_pad(&mut self, pad: usize, folder: Option<String>) {
+        self.pad_assignments.retain(|x| x.pad != pad);
+        if let Some(folder) = folder {
+            self.pad_assignments.push(PadAssignment { pad, folder });
+            self.pad_assignments.sort_by_key(|x| x.pad);
+        }
+    }
+
+    /// Gives the numbered ambience or sound effect to the file, or gives it back to the folder
+    #[cfg(feature = "gui")]
+    pub fn assign_key(&mut self, is_ambience: bool, number: usize, file: Option<String>) {
+        let assignments = if is_ambience {
+            &mut self.ambience_assignments
+        } else {
+            &mut self.sound_effect_assignments
+        };
+        assignments.retain(|x| x.number != number);
+        if let Some(file) = file {
+            assignments.push(KeyAssignment { number, file });
+            assignments.sort_by_key(|x| x.number);
+        }
+    }
+
+    /// Assigns the `NN_Name` sub folders of the music folder to pad NN, the pads and folders
+    /// already assigned are left as they are. Returns how many were assigned
+    pub fn fill_pad_assignments(&mut self) -> usize {
+        let folders = get_numbered_folders(&self.music_folder).unwrap_or_default();
+        let before = self.pad_assignments.len();
+        for (pad, folder) in folders.range(..MAX_PAD_COUNT) {
+            let (pad, folder) = (*pad, folder.display().to_string());
+            if self.get_pad_folder(pad).is_none()
+                && !self.pad_assignments.iter().any(|x| x.folder == folder)
+            {
+                self.pad_assignments.push(PadAssignment { pad, folder });
+            }
+        }
+        self.pad_assignments.sort_by_key(|x| x.pad);
+        self.pad_assignments.len() - before
+    }
+
+    /// Folders to index: the music, ambience and sound effect libraries and the pad folders
+    /// outside of them, each listed once
+    pub fn get_library_folders(&self) -> Vec<String> {
+        let mut folders = vec![
+            self.music_folder.clone(),
+            self.ambience_folder.clone(),
+            self.sound_effect_folder.clone(),
+        ];
+        for assignment in &self.pad_assignments {
+            if !folders
+                .iter()
+                .any(|x| Path::new(&assignment.folder).starts_with(x))
+            {
+                folders.push(assignment.folder.clone());
+            }
+        }
+        folders.sort();
+        folders.dedup();
+        folders
+    }
+
+    /// Settings of a newer version of Teatro, saving them would lose what this version ignores
+    pub const fn is_read_only(&self) -> bool {
+        self.version > CONFIG_VERSION
+    }
+
+    /// The user accepted to replace the newer config with one of this version
+    #[cfg(feature = "gui")]
+    pub const fn allow_overwrite(&mut self) {
+        self.version = CONFIG_VERSION;
     }
+
     pub fn write_to_config(&self, config_path: &str) -> anyhow::Result<()> {
+        if self.is_read_only() {
+            bail!(
+                "{config_path} was written by version {} of the config, it is not overwritten",
+                self.version
+            );
+        }
         let toml_string = toml::to_string(self)?;
         if let Err(e) = fs::write(config_path, &toml_string) {
             bail!("Failed to write settings file: {e}");
@@ -36,9 +257,287 @@ impl SettingsData {
         Ok(())
     }
 
-    pub fn load_from_config(path: &str) -> anyhow::Result<Self> {
+    /// Reads the config, migrating it from older versions. Along with the settings come the
+    /// problems to tell the user about: unknown keys and invalid values
+    pub fn load_from_config(path: &str) -> anyhow::Result<(Self, Vec<String>)> {
         let contents = fs::read_to_string(path)?;
-        let config: Self = toml::from_str(&contents)?;
-        Ok(config)
+        let mut table: toml::Table =
+            toml::from_str(&contents).map_err(|e| anyhow!("{path} is not valid TOML: {e}"))?;
+        let mut issues = vec![];
+        let version = match table.get("version") {
+            None => 1,
+            Some(value) => value
+                .as_integer()
+                .and_then(|x| u32::try_from(x).ok())
+                .with_context(|| format!("The version of {path} is not a positive number"))?,
+        };
+        if version > CONFIG_VERSION {
+            issues.push(format!(
+                "{path} was written by a newer version of Teatro, its new settings are ignored \
+                 and it is not saved unless you allow it"
+            ));
+        } else if version < CONFIG_VERSION {
+            for migrate in MIGRATIONS.iter().skip(version.saturating_sub(1) as usize) {
+                migrate(&mut table);
+            }
+            info!("Migrated {path} from version {version} to {CONFIG_VERSION}");
+            table.insert("version".to_string(), CONFIG_VERSION.into());
+        }
+
+        let known = toml::Table::try_from(Self::default())?;
+        issues.extend(
+            table
+                .keys()
+                .filter(|key| !known.contains_key(*key))
+                .map(|key| format!("Unknown setting {key} in {path}, it is ignored")),
+        );
+        let settings: Self = table
+            .try_into()
+            .map_err(|e| anyhow!("{path} has an invalid setting: {e}"))?;
+        issues.extend(settings.validate());
+        Ok((settings, issues))
+    }
+
+    /// Settings of `theirs`, except the values changed from `base` to `edited`
+    #[cfg(feature = "gui")]
+    pub fn merge_edits(base: &Self, edited: &Self, theirs: &Self) -> anyhow::Result<Self> {
+        let base = toml::Table::try_from(base)?;
+        let mut merged = toml::Table::try_from(theirs)?;
+        for (key, value) in toml::Table::try_from(edited)? {
+            if base.get(&key) != Some(&value) {
+                merged.insert(key, value);
+            }
+        }
+        Ok(merged.try_into()?)
+    }
+
+    /// Values that do not stop the settings from loading, but that the user should fix
+    pub fn validate(&self) -> Vec<String> {
+        let mut issues: Vec<String> = [
+            ("Music", &self.music_folder),
+            ("Ambience", &self.ambience_folder),
+            ("Sound effect", &self.sound_effect_folder),
+        ]
+        .into_iter()
+        .filter(|(_, folder)| !Path::new(folder).is_dir())
+        .map(|(name, folder)| format!("{name} folder {folder} does not exist"))
+        .collect();
+        #[cfg(feature = "remote")]
+        if self.remote_address.to_socket_addrs().is_err() {
+            issues.push(format!(
+                "Remote address {} is not valid, expected host:port",
+                self.remote_address
+            ));
+        }
+        #[cfg(feature = "osc")]
+        if self.osc_address.to_socket_addrs().is_err() {
+            issues.push(format!(
+                "OSC address {} is not valid, expected host:port",
+                self.osc_address
+            ));
+        }
+        issues.extend(self.validate_pad_assignments());
+        for (name, assignments) in [
+            ("ambience", &self.ambience_assignments),
+            ("sound effect", &self.sound_effect_assignments),
+        ] {
+            for assignment in assignments.iter().filter(|x| x.is_missing()) {
+                issues.push(format!(
+                    "File {} assigned to {name} {} does not exist",
+                    assignment.file, assignment.number
+                ));
+            }
+        }
+        if !CueList::is_valid_adventure(&self.adventure) {
+            issues.push(format!(
+                "Adventure {} is not a valid file name",
+                self.adventure
+            ));
+        }
+        issues
+    }
+
+    /// Pads sharing a number, folders gone and pads left without a folder between the others
+    fn validate_pad_assignments(&self) -> Vec<String> {
+        let mut issues = vec![];
+        for assignment in self.pad_assignments.iter().filter(|x| !x.is_pad_valid()) {
+            issues.push(format!(
+                "Pad {} of folder {} is past the last pad {}, it is ignored",
+                assignment.pad,
+                assignment.folder,
+                MAX_PAD_COUNT - 1
+            ));
+        }
+        let mut pads: Vec<usize> = self
+            .pad_assignments
+            .iter()
+            .filter(|x| x.is_pad_valid())
+            .map(|x| x.pad)
+            .collect();
+        pads.sort_unstable();
+        for pair in pads.windows(2).filter(|x| x[0] == x[1]) {
+            issues.push(format!(
+                "Pad {} has more than one folder, only the first is played",
+                pair[0]
+            ));
+        }
+        pads.dedup();
+        for assignment in self.pad_assignments.iter().filter(|x| x.is_missing()) {
+            issues.push(format!(
+                "Folder {} assigned to pad {} does not exist",
+                assignment.folder, assignment.pad
+            ));
+        }
+        let unassigned: Vec<String> = (0..pads.last().copied().unwrap_or_default())
+            .filter(|x| pads.binary_search(x).is_err())
+            .map(|x| x.to_string())
+            .collect();
+        if !unassigned.is_empty() {
+            issues.push(format!(
+                "No folder is assigned to pad {}",
+                unassigned.join(", ")
+            ));
+        }
+        issues
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_config(folder: &Path, contents: &str) -> String {
+        let path = folder.join("config.toml");
+        fs::write(&path, contents).expect("The config should be writable");
+        path.display().to_string()
+    }
+
+    #[test]
+    fn unversioned_configs_are_migrated() {
+        let folder = crate::get_test_folder("unversioned_config");
+        let path = write_config(&folder, "music_folder = \"songs\"\n");
+        let (settings, issues) =
+            SettingsData::load_from_config(&path).expect("The config should load");
+        assert_eq!(settings.version, CONFIG_VERSION);
+        assert_eq!(settings.music_folder, "songs");
+        assert_eq!(settings.ambience_folder, "ambience");
+        assert_eq!(settings.sound_effect_folder, "sound");
+        assert!(settings.pad_assignments.is_empty());
+        assert!(!issues.iter().any(|x| x.contains("Unknown setting")));
+    }
+
+    #[test]
+    fn prefixed_folders_keep_their_pad_from_version_2() {
+        let folder = crate::get_test_folder("version_2_config");
+        let music = folder.join("music");
+        for name in ["01_Intro", "03_Battle", "Tavern"] {
+            fs::create_dir_all(music.join(name)).expect("The folder should be writable");
+        }
+        let path = write_config(
+            &folder,
+            &format!(
+                "version = 2\nmusic_folder = \"{}\"\nambience_folder = \"a\"\n\
+                 sound_effect_folder = \"s\"\n",
+                music.display()
+            ),
+        );
+        let (settings, _) = SettingsData::load_from_config(&path).expect("The config should load");
+        assert_eq!(settings.version, CONFIG_VERSION);
+        assert_eq!(
+            settings.pad_assignments,
+            [
+                PadAssignment {
+                    pad: 1,
+                    folder: music.join("01_Intro").display().to_string(),
+                },
+                PadAssignment {
+                    pad: 3,
+                    folder: music.join("03_Battle").display().to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn newer_configs_are_not_overwritten() {
+        let folder = crate::get_test_folder("newer_config");
+        let contents = format!(
+            "version = {}\nmusic_folder = \"m\"\nambience_folder = \"a\"\n\
+             sound_effect_folder = \"s\"\nlighting = true\n",
+            CONFIG_VERSION + 1
+        );
+        let path = write_config(&folder, &contents);
+        let (settings, issues) =
+            SettingsData::load_from_config(&path).expect("The config should load");
+        assert_eq!(settings.version, CONFIG_VERSION + 1);
+        assert!(settings.is_read_only());
+        assert!(issues.iter().any(|x| x.contains("newer version")));
+        assert!(settings.write_to_config(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).ok(), Some(contents));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let folder = crate::get_test_folder("unknown_key_config");
+        let path = write_config(
+            &folder,
+            &format!(
+                "version = {CONFIG_VERSION}\nmusic_folder = \"m\"\nambience_folder = \"a\"\n\
+                 sound_effect_folder = \"s\"\nlighting = true\n"
+            ),
+        );
+        let (settings, issues) =
+            SettingsData::load_from_config(&path).expect("The config should load");
+        assert!(!settings.is_read_only());
+        assert!(
+            issues
+                .iter()
+                .any(|x| x.starts_with("Unknown setting lighting"))
+        );
+    }
+
+    #[test]
+    fn numbered_folders_fill_the_free_pads() {
+        let music = crate::get_test_folder("fill_pad_assignments");
+        for name in [
+            "01_Intro",
+            "04_Battle",
+            "Tavern",
+            "07_Chase",
+            "1000_Far",
+            "9_Kept",
+        ] {
+            fs::create_dir(music.join(name)).expect("The folder should be writable");
+        }
+        let mut settings = SettingsData {
+            music_folder: music.display().to_string(),
+            pad_assignments: vec![
+                PadAssignment {
+                    pad: 7,
+                    folder: "elsewhere".to_string(),
+                },
+                PadAssignment {
+                    pad: 2,
+                    folder: music.join("9_Kept").display().to_string(),
+                },
+            ],
+            ..SettingsData::default()
+        };
+        assert_eq!(settings.fill_pad_assignments(), 2);
+        let pads: Vec<(usize, String)> = settings
+            .pad_assignments
+            .iter()
+            .map(|x| (x.pad, x.get_label()))
+            .collect();
+        assert_eq!(
+            pads,
+            [
+                (1, "Intro".to_string()),
+                (2, "Kept".to_string()),
+                (4, "Battle".to_string()),
+                (7, "elsewhere".to_string()),
+            ]
+        );
+        assert_eq!(settings.fill_pad_assignments(), 0);
     }
 }
@@ -1,5 +1,8 @@
 use crate::states::audio_sinks::AudioSinks;
+use crate::states::cue_event::CueEvent;
 use crate::states::filter_data::FilterData;
+use crate::states::midi_macro::MacroRecorder;
+use crate::states::music_state::MusicState;
 use crate::states::visualizer::RuntimeData;
 use flume::Sender;
 use std::sync::{Arc, Mutex};
@@ -11,4 +14,21 @@ pub struct SoundState {
     pub ambience_filter: Arc<Mutex<FilterData>>,
     pub sound_effect_filter: Arc<Mutex<FilterData>>,
     pub tx_data: Sender<RuntimeData>,
+    pub macro_recorder: Arc<Mutex<MacroRecorder>>,
+    pub tx_cue: Sender<CueEvent>,
+}
+
+/// The music state holds everything the keyboard needs, used when the pads drive the keyboard channel
+impl From<&MusicState> for SoundState {
+    fn from(value: &MusicState) -> Self {
+        Self {
+            data: value.data.clone(),
+            audio_sinks: value.audio_sinks.clone(),
+            ambience_filter: value.ambience_filter.clone(),
+            sound_effect_filter: value.sound_effect_filter.clone(),
+            tx_data: value.tx_data.clone(),
+            macro_recorder: value.macro_recorder.clone(),
+            tx_cue: value.tx_cue.clone(),
+        }
+    }
 }
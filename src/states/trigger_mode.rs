@@ -0,0 +1,64 @@
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Sidecar file, placed in a sound folder, that assigns a trigger mode to its files.
+/// ```toml
+/// "flamethrower.ogg" = "gate"
+/// "crowd_murmur.ogg" = "toggle"
+/// ```
+pub const TRIGGER_MODES_FILE: &str = "trigger_modes.toml";
+
+/// How a key plays the file assigned to it
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TriggerMode {
+    /// The whole file is played, releasing the key does nothing
+    #[default]
+    OneShot,
+    /// The file loops while the key is held and fades out on release
+    Gate,
+    /// The file loops from one press to the next
+    Toggle,
+}
+
+impl TriggerMode {
+    fn load_from_folder(folder: &Path) -> HashMap<String, Self> {
+        let path = folder.join(TRIGGER_MODES_FILE);
+        if !path.is_file() {
+            return HashMap::new();
+        }
+        fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(toml::from_str(&contents)?))
+            .unwrap_or_else(|e| {
+                warn!("Could not read trigger modes in {}: {e}", path.display());
+                HashMap::new()
+            })
+    }
+
+    /// Modes of the files that are not one-shot, the sidecar of each folder is read once
+    pub fn load_for_files<'a>(files: impl IntoIterator<Item = &'a Path>) -> HashMap<PathBuf, Self> {
+        let mut folders: HashMap<&Path, HashMap<String, Self>> = HashMap::new();
+        files
+            .into_iter()
+            .filter_map(|file| {
+                let folder = file.parent()?;
+                let name = file.file_name()?.to_str()?;
+                let modes = folders
+                    .entry(folder)
+                    .or_insert_with(|| Self::load_from_folder(folder));
+                Some((file.to_path_buf(), *modes.get(name)?))
+            })
+            .collect()
+    }
+}
+
+/// Key whose file is currently playing on a channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SoundingKey {
+    pub key: u8,
+    pub mode: TriggerMode,
+}
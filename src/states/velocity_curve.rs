@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+
+/// How the velocity of a key press is turned into the gain of the sound it triggers
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VelocityCurve {
+    /// Gain grows with the velocity
+    #[default]
+    Linear,
+    /// Soft presses are already loud
+    Soft,
+    /// Only hard presses are loud
+    Hard,
+    /// Velocity is ignored, every press plays at full gain
+    Fixed,
+}
+
+impl VelocityCurve {
+    #[cfg(feature = "gui")]
+    pub const ALL: [Self; 4] = [Self::Linear, Self::Soft, Self::Hard, Self::Fixed];
+
+    pub fn gain(self, velocity: u8) -> f32 {
+        let v = f32::from(velocity.min(127)) / 127.;
+        match self {
+            Self::Linear => v,
+            Self::Soft => v.sqrt(),
+            Self::Hard => v * v,
+            Self::Fixed => 1.,
+        }
+    }
+}
+
+impl std::fmt::Display for VelocityCurve {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Linear => write!(f, "Linear"),
+            Self::Soft => write!(f, "Soft"),
+            Self::Hard => write!(f, "Hard"),
+            Self::Fixed => write!(f, "Fixed"),
+        }
+    }
+}
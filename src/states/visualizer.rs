@@ -1,18 +1,45 @@
 use crate::states::button_states::ToggleStates;
+use crate::states::library_index::LibraryIndex;
+use crate::states::pad_assignment::PadAssignment;
 use crate::states::playlist_data::PlaylistData;
+use crate::states::session::ResumeOffer;
 use crate::states::settings_data::SettingsData;
+use crate::states::trigger_mode::{SoundingKey, TriggerMode};
 use bon::bon;
 use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
+/// Number of pads on the controller grid, one page of albums
+pub const PADS_PER_PAGE: usize = 40;
+
 #[derive(Clone, Debug)]
 pub struct RuntimeData {
     pub settings_data: Arc<Mutex<SettingsData>>,
+    /// Audio files of the library folders, pads and keys resolve through it
+    pub library_index: Arc<Mutex<LibraryIndex>>,
+    /// Label of each pad, empty when no folder is assigned to it
     pub pad_labels: Vec<String>,
+    /// Pads whose assigned folder does not exist
+    pub missing_pads: Vec<usize>,
     pub knob_values: HashMap<u8, f32>,
     pub button_states: ToggleStates,
-    pub last_pad_pressed: Option<u8>,
+    pub last_pad_pressed: Option<usize>,
+    pub pad_page: usize,
+    pub key_labels: Vec<String>,
+    pub keyboard_bank: usize,
+    pub keyboard_bank_count: usize,
+    /// Trigger mode of the files played by the keys, the ones missing are one-shot
+    pub trigger_modes: HashMap<PathBuf, TriggerMode>,
+    pub sounding_ambience: Option<SoundingKey>,
+    pub sounding_sound_effect: Option<SoundingKey>,
     pub current_playlist: Option<PlaylistData>,
+    /// Music of the previous session, until it is resumed or dismissed
+    pub resume_offer: Option<ResumeOffer>,
+    /// Profile whose folders, scenes and mappings are loaded
+    pub profile: Option<String>,
+    /// Bumped each time the config file is reloaded after a change on disk
+    pub config_revision: usize,
 }
 
 #[bon]
@@ -20,15 +47,22 @@ impl RuntimeData {
     #[builder]
     pub fn new(
         settings_data: Arc<Mutex<SettingsData>>,
+        library_index: Arc<Mutex<LibraryIndex>>,
         pad_labels: Option<Vec<String>>,
         knob_values: Option<HashMap<u8, f32>>,
         button_states: Option<ToggleStates>,
-        last_pad_pressed: Option<u8>,
+        last_pad_pressed: Option<usize>,
+        pad_page: Option<usize>,
+        key_labels: Option<Vec<String>>,
+        keyboard_bank: Option<usize>,
         current_playlist: Option<PlaylistData>,
+        profile: Option<String>,
     ) -> Self {
         Self {
             settings_data,
-            pad_labels: pad_labels.unwrap_or_else(|| vec![String::new(); 40]),
+            library_index,
+            pad_labels: pad_labels.unwrap_or_else(|| vec![String::new(); PADS_PER_PAGE]),
+            missing_pads: vec![],
             knob_values: knob_values.unwrap_or_else(|| {
                 HashMap::from([
                     (1u8, 0.1),
@@ -43,17 +77,82 @@ impl RuntimeData {
             }),
             button_states: button_states.unwrap_or_default(),
             last_pad_pressed,
+            pad_page: pad_page.unwrap_or_default(),
+            key_labels: key_labels.unwrap_or_default(),
+            keyboard_bank: keyboard_bank.unwrap_or_default(),
+            keyboard_bank_count: 1,
+            trigger_modes: HashMap::new(),
+            sounding_ambience: None,
+            sounding_sound_effect: None,
             current_playlist,
+            resume_offer: None,
+            profile,
+            config_revision: 0,
         }
     }
 
     pub fn copy_data(&mut self, new_data: Self) {
         self.button_states = new_data.button_states;
         self.pad_labels = new_data.pad_labels;
+        self.missing_pads = new_data.missing_pads;
         self.button_states = new_data.button_states;
         self.settings_data = new_data.settings_data;
+        self.library_index = new_data.library_index;
         self.current_playlist = new_data.current_playlist;
         self.last_pad_pressed = new_data.last_pad_pressed;
+        self.pad_page = new_data.pad_page;
+        self.key_labels = new_data.key_labels;
+        self.keyboard_bank = new_data.keyboard_bank;
+        self.keyboard_bank_count = new_data.keyboard_bank_count;
+        self.trigger_modes = new_data.trigger_modes;
+        self.sounding_ambience = new_data.sounding_ambience;
+        self.sounding_sound_effect = new_data.sounding_sound_effect;
+        self.resume_offer = new_data.resume_offer;
+        self.profile = new_data.profile;
+        self.config_revision = new_data.config_revision;
+    }
+
+    /// Labels the pads after the folders assigned to them, the pad page stays in range
+    pub fn set_pad_assignments(&mut self, assignments: &[PadAssignment]) {
+        // Pads out of range are reported by the settings validation
+        let assignments: Vec<&PadAssignment> =
+            assignments.iter().filter(|x| x.is_pad_valid()).collect();
+        let pad_count = assignments
+            .iter()
+            .map(|x| x.pad + 1)
+            .max()
+            .unwrap_or_default();
+        self.pad_labels = vec![String::new(); pad_count];
+        self.missing_pads.clear();
+        // The first folder of a pad is the one played
+        for assignment in assignments {
+            let label = &mut self.pad_labels[assignment.pad];
+            if label.is_empty() {
+                *label = assignment.get_label();
+                if assignment.is_missing() {
+                    self.missing_pads.push(assignment.pad);
+                }
+            }
+        }
+        self.pad_page = self.pad_page.min(self.get_pad_page_count() - 1);
+    }
+
+    pub fn get_pad_page_count(&self) -> usize {
+        self.pad_labels.len().div_ceil(PADS_PER_PAGE).max(1)
+    }
+
+    /// Album index pointed by the given pad in the current page
+    pub const fn get_album_index(&self, pad: u8) -> usize {
+        self.pad_page * PADS_PER_PAGE + pad as usize
+    }
+
+    /// Pad showing the given album, if the album is in the current page
+    pub const fn get_pad_in_current_page(&self, album: usize) -> Option<u8> {
+        if album / PADS_PER_PAGE == self.pad_page {
+            Some((album % PADS_PER_PAGE) as u8)
+        } else {
+            None
+        }
     }
 
     pub fn get_music_volume(&self) -> Option<f32> {
@@ -67,3 +166,72 @@ impl RuntimeData {
         self.knob_values.get(&7).copied()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::states::pad_assignment::MAX_PAD_COUNT;
+
+    #[test]
+    fn pads_out_of_range_are_left_out() {
+        let mut data = RuntimeData::builder()
+            .settings_data(Arc::default())
+            .library_index(Arc::default())
+            .build();
+        let assignments = [
+            PadAssignment {
+                pad: usize::MAX,
+                folder: "too_far".to_string(),
+            },
+            PadAssignment {
+                pad: MAX_PAD_COUNT,
+                folder: "just_past".to_string(),
+            },
+            PadAssignment {
+                pad: 41,
+                folder: "01_Intro".to_string(),
+            },
+        ];
+        data.set_pad_assignments(&assignments);
+        assert_eq!(data.pad_labels.len(), 42);
+        assert_eq!(data.pad_labels[41], "Intro");
+        assert_eq!(data.missing_pads, [41]);
+        assert_eq!(data.get_pad_page_count(), 2);
+    }
+
+    #[test]
+    fn pads_follow_their_assignment_not_the_folder_order() {
+        let music = crate::get_test_folder("set_pad_assignments");
+        for name in ["01_Intro", "Tavern", "Battle"] {
+            std::fs::create_dir(music.join(name)).expect("The folder should be writable");
+        }
+        let folder = |name: &str| music.join(name).display().to_string();
+        let mut data = RuntimeData::builder()
+            .settings_data(Arc::default())
+            .library_index(Arc::default())
+            .pad_page(3)
+            .build();
+        let assignments = [
+            PadAssignment {
+                pad: 0,
+                folder: folder("Tavern"),
+            },
+            PadAssignment {
+                pad: 3,
+                folder: folder("01_Intro"),
+            },
+            PadAssignment {
+                pad: 3,
+                folder: folder("Battle"),
+            },
+            PadAssignment {
+                pad: 5,
+                folder: folder("02_Gone"),
+            },
+        ];
+        data.set_pad_assignments(&assignments);
+        assert_eq!(data.pad_labels, ["Tavern", "", "", "Intro", "", "Gone"]);
+        assert_eq!(data.missing_pads, [5]);
+        assert_eq!(data.pad_page, 0);
+    }
+}